@@ -0,0 +1,155 @@
+use serde::Serialize;
+use std::time::Duration;
+
+/// Throughput and latency percentiles for a benchmark run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchStats {
+    pub count: usize,
+    pub ops_per_sec: f64,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+// Nearest-rank percentile of an ascending list of latencies
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+pub fn summarize(mut latencies: Vec<Duration>, elapsed: Duration) -> BenchStats {
+    latencies.sort_unstable();
+    let elapsed_secs = elapsed.as_secs_f64();
+    BenchStats {
+        count: latencies.len(),
+        ops_per_sec: if elapsed_secs > 0.0 {
+            latencies.len() as f64 / elapsed_secs
+        } else {
+            0.0
+        },
+        p50: percentile(&latencies, 50.0),
+        p95: percentile(&latencies, 95.0),
+        p99: percentile(&latencies, 99.0),
+    }
+}
+
+impl std::fmt::Display for BenchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        write!(
+            f,
+            "{} reads, {:.1} ops/sec, latency p50 {:.2}ms p95 {:.2}ms p99 {:.2}ms",
+            self.count,
+            self.ops_per_sec,
+            ms(self.p50),
+            ms(self.p95),
+            ms(self.p99)
+        )
+    }
+}
+
+/// Outcome of a `write --repeat` run
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RepeatStats {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub elapsed: Duration,
+}
+
+/// `write --repeat` summary for `--output-format json`
+#[derive(Debug, Serialize)]
+pub struct RepeatJson {
+    pub writes: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub seconds: f64,
+    pub writes_per_sec: f64,
+}
+
+impl RepeatStats {
+    pub fn writes_per_sec(&self) -> f64 {
+        let elapsed_secs = self.elapsed.as_secs_f64();
+        if elapsed_secs > 0.0 {
+            (self.succeeded + self.failed) as f64 / elapsed_secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Summary object for `--output-format json`, with seconds to the millisecond and the
+    /// rate to one decimal place
+    pub fn json(&self) -> RepeatJson {
+        RepeatJson {
+            writes: self.succeeded + self.failed,
+            succeeded: self.succeeded,
+            failed: self.failed,
+            seconds: (self.elapsed.as_secs_f64() * 1000.0).round() / 1000.0,
+            writes_per_sec: (self.writes_per_sec() * 10.0).round() / 10.0,
+        }
+    }
+}
+
+impl std::fmt::Display for RepeatStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} writes in {:.2}s ({:.1} writes/sec): {} succeeded, {} failed",
+            self.succeeded + self.failed,
+            self.elapsed.as_secs_f64(),
+            self.writes_per_sec(),
+            self.succeeded,
+            self.failed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_percentiles() {
+        let latencies: Vec<Duration> = (1..=100).rev().map(Duration::from_millis).collect();
+        let stats = summarize(latencies, Duration::from_secs(2));
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.ops_per_sec, 50.0);
+        assert_eq!(stats.p50, Duration::from_millis(50));
+        assert_eq!(stats.p95, Duration::from_millis(95));
+        assert_eq!(stats.p99, Duration::from_millis(99));
+    }
+
+    #[test]
+    fn test_summarize_empty_and_display() {
+        let stats = summarize(Vec::new(), Duration::ZERO);
+        assert_eq!(stats.count, 0);
+        assert_eq!(stats.p99, Duration::ZERO);
+
+        let stats = summarize(vec![Duration::from_micros(1500)], Duration::from_secs(1));
+        assert_eq!(
+            stats.to_string(),
+            "1 reads, 1.0 ops/sec, latency p50 1.50ms p95 1.50ms p99 1.50ms"
+        );
+    }
+
+    #[test]
+    fn test_repeat_stats() {
+        let stats = RepeatStats {
+            succeeded: 9,
+            failed: 1,
+            elapsed: Duration::from_secs(2),
+        };
+        assert_eq!(stats.writes_per_sec(), 5.0);
+        assert_eq!(
+            stats.to_string(),
+            "10 writes in 2.00s (5.0 writes/sec): 9 succeeded, 1 failed"
+        );
+        assert_eq!(
+            serde_json::to_string(&stats.json()).unwrap(),
+            r#"{"writes":10,"succeeded":9,"failed":1,"seconds":2.0,"writes_per_sec":5.0}"#
+        );
+        assert_eq!(RepeatStats::default().writes_per_sec(), 0.0);
+    }
+}
@@ -1,5 +1,12 @@
+use crate::enron::MAX_ENRON_QTY;
+use crate::file_record::MAX_READ_RECORD_LEN;
+use crate::layout::{parse_layout_file, Layout};
+use crate::server::{parse_write_delay, WriteDelay};
+use crate::transform::{parse_transform_file, Transform};
+use crate::webhook::{parse_webhook_url, WebhookUrl};
 use clap::{Parser, Subcommand, ValueEnum};
-use std::{net::IpAddr, path::PathBuf};
+use std::collections::HashMap;
+use std::{net::IpAddr, path::PathBuf, time::Duration};
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum Parity {
@@ -18,6 +25,22 @@ impl From<Parity> for tokio_serial::Parity {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FlowControl {
+    None,
+    /// Hardware flow control on the RTS/CTS lines
+    Rtscts,
+}
+
+impl From<FlowControl> for tokio_serial::FlowControl {
+    fn from(flow_control: FlowControl) -> Self {
+        match flow_control {
+            FlowControl::None => tokio_serial::FlowControl::None,
+            FlowControl::Rtscts => tokio_serial::FlowControl::Hardware,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum StopBits {
     #[value(name = "1")]
@@ -58,6 +81,176 @@ impl From<DataBits> for tokio_serial::DataBits {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum TableStyle {
+    /// Aligned columns with a header underline
+    #[default]
+    Plain,
+    /// Markdown table for pasting into docs
+    Markdown,
+    /// Unicode box-drawing borders
+    Box,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable tables and messages
+    #[default]
+    Text,
+    /// One JSON object per result or failure, for scripts
+    Json,
+    /// The JSON results and failures as YAML documents, for tooling that prefers YAML
+    Yaml,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ValueFormat {
+    /// Unsigned 16-bit value per register
+    #[default]
+    U16,
+    /// IEEE 754 single-precision float across two registers
+    Float32,
+    /// Float32 as Schneider Modicon PLCs store it: low word first, each word big-endian (CDAB)
+    ModiconFloat,
+    /// Unix timestamp (seconds) across two registers, shown with its UTC date and time
+    Epoch32,
+    /// Signed 64-bit integer across four registers
+    Int64,
+    /// Unsigned 64-bit integer across four registers
+    Uint64,
+    /// IEEE 754 double-precision float across four registers
+    Float64,
+    /// Elapsed time in one register, counted in --duration-unit, shown as e.g. 2h 3m 5s
+    Duration,
+    /// Elapsed time across two registers, counted in --duration-unit
+    Duration32,
+    /// Gray-coded register (as from absolute encoders) converted to binary
+    Gray,
+    /// Packed BCD register, four decimal digits (0x1234 is 1234)
+    Bcd,
+    /// Coils packed into hex bytes, LSB first as on the wire (coil/discrete reads only)
+    Packed,
+    /// Classic hexdump: 8 registers per row in hex with an ASCII gutter (register reads only)
+    Hexdump,
+    /// Status board of one character per coil, rows of --columns (default 32), changes
+    /// highlighted between watch polls (coil/discrete reads only)
+    BoolMatrix,
+}
+
+/// What one count of a `--format duration` register stands for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DurationUnit {
+    /// Milliseconds
+    Ms,
+    /// Seconds
+    #[default]
+    Seconds,
+    /// Minutes
+    Minutes,
+}
+
+impl DurationUnit {
+    /// The time `count` of these units add up to
+    pub fn duration(self, count: u64) -> Duration {
+        match self {
+            DurationUnit::Ms => Duration::from_millis(count),
+            DurationUnit::Seconds => Duration::from_secs(count),
+            DurationUnit::Minutes => Duration::from_secs(count.saturating_mul(60)),
+        }
+    }
+}
+
+/// Initial contents of the server's holding registers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Pattern {
+    /// Each register holds its own address
+    #[default]
+    Address,
+    /// Rises evenly from 0 at the first register to 65535 at the last
+    Ramp,
+    /// 0, 65535, 0, 65535, ...
+    Alternating,
+    /// Pseudo-random values that repeat for the same --seed
+    Random,
+}
+
+/// Initial contents of a server's coils or discrete inputs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum BitPattern {
+    /// Every bit off
+    #[default]
+    AllOff,
+    /// Every bit on
+    AllOn,
+    /// On, off, on, off, ... from the first address
+    Alternating,
+}
+
+/// What the time column of a CSV capture counts from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TimeRef {
+    /// Wall-clock ISO 8601 UTC timestamps
+    Wall,
+    /// Seconds since the capture started
+    Start,
+}
+
+/// Most detailed kind of log message printed, from least to most chatty
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum LogLevel {
+    /// Failures only
+    Error,
+    /// Failures and warnings
+    Warn,
+    /// Connections and other notable events
+    #[default]
+    Info,
+    /// Every request and connection step (what --verbose prints)
+    Debug,
+    /// Debug plus the raw request the server decoded
+    Trace,
+}
+
+/// Order of the registers that make up a multi-register value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum WordOrder {
+    /// Most significant register first (Modbus convention)
+    #[default]
+    #[value(alias = "abcd")]
+    Big,
+    /// Least significant register first
+    #[value(alias = "cdab")]
+    Little,
+}
+
+impl ValueFormat {
+    /// Number of consecutive registers combined into one value
+    pub fn width(self) -> usize {
+        match self {
+            ValueFormat::U16
+            | ValueFormat::Duration
+            | ValueFormat::Gray
+            | ValueFormat::Bcd
+            | ValueFormat::Packed
+            | ValueFormat::Hexdump
+            | ValueFormat::BoolMatrix => 1,
+            ValueFormat::Float32
+            | ValueFormat::ModiconFloat
+            | ValueFormat::Epoch32
+            | ValueFormat::Duration32 => 2,
+            ValueFormat::Int64 | ValueFormat::Uint64 | ValueFormat::Float64 => 4,
+        }
+    }
+
+    /// Word order to combine registers in: the requested one, except for formats that fix it
+    pub fn word_order(self, requested: WordOrder) -> WordOrder {
+        match self {
+            ValueFormat::ModiconFloat => WordOrder::Little,
+            _ => requested,
+        }
+    }
+}
+
 // Custom validation functions for Modbus specification limits
 fn validate_coil_qty(s: &str) -> Result<u16, String> {
     let qty: u16 = s
@@ -73,6 +266,21 @@ fn validate_coil_qty(s: &str) -> Result<u16, String> {
     }
 }
 
+// Like `validate_coil_qty`, for the smaller limit of a coil write
+fn validate_coil_write_qty(s: &str) -> Result<u16, String> {
+    let qty: u16 = s
+        .parse()
+        .map_err(|_| format!("Invalid quantity '{s}': must be a number"))?;
+
+    if !(1..=MAX_WRITE_COILS as u16).contains(&qty) {
+        Err(format!(
+            "Invalid quantity {qty}: Modbus specification limits coil writes to 1-{MAX_WRITE_COILS} coils per request (FC 15)"
+        ))
+    } else {
+        Ok(qty)
+    }
+}
+
 fn validate_register_qty(s: &str) -> Result<u16, String> {
     let qty: u16 = s
         .parse()
@@ -87,8 +295,597 @@ fn validate_register_qty(s: &str) -> Result<u16, String> {
     }
 }
 
-/// Flags common to every subcommand
+/// MBAP unit id that addresses a Modbus TCP device itself rather than a slave behind it
+pub const MBAP_DIRECT_UNIT: u8 = 255;
+
+// Unit IDs for both client and server: 0 is broadcast and 248-255 are reserved
+fn validate_unit(s: &str) -> Result<u8, String> {
+    let s = s.trim();
+    let unit: u8 = s
+        .parse()
+        .map_err(|_| format!("Invalid unit '{s}': must be a number from 1 to 247"))?;
+
+    match unit {
+        1..=247 => Ok(unit),
+        0 => Err(
+            "Invalid unit 0: it is the broadcast address and devices never reply to it; use 1-247"
+                .to_string(),
+        ),
+        _ => Err(format!(
+            "Invalid unit {unit}: 248-255 are reserved by the Modbus specification; use 1-247"
+        )),
+    }
+}
+
+// Client `--unit`: as `validate_unit`, plus 255, which the Modbus TCP spec recommends as the
+// MBAP unit id of a device addressed directly rather than through a gateway. It is refused
+// at connect time for serial links, where 255 is reserved
+fn validate_client_unit(s: &str) -> Result<u8, String> {
+    match s.trim().parse::<u8>() {
+        Ok(MBAP_DIRECT_UNIT) => Ok(MBAP_DIRECT_UNIT),
+        _ => validate_unit(s),
+    }
+}
+
+/// Modbus data areas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Area {
+    Coil,
+    Discrete,
+    Holding,
+    Input,
+}
+
+impl Area {
+    /// Display name used in read summaries
+    pub fn label(self) -> &'static str {
+        match self {
+            Area::Coil => "Coils",
+            Area::Discrete => "Discrete inputs",
+            Area::Holding => "Holding registers",
+            Area::Input => "Input registers",
+        }
+    }
+
+    /// Lowercase name used in JSON output, matching the command-line spelling
+    pub fn key(self) -> &'static str {
+        match self {
+            Area::Coil => "coil",
+            Area::Discrete => "discrete",
+            Area::Holding => "holding",
+            Area::Input => "input",
+        }
+    }
+
+    /// Function code that reads this area
+    pub fn read_function_code(self) -> u8 {
+        match self {
+            Area::Coil => 0x01,
+            Area::Discrete => 0x02,
+            Area::Holding => 0x03,
+            Area::Input => 0x04,
+        }
+    }
+
+    /// Maximum quantity per read request allowed by the Modbus specification
+    pub fn max_read_qty(self) -> u16 {
+        match self {
+            Area::Coil | Area::Discrete => 2000,
+            Area::Holding | Area::Input => 125,
+        }
+    }
+}
+
+/// A named range from a device profile, e.g. `temperature = holding 100-104`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alias {
+    pub area: Area,
+    pub start: u16,
+    pub qty: u16,
+}
+
+impl Alias {
+    /// The `mb read` arguments that read this range
+    pub fn read_args(&self) -> Vec<String> {
+        vec![
+            self.area.key().to_string(),
+            "--addr".to_string(),
+            self.start.to_string(),
+            "--qty".to_string(),
+            self.qty.to_string(),
+        ]
+    }
+}
+
+/// Parse a device profile: one `name = area start[-end]` per line, with blank lines and
+/// `#` comments ignored
+pub fn parse_profile(text: &str) -> Result<HashMap<String, Alias>, String> {
+    let mut aliases = HashMap::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fail = |message: String| format!("Line {}: {message}", number + 1);
+        let (name, range) = line
+            .split_once('=')
+            .ok_or_else(|| fail(format!("expected `name = area start-end`, got '{line}'")))?;
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(fail(format!("invalid alias name '{name}'")));
+        }
+        let (area, addresses) = range
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| fail(format!("expected `area start-end` after '{name} ='")))?;
+        let area = Area::from_str(area, true).map_err(|_| {
+            fail(format!(
+                "unknown area '{area}'; use coil, discrete, holding or input"
+            ))
+        })?;
+        let parse_address = |text: &str| {
+            text.trim()
+                .parse::<u16>()
+                .map_err(|_| fail(format!("invalid address '{}'", text.trim())))
+        };
+        let (start, end) = match addresses.split_once('-') {
+            Some((start, end)) => (parse_address(start)?, parse_address(end)?),
+            None => (parse_address(addresses)?, parse_address(addresses)?),
+        };
+        if end < start {
+            return Err(fail(format!("range {start}-{end} ends before it starts")));
+        }
+        let alias = Alias {
+            area,
+            start,
+            qty: end - start + 1,
+        };
+        if aliases.insert(name.to_string(), alias).is_some() {
+            return Err(fail(format!("alias '{name}' is defined twice")));
+        }
+    }
+    Ok(aliases)
+}
+
+/// Value of `--addr` on read commands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressSpec {
+    /// A single starting address, combined with `--qty`
+    Start(u16),
+    /// A sorted, de-duplicated list of individual addresses
+    List(Vec<u16>),
+    /// One bit of a register, written `register.bit` (e.g. 100.3)
+    Bit(u16, u8),
+}
+
+// Parse `register.bit` (e.g. 100.3), the notation device manuals use for one bit of a register
+fn parse_register_bit(s: &str) -> Result<(u16, u8), String> {
+    let (register, bit) = s
+        .split_once('.')
+        .ok_or_else(|| format!("Invalid bit address '{s}': expected register.bit (e.g. 100.3)"))?;
+    let register = register.trim().parse::<u16>().map_err(|_| {
+        format!("Invalid address '{register}': must be a number between 0 and 65535")
+    })?;
+    let bit = bit
+        .trim()
+        .parse::<u8>()
+        .ok()
+        .filter(|&bit| bit < 16)
+        .ok_or_else(|| format!("Invalid bit '{bit}' in '{s}': registers have bits 0-15"))?;
+    Ok((register, bit))
+}
+
+/// `--addr` of a holding register write: a whole register, or one bit of it as `register.bit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteAddress {
+    pub register: u16,
+    pub bit: Option<u8>,
+}
+
+fn parse_write_address(s: &str) -> Result<WriteAddress, String> {
+    if s.contains('.') {
+        let (register, bit) = parse_register_bit(s)?;
+        return Ok(WriteAddress {
+            register,
+            bit: Some(bit),
+        });
+    }
+    let register = s
+        .trim()
+        .parse::<u16>()
+        .map_err(|_| format!("Invalid address '{s}': must be a number between 0 and 65535"))?;
+    Ok(WriteAddress {
+        register,
+        bit: None,
+    })
+}
+
+// Parse `--addr` as a single address or a comma list of addresses and ranges (e.g. 0,5,100-101)
+fn parse_address_spec(s: &str) -> Result<AddressSpec, String> {
+    let parse_address = |part: &str| {
+        part.trim()
+            .parse::<u16>()
+            .map_err(|_| format!("Invalid address '{part}': must be a number between 0 and 65535"))
+    };
+
+    if !s.contains([',', '-']) {
+        if s.contains('.') {
+            let (register, bit) = parse_register_bit(s)?;
+            return Ok(AddressSpec::Bit(register, bit));
+        }
+        return parse_address(s).map(AddressSpec::Start);
+    }
+
+    let mut addresses = std::collections::BTreeSet::new();
+    for part in s.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_address(first)?, parse_address(last)?);
+                if first > last {
+                    return Err(format!(
+                        "Invalid address range '{part}': start must not be greater than end"
+                    ));
+                }
+                addresses.extend(first..=last);
+            }
+            None => {
+                addresses.insert(parse_address(part)?);
+            }
+        }
+    }
+    Ok(AddressSpec::List(addresses.into_iter().collect()))
+}
+
+/// Most registers one FC 16 request can write
+pub const MAX_WRITE_REGISTERS: usize = 123;
+
+// Most coils one FC 15 request can write
+const MAX_WRITE_COILS: usize = 1968;
+
+/// Reject a contiguous range that runs past the end of the 16-bit address space
+pub fn check_address_range(start: u16, qty: usize) -> Result<(), String> {
+    let end = start as usize + qty;
+    if end > u16::MAX as usize + 1 {
+        return Err(format!(
+            "Address {start} with quantity {qty} ends at {}, past the last addressable register 65535; use a quantity of at most {} from {start}",
+            end - 1,
+            u16::MAX as usize + 1 - start as usize
+        ));
+    }
+    Ok(())
+}
+
+// Expand a single value into `qty` copies for fill writes
+pub fn expand_write_values(
+    values: Vec<u16>,
+    qty: Option<u16>,
+    area: Area,
+) -> Result<Vec<u16>, String> {
+    let values = match qty {
+        None => values,
+        Some(qty) if values.len() == 1 => vec![values[0]; qty as usize],
+        Some(qty) => {
+            return Err(format!(
+            "Cannot combine --qty {qty} with {} values: --qty fills a range with a single --value",
+            values.len()
+        ))
+        }
+    };
+
+    let (max, noun, function) = match area {
+        Area::Coil | Area::Discrete => (MAX_WRITE_COILS, "coils", "FC 15"),
+        Area::Holding | Area::Input => (MAX_WRITE_REGISTERS, "registers", "FC 16"),
+    };
+    if values.len() > max {
+        return Err(format!(
+            "Invalid quantity {}: Modbus specification limits multiple writes to 1-{max} {noun} per request ({function})",
+            values.len()
+        ));
+    }
+    Ok(values)
+}
+
+/// How the characters of `--bits` map onto coil addresses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum BitOrder {
+    /// The first character is the coil at --addr, like --value
+    #[default]
+    LsbFirst,
+    /// The string reads as a binary number: the last character is the coil at --addr
+    MsbFirst,
+}
+
+/// Coil values from `--bits`, in the order they were typed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitString(pub Vec<bool>);
+
+// Parse `--bits` such as 10110010, rejecting anything but 0 and 1
+fn parse_bit_string(s: &str) -> Result<BitString, String> {
+    if s.is_empty() {
+        return Err("Invalid bit string: must not be empty".to_string());
+    }
+    s.chars()
+        .map(|c| match c {
+            '0' => Ok(false),
+            '1' => Ok(true),
+            other => Err(format!("Invalid bit string '{s}': '{other}' is not 0 or 1")),
+        })
+        .collect::<Result<_, _>>()
+        .map(BitString)
+}
+
+// Arrange `--bits` so that index 0 is the coil at the starting address
+pub fn order_bits(bits: BitString, order: BitOrder) -> Vec<bool> {
+    let mut bits = bits.0;
+    if order == BitOrder::MsbFirst {
+        bits.reverse();
+    }
+    bits
+}
+
+// Parse a duration such as `500ms`, `2s` or `1m` (bare numbers are seconds)
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number.parse().map_err(|_| {
+        format!(
+            "Invalid duration '{s}': expected a number with an optional unit (e.g. 500ms, 2s, 1m)"
+        )
+    })?;
+
+    let duration = match unit {
+        "ms" => Duration::from_millis(number),
+        "" | "s" => Duration::from_secs(number),
+        "m" => Duration::from_secs(number.saturating_mul(60)),
+        _ => {
+            return Err(format!(
+                "Invalid duration unit '{unit}': use ms, s or m (e.g. 500ms, 2s, 1m)"
+            ))
+        }
+    };
+
+    if duration.is_zero() {
+        Err(format!("Invalid duration '{s}': must be greater than 0"))
+    } else {
+        Ok(duration)
+    }
+}
+
+// Parse a polling interval like `parse_duration`, no shorter than 100ms
+fn parse_watch_interval(s: &str) -> Result<Duration, String> {
+    let interval = parse_duration(s)?;
+    if interval < Duration::from_millis(100) {
+        Err(format!(
+            "Invalid interval '{}': watch interval must be at least 100ms to avoid flooding the device",
+            s.trim()
+        ))
+    } else {
+        Ok(interval)
+    }
+}
+
+/// Operator of a `--poll-until` condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl CompareOp {
+    // Two-character operators first so `>=` isn't read as `>` followed by `=100`
+    const ALL: [CompareOp; 6] = [
+        CompareOp::Eq,
+        CompareOp::Ne,
+        CompareOp::Ge,
+        CompareOp::Le,
+        CompareOp::Gt,
+        CompareOp::Lt,
+    ];
+
+    fn symbol(self) -> &'static str {
+        match self {
+            CompareOp::Eq => "==",
+            CompareOp::Ne => "!=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+        }
+    }
+}
+
+impl std::fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+/// Comparison for `--poll-until`, e.g. `>=100`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Condition {
+    pub op: CompareOp,
+    pub value: f64,
+}
+
+impl Condition {
+    pub fn holds(&self, value: f64) -> bool {
+        match self.op {
+            CompareOp::Eq => value == self.value,
+            CompareOp::Ne => value != self.value,
+            CompareOp::Gt => value > self.value,
+            CompareOp::Ge => value >= self.value,
+            CompareOp::Lt => value < self.value,
+            CompareOp::Le => value <= self.value,
+        }
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.op, self.value)
+    }
+}
+
+// Parse `--poll-until` as an operator followed by a number (e.g. `>=100`, `!= 0`, `<2.5`)
+fn parse_condition(s: &str) -> Result<Condition, String> {
+    let s = s.trim();
+    let op = CompareOp::ALL
+        .into_iter()
+        .find(|op| s.starts_with(op.symbol()))
+        .ok_or_else(|| {
+            format!("Invalid condition '{s}': start with ==, !=, >, >=, < or <= (e.g. >=100)")
+        })?;
+    let value = s[op.symbol().len()..]
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid condition '{s}': expected a number after '{op}'"))?;
+    Ok(Condition { op, value })
+}
+
+/// Flags common to every read subcommand
 #[derive(Debug, clap::Args)]
+pub struct ReadOptions {
+    /// Repeat the read at this interval until Ctrl+C (e.g. 500ms, 2s, 1m)
+    #[arg(long, value_name = "INTERVAL", value_parser = parse_watch_interval, display_order = 11)]
+    pub watch: Option<Duration>,
+
+    /// Stop watching after this many polls
+    #[arg(long, value_name = "N", requires = "watch", value_parser = clap::value_parser!(u64).range(1..), display_order = 12)]
+    pub count: Option<u64>,
+
+    /// Report only every Nth address, starting at --addr (--qty counts reported addresses)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..), display_order = 12)]
+    pub step: Option<u16>,
+
+    /// Show addresses counted from this base (0 or 1), for manuals that number registers from 1.
+    /// Display only: --addr and the addresses sent stay 0-based
+    #[arg(long, value_name = "BASE", default_value_t = 0, value_parser = clap::value_parser!(u16).range(0..=1), display_order = 12)]
+    pub address_base: u16,
+
+    /// With an address list or --step, read addresses more than N apart in separate requests
+    /// instead of one span covering the addresses between them
+    #[arg(long, value_name = "N", display_order = 12)]
+    pub max_gap: Option<u16>,
+
+    /// Warn when a watch poll takes longer than this many milliseconds
+    #[arg(long, value_name = "MS", requires = "watch", value_parser = clap::value_parser!(u64).range(1..), display_order = 12)]
+    pub max_latency: Option<u64>,
+
+    /// With --max-latency, stop with an error at the first slow poll
+    #[arg(long, requires = "max_latency", display_order = 12)]
+    pub fail_on_slow: bool,
+
+    /// Stop watching with exit 0 once every value read satisfies this condition (e.g. ">=100")
+    #[arg(long, value_name = "CONDITION", value_parser = parse_condition, requires = "watch", allow_hyphen_values = true, display_order = 12)]
+    pub poll_until: Option<Condition>,
+
+    /// With --poll-until, give up with a nonzero exit after this long (e.g. 60s, 5m)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, requires = "poll_until", display_order = 12)]
+    pub timeout_total: Option<Duration>,
+
+    /// Between watch polls, read one value every this many seconds so the connection never sits idle
+    #[arg(long, value_name = "SECS", requires = "watch", value_parser = clap::value_parser!(u64).range(1..), display_order = 12)]
+    pub keepalive: Option<u64>,
+
+    /// Include the round-trip time of each read in the summary line
+    #[arg(long, display_order = 13)]
+    pub timing: bool,
+
+    /// Disable thousands separators in verbose decimal columns
+    #[arg(long, display_order = 14)]
+    pub no_grouping: bool,
+
+    /// Swap the two bytes of each register before display (0x1234 shows as 0x3412)
+    #[arg(long, display_order = 15)]
+    pub byte_swap: bool,
+
+    /// How to decode and display values (float32/epoch32/int64/uint64/float64 for registers, packed for coils)
+    #[arg(long, value_enum, default_value = "u16", display_order = 16)]
+    pub format: ValueFormat,
+
+    /// Register order for multi-register formats
+    #[arg(long, value_enum, default_value = "big", display_order = 16)]
+    pub word_order: WordOrder,
+
+    /// What one count of a --format duration/duration32 value is (default: seconds)
+    #[arg(long, value_enum, value_name = "UNIT", display_order = 16)]
+    pub duration_unit: Option<DurationUnit>,
+
+    /// Decode the block as the named fields of a layout file (see README)
+    #[arg(long, value_name = "FILE", value_parser = parse_layout_file, display_order = 16)]
+    pub layout: Option<Layout>,
+
+    /// Enron/Daniel Modbus: every register is 32 bits, addresses count 32-bit registers and
+    /// --qty counts them too (max 62). Values are unsigned 32-bit integers, or floats with
+    /// --format float32, whose two halves follow --word-order
+    #[arg(long, display_order = 16)]
+    pub enron: bool,
+
+    /// Convert register values to engineering units with an address,scale,offset CSV file (see README)
+    #[arg(long, value_name = "FILE", value_parser = parse_transform_file, conflicts_with = "layout", display_order = 16)]
+    pub transform: Option<Transform>,
+
+    /// Write results to a CSV file instead of printing a table
+    #[arg(long, value_name = "FILE", display_order = 17)]
+    pub output: Option<PathBuf>,
+
+    /// With --output, start each CSV row with when it was read: a UTC timestamp (wall), or
+    /// seconds since the capture started (start), with the start time in a comment line
+    #[arg(
+        long,
+        value_enum,
+        value_name = "REF",
+        requires = "output",
+        display_order = 17
+    )]
+    pub time_ref: Option<TimeRef>,
+
+    /// Compare each value read against a CSV file of expected values (e.g. an earlier --output
+    /// capture) and fail with a table of the differences if any address doesn't match
+    #[arg(long, value_name = "FILE", display_order = 17)]
+    pub expect: Option<PathBuf>,
+
+    /// With --output, flush the CSV file once N rows are waiting rather than after every
+    /// poll: faster for fast polling, but a crash can lose up to N rows
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "output",
+        display_order = 17
+    )]
+    pub flush_interval: Option<u32>,
+
+    /// With --output, also print results as usual while writing the CSV file
+    #[arg(long, requires = "output", display_order = 17)]
+    pub also_stdout: bool,
+
+    /// Table style for results
+    #[arg(long, value_enum, default_value = "plain", display_order = 18)]
+    pub table_style: TableStyle,
+
+    /// Leave out the column header row of the table or CSV file; with --output, rows are
+    /// appended to the file so several runs build one CSV
+    #[arg(long, display_order = 18)]
+    pub no_header: bool,
+
+    /// Show coils in a grid this many wide, with the address at the start of each row (also
+    /// the row width of --format bool-matrix)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..=256), display_order = 18)]
+    pub columns: Option<u16>,
+
+    /// Skip the confirmation prompt before printing a large read to a terminal
+    #[arg(long, short, display_order = 19)]
+    pub yes: bool,
+}
+
+/// Flags common to every subcommand
+#[derive(Debug, Clone, clap::Args)]
 pub struct Common {
     /// Modbus TCP server IP address (TCP only)
     #[arg(long, value_parser = clap::value_parser!(IpAddr), conflicts_with = "device", display_order = 1)]
@@ -118,17 +915,83 @@ pub struct Common {
     #[arg(long, value_enum, default_value = "8", display_order = 7)]
     pub data_bits: DataBits,
 
-    /// Modbus slave / unit ID
-    #[arg(long, default_value_t = 0, display_order = 8)]
+    /// Flow control for serial communication (RTU only)
+    #[arg(long, value_enum, default_value = "none", display_order = 7)]
+    pub flow_control: FlowControl,
+
+    /// Raise RTS this many milliseconds before each request and drop it the same time after (RTU only)
+    #[arg(
+        long,
+        value_name = "MS",
+        requires = "device",
+        conflicts_with = "ip",
+        display_order = 7
+    )]
+    pub rts_delay: Option<u64>,
+
+    /// Print every RTU request and response frame in hex with its CRC16 check (RTU only)
+    #[arg(long, requires = "device", conflicts_with = "ip", display_order = 7)]
+    pub show_frame: bool,
+
+    /// Unit ID (1-247): the slave address on a serial line. Over TCP it is the MBAP unit
+    /// identifier, which a TCP-to-RTU gateway uses as the serial slave address to forward to;
+    /// 255 addresses a TCP device directly
+    #[arg(long, default_value_t = 1, value_parser = validate_client_unit, display_order = 8)]
     pub unit: u8,
 
     /// Timeout for connections and operations in seconds
     #[arg(long, default_value_t = 5, display_order = 9)]
     pub timeout: u64,
 
+    /// Retry operations the device answers with Server Device Busy or Acknowledge, which mean
+    /// "try again later"; other exceptions still fail at once
+    #[arg(long, display_order = 9)]
+    pub retry_on_exception: bool,
+
+    /// How many times to retry one operation: after Busy or Acknowledge with
+    /// --retry-on-exception, and after a timeout with --udp
+    #[arg(long, value_name = "N", default_value_t = 3, display_order = 9)]
+    pub retries: u32,
+
     /// Verbose output
     #[arg(long, short, display_order = 10)]
     pub verbose: bool,
+
+    /// Print only values, one per line (coils as 1/0), with no headers or messages
+    #[arg(long, short, conflicts_with = "verbose", display_order = 10)]
+    pub quiet: bool,
+
+    /// Print the bytes sent and received over the connection when the command ends
+    #[arg(long, display_order = 10)]
+    pub stats: bool,
+
+    /// Send Modbus TCP frames over UDP instead of a TCP connection (TCP only). UDP can drop
+    /// datagrams, so an operation with no reply is resent up to --retries times; keep --timeout short
+    #[arg(long, requires = "ip", display_order = 11)]
+    pub udp: bool,
+
+    /// MBAP protocol identifier to send instead of 0, for gateways that insist on another.
+    /// Any other value breaks Modbus TCP compliance; replies are accepted whatever they carry (TCP only)
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        requires = "ip",
+        hide_short_help = true,
+        display_order = 11
+    )]
+    pub protocol_id: u16,
+}
+
+impl Common {
+    /// Human-readable connection target (`ip:port` for TCP, device path for RTU)
+    pub fn target(&self) -> String {
+        match (&self.ip, &self.device) {
+            (Some(ip), _) => std::net::SocketAddr::new(*ip, self.port).to_string(),
+            (None, Some(device)) => device.display().to_string(),
+            (None, None) => "unknown".to_string(),
+        }
+    }
 }
 
 /// CLI entry point
@@ -140,16 +1003,73 @@ pub struct Common {
     after_help = "EXAMPLES:\n    mb read holding --ip 127.0.0.1 --port 502 --addr 1\n    mb read coils --ip 192.168.1.100 --addr 0 --qty 8\n    mb write holding --ip 127.0.0.1 --addr 100 --value 42\n    mb write coils --ip 127.0.0.1 --addr 0 --value 1,0,1,1\n    mb server --ip 0.0.0.0 --port 502"
 )]
 pub struct Cli {
+    /// Output format for read/write results and for failures
+    #[arg(long, value_enum, default_value = "text", global = true)]
+    pub output_format: OutputFormat,
+
+    /// Indent JSON output for reading by eye (implies --output-format json)
+    #[arg(long, global = true)]
+    pub json_pretty: bool,
+
+    /// Most detailed log messages to print; --verbose raises it to debug. Log messages are JSON
+    /// objects on stderr with --output-format json
+    #[arg(long, value_enum, default_value = "info", global = true)]
+    pub log_level: LogLevel,
+
     #[command(subcommand)]
     pub cmd: Command,
 }
 
+impl Cli {
+    /// Parse the plain read an alias stands for: `mb read <area> --addr --qty` followed by the
+    /// alias read's own options, keeping the global options given before `read`
+    pub fn alias_read(&self, alias: &Alias, args: &[String]) -> Result<Cli, clap::Error> {
+        let mut argv = vec!["mb".to_string()];
+        for (flag, value) in [
+            ("--output-format", self.output_format.to_possible_value()),
+            ("--log-level", self.log_level.to_possible_value()),
+        ] {
+            argv.push(flag.to_string());
+            argv.extend(value.map(|v| v.get_name().to_string()));
+        }
+        if self.json_pretty {
+            argv.push("--json-pretty".to_string());
+        }
+        argv.push("read".to_string());
+        argv.extend(alias.read_args());
+        argv.extend(args.iter().cloned());
+        Cli::try_parse_from(argv)
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Command {
     /// Read coils, discrete inputs, input or holding registers
+    #[command(
+        args_conflicts_with_subcommands = true,
+        subcommand_required = false,
+        arg_required_else_help = true
+    )]
     Read {
         #[command(subcommand)]
-        area: ReadArea,
+        area: Option<Box<ReadArea>>,
+
+        /// Read a named register group from --profile instead of naming an area
+        #[arg(long, requires = "profile")]
+        alias: Option<String>,
+
+        /// Device profile file mapping aliases to ranges (e.g. `temperature = holding 100-104`).
+        /// Give it before the read options, which are passed through to the aliased read
+        #[arg(long, requires = "alias")]
+        profile: Option<PathBuf>,
+
+        /// Read options for the alias, as for `mb read <area>` (e.g. --ip, --format, --watch)
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            requires = "alias"
+        )]
+        args: Vec<String>,
     },
 
     /// Write coils or holding registers
@@ -158,6 +1078,109 @@ pub enum Command {
         area: WriteArea,
     },
 
+    /// Read or write file records (FC 20/21)
+    File {
+        #[command(subcommand)]
+        op: FileOp,
+    },
+
+    /// Query a device's diagnostics (FC 8)
+    Diag {
+        #[command(subcommand)]
+        op: DiagOp,
+    },
+
+    /// Read the queue of values behind a FIFO pointer register (FC 24)
+    Fifo {
+        /// FIFO pointer address
+        #[arg(long = "addr", value_name = "ADDRESS", display_order = 11)]
+        pointer: u16,
+        #[command(flatten)]
+        common: Common,
+    },
+
+    /// Interactive shell for exploring a device over one connection
+    Repl {
+        #[command(flatten)]
+        common: Common,
+    },
+
+    /// Check that a device answers: exits 0 on a reply, nonzero otherwise
+    Ping {
+        #[command(flatten)]
+        common: Common,
+    },
+
+    /// Start the built-in server on a loopback port and check reads and writes of every area
+    /// against it through the client. Exits nonzero when any check fails.
+    Selftest {
+        /// Timeout for connections and operations in seconds
+        #[arg(long, default_value_t = 5)]
+        timeout: u64,
+
+        /// Print the requests and the server's log alongside the results
+        #[arg(long, short)]
+        verbose: bool,
+    },
+
+    /// Read back-to-back for a fixed duration and report throughput and latency.
+    /// Unlike `--watch` there is no pause between reads, so this can flood a slow device.
+    Bench {
+        /// Area to read
+        #[arg(long, value_enum, default_value = "holding", display_order = 11)]
+        area: Area,
+
+        /// Starting address
+        #[arg(long = "addr", default_value_t = 0, display_order = 12)]
+        addr: u16,
+
+        /// Quantity per read (max 2000 for coils/discrete inputs, 125 for registers)
+        #[arg(long = "qty", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..=2000), display_order = 13)]
+        qty: u16,
+
+        /// How long to run (e.g. 500ms, 10s, 1m)
+        #[arg(long, default_value = "10s", value_parser = parse_duration, display_order = 14)]
+        duration: Duration,
+
+        #[command(flatten)]
+        common: Common,
+    },
+
+    /// Read the same range from two devices and list the addresses whose values differ.
+    /// Exits 1 when any address differs, like diff(1).
+    Diff {
+        /// Area to compare
+        #[arg(value_enum)]
+        area: Area,
+
+        /// Starting address
+        #[arg(long = "addr", default_value_t = 0, display_order = 11)]
+        addr: u16,
+
+        /// Number of addresses to compare; larger ranges are read in several requests
+        #[arg(long = "qty", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..), display_order = 12)]
+        qty: u16,
+
+        /// IP address of device A
+        #[arg(long, conflicts_with_all = ["ip", "device"], display_order = 1)]
+        ip_a: IpAddr,
+
+        /// IP address of device B
+        #[arg(long, display_order = 1)]
+        ip_b: IpAddr,
+
+        /// Port of device A (default: --port)
+        #[arg(long, display_order = 2)]
+        port_a: Option<u16>,
+
+        /// Port of device B (default: --port)
+        #[arg(long, display_order = 2)]
+        port_b: Option<u16>,
+
+        #[command(flatten)]
+        common: Common,
+    },
+
     /// Run a Modbus server
     Server {
         /// IP address to bind to (TCP only)
@@ -188,8 +1211,12 @@ pub enum Command {
         #[arg(long, value_enum, default_value = "8", display_order = 7)]
         data_bits: DataBits,
 
-        /// Unit/Slave ID
-        #[arg(long, default_value_t = 1, display_order = 8)]
+        /// Flow control for serial communication (RTU only)
+        #[arg(long, value_enum, default_value = "none", display_order = 7)]
+        flow_control: FlowControl,
+
+        /// Unit/Slave ID to answer as (1-247); requests for other units get no response
+        #[arg(long, default_value_t = 1, value_parser = validate_unit, display_order = 8)]
         unit: u8,
 
         /// Number of coils (0-65535)
@@ -208,9 +1235,90 @@ pub enum Command {
         #[arg(long, default_value_t = 10000, display_order = 12)]
         num_input: u16,
 
+        /// Allocate memory only for written addresses, for cheap 65535-address areas
+        #[arg(long, display_order = 12)]
+        sparse: bool,
+
+        /// Initial contents of the holding registers
+        #[arg(long, value_enum, default_value = "address", display_order = 12)]
+        pattern: Pattern,
+
+        /// Seed for --pattern random
+        #[arg(long, default_value_t = 1, display_order = 12)]
+        seed: u64,
+
+        /// Initial contents of the coils
+        #[arg(long, value_enum, default_value = "all-off", display_order = 12)]
+        coil_pattern: BitPattern,
+
+        /// Initial contents of the discrete inputs
+        #[arg(long, value_enum, default_value = "all-off", display_order = 12)]
+        discrete_pattern: BitPattern,
+
+        /// Add up to this percent of full scale (0-100) of seeded noise to each holding register
+        /// of the pattern, so it looks like sensor data
+        #[arg(long, value_name = "PCT", default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=100), display_order = 12)]
+        jitter: u8,
+
         /// Verbose logging
         #[arg(long, display_order = 13)]
         verbose: bool,
+
+        /// Zero-pad reads that run past the end of an area instead of returning IllegalDataAddress
+        #[arg(long, display_order = 14)]
+        clamp_reads: bool,
+
+        /// Protocol address of the first element in every area (e.g. 40000)
+        #[arg(long, default_value_t = 0, display_order = 15)]
+        base_addr: u16,
+
+        /// Serve Prometheus metrics on this port at /metrics
+        #[arg(long, value_name = "PORT", display_order = 16)]
+        prometheus_port: Option<u16>,
+
+        /// POST a JSON description of every write to this http:// URL
+        #[arg(long, value_name = "URL", value_parser = parse_webhook_url, display_order = 17)]
+        webhook: Option<WebhookUrl>,
+
+        /// Close TCP connections that send nothing for this many seconds
+        #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..), display_order = 18)]
+        idle_timeout: Option<u64>,
+
+        /// Stream every read and write as Server-Sent Events on this port at /events
+        #[arg(long, value_name = "PORT", display_order = 19)]
+        events_port: Option<u16>,
+
+        /// Queue every value written to these holding registers for FIFO reads (FC 24)
+        #[arg(
+            long,
+            value_name = "ADDRESS",
+            value_delimiter = ',',
+            display_order = 20
+        )]
+        fifo: Vec<u16>,
+
+        /// Show the configured areas as a table of address ranges and sizes before serving
+        #[arg(long, display_order = 20)]
+        print_map: bool,
+
+        /// Wait this many milliseconds before applying and acknowledging each write; per area
+        /// with coil=MS,holding=MS
+        #[arg(long, value_name = "MS", value_parser = parse_write_delay, display_order = 20)]
+        write_delay: Option<WriteDelay>,
+
+        /// Answer the first N requests with a Server Device Busy exception, like a device that
+        /// is still starting up
+        #[arg(long, value_name = "N", display_order = 20)]
+        fail_first: Option<usize>,
+
+        /// Count this holding register up by one every second (wrapping at 65535), so clients
+        /// can poll it to see the server is alive
+        #[arg(long, value_name = "ADDR", display_order = 20)]
+        heartbeat_reg: Option<u16>,
+
+        /// Serve at most this many TCP clients at once, refusing further connections
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "device", display_order = 20)]
+        max_connections: Option<u32>,
     },
 }
 
@@ -218,50 +1326,417 @@ pub enum Command {
 pub enum ReadArea {
     /// Read Coils (FC 1)
     Coil {
-        /// Starting address
-        #[arg(long = "addr", value_name = "ADDRESS")]
-        start: u16,
+        /// Starting address, or a list of addresses and ranges (e.g. 0,5,100-101)
+        #[arg(long = "addr", value_name = "ADDRESS", value_parser = parse_address_spec)]
+        addr: AddressSpec,
         /// Quantity (default 1, max 2000)
-        #[arg(long = "qty", default_value_t = 1, value_parser = validate_coil_qty, display_order = 6)]
-        qty: u16,
+        #[arg(long = "qty", value_parser = validate_coil_qty, display_order = 6)]
+        qty: Option<u16>,
+        #[command(flatten)]
+        options: ReadOptions,
         #[command(flatten)]
         common: Common,
     },
     /// Read Discrete Inputs (FC 2)
     Discrete {
-        /// Starting address
-        #[arg(long = "addr", value_name = "ADDRESS")]
-        start: u16,
+        /// Starting address, or a list of addresses and ranges (e.g. 0,5,100-101)
+        #[arg(long = "addr", value_name = "ADDRESS", value_parser = parse_address_spec)]
+        addr: AddressSpec,
         /// Quantity (default 1, max 2000)
-        #[arg(long = "qty", default_value_t = 1, value_parser = validate_coil_qty, display_order = 6)]
-        qty: u16,
+        #[arg(long = "qty", value_parser = validate_coil_qty, display_order = 6)]
+        qty: Option<u16>,
+        #[command(flatten)]
+        options: ReadOptions,
         #[command(flatten)]
         common: Common,
     },
     /// Read Holding Registers (FC 3)
     Holding {
-        /// Starting address
-        #[arg(long = "addr", value_name = "ADDRESS")]
-        start: u16,
+        /// Starting address, or a list of addresses and ranges (e.g. 0,5,100-101)
+        #[arg(long = "addr", value_name = "ADDRESS", value_parser = parse_address_spec)]
+        addr: AddressSpec,
         /// Quantity (default 1, max 125)
-        #[arg(long = "qty", default_value_t = 1, value_parser = validate_register_qty, display_order = 6)]
-        qty: u16,
+        #[arg(long = "qty", value_parser = validate_register_qty, display_order = 6)]
+        qty: Option<u16>,
+        #[command(flatten)]
+        options: ReadOptions,
         #[command(flatten)]
         common: Common,
     },
     /// Read Input Registers (FC 4)
     Input {
-        /// Starting address
-        #[arg(long = "addr", value_name = "ADDRESS")]
-        start: u16,
+        /// Starting address, or a list of addresses and ranges (e.g. 0,5,100-101)
+        #[arg(long = "addr", value_name = "ADDRESS", value_parser = parse_address_spec)]
+        addr: AddressSpec,
         /// Quantity (default 1, max 125)
-        #[arg(long = "qty", default_value_t = 1, value_parser = validate_register_qty, display_order = 6)]
-        qty: u16,
+        #[arg(long = "qty", value_parser = validate_register_qty, display_order = 6)]
+        qty: Option<u16>,
+        #[command(flatten)]
+        options: ReadOptions,
         #[command(flatten)]
         common: Common,
     },
 }
 
+impl ReadArea {
+    /// Resolve the subcommand into a read request, rejecting conflicting flags
+    pub fn into_request(self) -> Result<ReadRequest, String> {
+        let (area, addr, qty, options, common) = match self {
+            ReadArea::Coil {
+                addr,
+                qty,
+                options,
+                common,
+            } => (Area::Coil, addr, qty, options, common),
+            ReadArea::Discrete {
+                addr,
+                qty,
+                options,
+                common,
+            } => (Area::Discrete, addr, qty, options, common),
+            ReadArea::Holding {
+                addr,
+                qty,
+                options,
+                common,
+            } => (Area::Holding, addr, qty, options, common),
+            ReadArea::Input {
+                addr,
+                qty,
+                options,
+                common,
+            } => (Area::Input, addr, qty, options, common),
+        };
+
+        if let Some(layout) = &options.layout {
+            if matches!(area, Area::Coil | Area::Discrete) {
+                return Err("--layout applies only to holding and input register reads".to_string());
+            }
+            if matches!(addr, AddressSpec::List(_)) {
+                return Err("--layout needs a contiguous block; use a single --addr".to_string());
+            }
+            if options.format != ValueFormat::U16 {
+                return Err("--layout cannot be combined with --format".to_string());
+            }
+            let size = layout.size();
+            if size > area.max_read_qty() as usize {
+                return Err(format!(
+                    "Layout covers {size} registers, more than the {} allowed in one read",
+                    area.max_read_qty()
+                ));
+            }
+            if let Some(qty) = qty.filter(|&qty| qty as usize != size) {
+                return Err(format!(
+                    "--qty {qty} does not match the layout size of {size} registers"
+                ));
+            }
+        }
+        let layout_size = options.layout.as_ref().map(|layout| layout.size() as u16);
+
+        if options.transform.is_some() {
+            if matches!(area, Area::Coil | Area::Discrete) {
+                return Err(
+                    "--transform applies only to holding and input register reads".to_string(),
+                );
+            }
+            if matches!(addr, AddressSpec::Bit(..)) || options.format == ValueFormat::Hexdump {
+                return Err(
+                    "--transform needs register values; it cannot be combined with register.bit or --format hexdump"
+                        .to_string(),
+                );
+            }
+        }
+
+        if options.duration_unit.is_some()
+            && !matches!(
+                options.format,
+                ValueFormat::Duration | ValueFormat::Duration32
+            )
+        {
+            return Err(
+                "--duration-unit applies only to --format duration and duration32".to_string(),
+            );
+        }
+
+        if let (Some(keepalive), Some(watch)) = (options.keepalive, options.watch) {
+            if Duration::from_secs(keepalive) >= watch {
+                return Err(format!(
+                    "--keepalive {keepalive}s must be shorter than the --watch interval"
+                ));
+            }
+        }
+
+        let mut bit = None;
+        let (start, qty, addresses) = match addr {
+            AddressSpec::Start(start) => (start, qty.or(layout_size).unwrap_or(1), None),
+            AddressSpec::Bit(register, register_bit) => {
+                if matches!(area, Area::Coil | Area::Discrete) {
+                    return Err(
+                        "--addr register.bit applies only to holding and input registers"
+                            .to_string(),
+                    );
+                }
+                if qty.is_some()
+                    || options.layout.is_some()
+                    || options.step.is_some()
+                    || options.format != ValueFormat::U16
+                {
+                    return Err(
+                        "--addr register.bit reads a single bit and cannot be combined with --qty, --layout, --step or --format"
+                            .to_string(),
+                    );
+                }
+                bit = Some(register_bit);
+                (register, 1, None)
+            }
+            AddressSpec::List(addresses) => {
+                if qty.is_some() {
+                    return Err(
+                        "--qty cannot be combined with an address list; list every address or range in --addr"
+                            .to_string(),
+                    );
+                }
+                (addresses[0], addresses.len() as u16, Some(addresses))
+            }
+        };
+
+        // A stepped read becomes an address list, so it is bulk-read and filtered like one
+        let addresses = match options.step.filter(|&step| step > 1) {
+            Some(step) => {
+                if addresses.is_some() {
+                    return Err("--step cannot be combined with an address list".to_string());
+                }
+                if options.layout.is_some() || options.format != ValueFormat::U16 {
+                    return Err("--step applies only to single-register values".to_string());
+                }
+                let last = start as u32 + (qty as u32 - 1) * step as u32;
+                if last > u16::MAX as u32 {
+                    return Err(format!(
+                        "--qty {qty} with --step {step} from address {start} runs past address 65535"
+                    ));
+                }
+                Some((0..qty).map(|i| start + i * step).collect())
+            }
+            None => addresses,
+        };
+        if options.time_ref == Some(TimeRef::Start) && options.no_header {
+            return Err(
+                "--time-ref start restarts at 0 every run, so appended runs can't be told apart; use --time-ref wall with --no-header"
+                    .to_string(),
+            );
+        }
+        if options.max_gap.is_some() && addresses.is_none() {
+            return Err(
+                "--max-gap applies only to an address list or --step; a contiguous range is one read"
+                    .to_string(),
+            );
+        }
+        if options.enron {
+            if matches!(area, Area::Coil | Area::Discrete) {
+                return Err("--enron applies only to holding and input register reads".to_string());
+            }
+            if addresses.is_some() || bit.is_some() {
+                return Err(
+                    "--enron needs a contiguous range; use a single --addr with --qty".to_string(),
+                );
+            }
+            if options.layout.is_some() || options.transform.is_some() {
+                return Err("--enron cannot be combined with --layout or --transform".to_string());
+            }
+            if !matches!(options.format, ValueFormat::U16 | ValueFormat::Float32) {
+                return Err(
+                    "--enron registers are 32 bits; use the default format or --format float32"
+                        .to_string(),
+                );
+            }
+            if qty > MAX_ENRON_QTY {
+                return Err(format!(
+                    "Invalid quantity {qty}: an Enron read returns at most {MAX_ENRON_QTY} 32-bit registers"
+                ));
+            }
+        }
+        if addresses.is_none() {
+            check_address_range(start, qty as usize)?;
+        }
+
+        if options.columns.is_some() {
+            if matches!(area, Area::Holding | Area::Input) {
+                return Err("--columns applies only to coil and discrete input reads".to_string());
+            }
+            if addresses.is_some() {
+                return Err("--columns needs a contiguous range; use --addr with --qty".to_string());
+            }
+            if options.format == ValueFormat::Packed {
+                return Err("--columns cannot be combined with --format packed".to_string());
+            }
+        }
+
+        match (area, options.format) {
+            (Area::Coil | Area::Discrete, format)
+                if format.width() > 1
+                    || matches!(
+                        format,
+                        ValueFormat::Hexdump | ValueFormat::Gray | ValueFormat::Bcd
+                    ) =>
+            {
+                return Err(format!(
+                    "--format {} applies only to holding and input register reads",
+                    format.to_possible_value().unwrap().get_name()
+                ));
+            }
+            (
+                Area::Holding | Area::Input,
+                format @ (ValueFormat::Packed | ValueFormat::BoolMatrix),
+            ) => {
+                return Err(format!(
+                    "--format {} applies only to coil and discrete input reads",
+                    format.to_possible_value().unwrap().get_name()
+                ));
+            }
+            (
+                _,
+                format @ (ValueFormat::Packed | ValueFormat::Hexdump | ValueFormat::BoolMatrix),
+            ) if addresses.is_some() => {
+                return Err(format!(
+                    "--format {} needs a contiguous range; use --addr with --qty",
+                    format.to_possible_value().unwrap().get_name()
+                ));
+            }
+            _ => {}
+        }
+
+        Ok(ReadRequest {
+            area,
+            start,
+            qty,
+            addresses,
+            bit,
+            options,
+            common,
+        })
+    }
+}
+
+/// A read subcommand resolved into its area, addresses and flags
+#[derive(Debug)]
+pub struct ReadRequest {
+    pub area: Area,
+    pub start: u16,
+    pub qty: u16,
+    /// Explicit addresses when `--addr` is a list; `None` for a contiguous `start`/`qty` read
+    pub addresses: Option<Vec<u16>>,
+    /// The one bit of the `start` register to show, for `--addr register.bit`
+    pub bit: Option<u8>,
+    pub options: ReadOptions,
+    pub common: Common,
+}
+
+/// Row width of `--format bool-matrix` without `--columns`
+pub const BOOL_MATRIX_COLUMNS: u16 = 32;
+
+/// Reads printing more rows than this to a terminal ask for confirmation first
+pub const BIG_READ_ROWS: u16 = 500;
+
+impl ReadRequest {
+    /// Number of table rows the read prints
+    pub fn printed_rows(&self) -> u16 {
+        match (self.options.columns, self.options.format) {
+            (Some(columns), _) => self.qty.div_ceil(columns),
+            (None, ValueFormat::BoolMatrix) => self.qty.div_ceil(BOOL_MATRIX_COLUMNS),
+            (None, _) => self.qty,
+        }
+    }
+
+    /// Whether the read prints enough rows to need confirming (the caller checks for a terminal)
+    pub fn needs_confirmation(&self) -> bool {
+        let to_terminal = self.options.output.is_none() || self.options.also_stdout;
+        self.printed_rows() > BIG_READ_ROWS && !self.options.yes && to_terminal
+    }
+
+    /// Whether the read prints one `addr: value` line instead of a summary and table: a single
+    /// plain value, not verbose, quiet or timed (JSON output is the caller's to check)
+    pub fn is_compact(&self) -> bool {
+        let options = &self.options;
+        self.qty == 1
+            && self.bit.is_none()
+            && !self.common.verbose
+            && !self.common.quiet
+            && !options.timing
+            && (options.output.is_none() || options.also_stdout)
+            && options.layout.is_none()
+            && options.transform.is_none()
+            && options.format != ValueFormat::Hexdump
+    }
+
+    /// Whether a returned address was asked for (disjoint reads cover gaps in bulk)
+    pub fn is_requested(&self, addr: u16) -> bool {
+        self.addresses
+            .as_ref()
+            .is_none_or(|addresses| addresses.binary_search(&addr).is_ok())
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DiagOp {
+    /// Read the bus and server counters (sub-functions 11-18): messages, CRC errors,
+    /// exceptions, busy replies and overruns, for judging the quality of a serial link
+    Counters {
+        #[command(flatten)]
+        common: Common,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum FileOp {
+    /// Read registers from a file record (FC 20)
+    Read {
+        /// File number
+        #[arg(long, value_parser = clap::value_parser!(u16).range(1..), display_order = 11)]
+        file: u16,
+        /// Starting record number within the file
+        #[arg(long, value_parser = clap::value_parser!(u16).range(0..=9999), display_order = 12)]
+        record: u16,
+        /// Number of registers to read (max 124)
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..=MAX_READ_RECORD_LEN as i64), display_order = 13)]
+        length: u16,
+        #[command(flatten)]
+        common: Common,
+    },
+    /// Write registers to a file record (FC 21)
+    Write {
+        /// File number
+        #[arg(long, value_parser = clap::value_parser!(u16).range(1..), display_order = 11)]
+        file: u16,
+        /// Starting record number within the file
+        #[arg(long, value_parser = clap::value_parser!(u16).range(0..=9999), display_order = 12)]
+        record: u16,
+        /// Register value(s) to write (comma-separated, max 122)
+        #[arg(
+            long = "value",
+            value_delimiter = ',',
+            num_args = 1..,
+            required = true,
+            value_parser = clap::value_parser!(u16),
+            display_order = 13
+        )]
+        values: Vec<u16>,
+        #[command(flatten)]
+        common: Common,
+    },
+}
+
+/// Options for issuing the same write many times, shared by the write commands
+#[derive(Debug, Clone, clap::Args)]
+pub struct RepeatOptions {
+    /// Issue the write this many times over one connection and report throughput
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub repeat: Option<u32>,
+
+    /// With --repeat, pause this long between writes (e.g. 500ms, 2s)
+    #[arg(long, value_parser = parse_watch_interval, requires = "repeat")]
+    pub interval: Option<Duration>,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum WriteArea {
     /// Write Single/Multiple Coils (FC 5/15)
@@ -274,27 +1749,57 @@ pub enum WriteArea {
             long = "value",
             value_delimiter = ',',
             num_args = 1..,
-            required = true,
+            required_unless_present = "bits",
+            conflicts_with = "bits",
             value_parser = clap::value_parser!(u16)
         )]
         values: Vec<u16>,
+        /// Coil values as a string of 0s and 1s (e.g. 10110010)
+        #[arg(long, value_parser = parse_bit_string)]
+        bits: Option<BitString>,
+        /// Which end of --bits is the coil at --addr
+        #[arg(long, value_enum, default_value = "lsb-first", requires = "bits")]
+        bit_order: BitOrder,
+        /// Write the single --value to this many consecutive coils (max 1968)
+        #[arg(long = "qty", value_parser = validate_coil_write_qty, conflicts_with = "bits")]
+        qty: Option<u16>,
+        #[command(flatten)]
+        repeat: RepeatOptions,
         #[command(flatten)]
         common: Common,
     },
     /// Write Single/Multiple Holding Registers (FC 6/16)
     Holding {
-        /// Starting address
-        #[arg(long = "addr", value_name = "ADDRESS")]
-        start: u16,
-        /// Value(s) to write (comma-separated for multiple)
+        /// Starting address, or `register.bit` (e.g. 100.3) to set or clear one bit
+        #[arg(long = "addr", value_name = "ADDRESS", value_parser = parse_write_address, required_unless_present = "from")]
+        start: Option<WriteAddress>,
+        /// Value(s) to write (comma-separated for multiple), encoded per --format; `-` reads them from stdin
         #[arg(
             long = "value",
             value_delimiter = ',',
-            num_args = 1..,
-            required = true,
-            value_parser = clap::value_parser!(u16)
+            num_args = 1,
+            required_unless_present = "from",
+            allow_hyphen_values = true
         )]
-        values: Vec<u16>,
+        values: Vec<String>,
+        /// Take the start address and values from a JSON file: {"start":100,"values":[1,2,3]},
+        /// optionally with a "format", or a holding register read saved with --output-format json
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["start", "values", "qty"])]
+        from: Option<PathBuf>,
+        /// Write the single --value to this many consecutive registers (max 123)
+        #[arg(long = "qty", value_parser = validate_register_qty)]
+        qty: Option<u16>,
+        /// How to encode each value (float32/epoch32/int64/uint64/float64 span several registers)
+        #[arg(long, value_enum, default_value = "u16")]
+        format: ValueFormat,
+        /// Register order for multi-register formats, as for reads
+        #[arg(long, value_enum, default_value = "big")]
+        word_order: WordOrder,
+        /// Swap the two bytes of each register after encoding, as for reads
+        #[arg(long)]
+        byte_swap: bool,
+        #[command(flatten)]
+        repeat: RepeatOptions,
         #[command(flatten)]
         common: Common,
     },
@@ -332,6 +1837,14 @@ mod tests {
         assert!(result.unwrap_err().contains("must be a number"));
     }
 
+    #[test]
+    fn test_validate_coil_write_qty() {
+        assert_eq!(validate_coil_write_qty("1968"), Ok(1968));
+        assert!(validate_coil_write_qty("0").is_err());
+        let error_msg = validate_coil_write_qty("1969").unwrap_err();
+        assert!(error_msg.contains("1-1968 coils per request (FC 15)"));
+    }
+
     #[test]
     fn test_validate_register_qty_valid() {
         assert_eq!(validate_register_qty("1"), Ok(1));
@@ -359,4 +1872,1036 @@ mod tests {
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("must be a number"));
     }
+
+    #[test]
+    fn test_parse_address_spec_single() {
+        assert_eq!(parse_address_spec("0"), Ok(AddressSpec::Start(0)));
+        assert_eq!(parse_address_spec("65535"), Ok(AddressSpec::Start(65535)));
+        assert!(parse_address_spec("65536").is_err());
+        assert!(parse_address_spec("abc").is_err());
+    }
+
+    #[test]
+    fn test_parse_address_spec_list() {
+        assert_eq!(
+            parse_address_spec("0,5,100-101"),
+            Ok(AddressSpec::List(vec![0, 5, 100, 101]))
+        );
+        // Sorted and de-duplicated
+        assert_eq!(
+            parse_address_spec("7,3-5,4"),
+            Ok(AddressSpec::List(vec![3, 4, 5, 7]))
+        );
+        assert!(parse_address_spec("5-3").is_err());
+        assert!(parse_address_spec("1,,2").is_err());
+        assert!(parse_address_spec("1-x").is_err());
+    }
+
+    #[test]
+    fn test_read_request_rejects_qty_with_address_list() {
+        let cli = Cli::try_parse_from([
+            "mb",
+            "read",
+            "holding",
+            "--ip",
+            "127.0.0.1",
+            "--addr",
+            "0,5",
+            "--qty",
+            "2",
+        ])
+        .unwrap();
+        let Command::Read {
+            area: Some(area), ..
+        } = cli.cmd
+        else {
+            panic!("Expected read command");
+        };
+        assert!(area.into_request().is_err());
+    }
+
+    #[test]
+    fn test_max_latency_requires_watch() {
+        let args = ["mb", "read", "holding", "--ip", "127.0.0.1", "--addr", "0"];
+        let parse = |extra: &[&str]| Cli::try_parse_from(args.iter().chain(extra));
+        assert!(parse(&["--max-latency", "200"]).is_err());
+        assert!(parse(&["--watch", "1s", "--max-latency", "200"]).is_ok());
+        assert!(parse(&["--watch", "1s", "--fail-on-slow"]).is_err());
+        assert!(parse(&["--watch", "1s", "--max-latency", "200", "--fail-on-slow"]).is_ok());
+    }
+
+    #[test]
+    fn test_output_format_is_accepted_anywhere() {
+        let args = ["mb", "read", "holding", "--ip", "127.0.0.1", "--addr", "0"];
+        let default = Cli::try_parse_from(args).unwrap();
+        assert_eq!(default.output_format, OutputFormat::Text);
+
+        let after = Cli::try_parse_from(args.iter().chain(&["--output-format", "json"])).unwrap();
+        assert_eq!(after.output_format, OutputFormat::Json);
+        let before =
+            Cli::try_parse_from(["mb", "--output-format", "json"].iter().chain(&args[1..]))
+                .unwrap();
+        assert_eq!(before.output_format, OutputFormat::Json);
+    }
+
+    #[test]
+    fn test_parse_profile() {
+        let aliases = parse_profile(
+            "# PLC-5 profile\n\ntemperature = holding 100-104\nrunning = coil 7  # motor\n",
+        )
+        .unwrap();
+        assert_eq!(
+            aliases["temperature"],
+            Alias {
+                area: Area::Holding,
+                start: 100,
+                qty: 5
+            }
+        );
+        assert_eq!(aliases["running"].qty, 1);
+        assert_eq!(
+            aliases["temperature"].read_args(),
+            ["holding", "--addr", "100", "--qty", "5"]
+        );
+
+        assert!(parse_profile("temperature holding 100").is_err());
+        assert!(parse_profile("t = registers 100").is_err());
+        assert!(parse_profile("t = holding 104-100").is_err());
+        assert!(parse_profile("t = holding 1\nt = coil 2")
+            .unwrap_err()
+            .starts_with("Line 2:"));
+    }
+
+    #[test]
+    fn test_read_alias_passes_options_through() {
+        let args = ["mb", "read", "--alias", "t", "--profile", "plc.profile"];
+        let cli = Cli::try_parse_from(args.iter().chain(&["--ip", "10.0.0.1", "-v"])).unwrap();
+        let Command::Read {
+            area: None,
+            alias,
+            args,
+            ..
+        } = cli.cmd
+        else {
+            panic!("Expected an alias read");
+        };
+        assert_eq!(alias.as_deref(), Some("t"));
+        assert_eq!(args, ["--ip", "10.0.0.1", "-v"]);
+        assert!(Cli::try_parse_from(["mb", "read", "--alias", "t"]).is_err());
+    }
+
+    #[test]
+    fn test_alias_read_keeps_global_options() {
+        let alias = Alias {
+            area: Area::Holding,
+            start: 100,
+            qty: 5,
+        };
+        let options = ["--ip".to_string(), "10.0.0.1".to_string()];
+        let read = |args: &[&str]| {
+            let args = ["mb"].iter().chain(args).chain(&["read", "--alias", "t"]);
+            let cli = Cli::try_parse_from(args.chain(&["--profile", "plc.profile"])).unwrap();
+            cli.alias_read(&alias, &options).unwrap()
+        };
+
+        let cli = read(&["--json-pretty", "--log-level", "debug"]);
+        assert!(cli.json_pretty);
+        assert_eq!(cli.log_level, LogLevel::Debug);
+        assert!(matches!(cli.cmd, Command::Read { area: Some(_), .. }));
+        assert_eq!(
+            read(&["--output-format", "yaml"]).output_format,
+            OutputFormat::Yaml
+        );
+
+        // Given after the alias, they are among the read's own options
+        let cli = read(&[]);
+        let after = [options.as_slice(), &["--json-pretty".to_string()]].concat();
+        assert!(cli.alias_read(&alias, &after).unwrap().json_pretty);
+    }
+
+    #[test]
+    fn test_diff_takes_two_devices() {
+        let args = [
+            "mb", "diff", "holding", "--ip-a", "10.0.0.1", "--ip-b", "10.0.0.2",
+        ];
+        let cli = Cli::try_parse_from(args.iter().chain(&["--qty", "100"])).unwrap();
+        let Command::Diff {
+            area, qty, ip_b, ..
+        } = cli.cmd
+        else {
+            panic!("Expected diff command");
+        };
+        assert_eq!((area, qty), (Area::Holding, 100));
+        assert_eq!(ip_b, "10.0.0.2".parse::<IpAddr>().unwrap());
+
+        assert!(Cli::try_parse_from(args.iter().chain(&["--ip", "10.0.0.3"])).is_err());
+        assert!(Cli::try_parse_from(&args[..5]).is_err());
+    }
+
+    #[test]
+    fn test_check_address_range() {
+        assert!(check_address_range(0, 65536).is_ok());
+        assert!(check_address_range(65535, 1).is_ok());
+        let message = check_address_range(65000, 1000).unwrap_err();
+        assert!(message.contains("ends at 65999"));
+        assert!(message.contains("65535"));
+        assert!(message.contains("at most 536"));
+    }
+
+    #[test]
+    fn test_read_request_rejects_range_past_65535() {
+        let args = ["mb", "read", "coil", "--ip", "127.0.0.1", "--addr"];
+        let request = |extra: &[&str]| {
+            let cli = Cli::try_parse_from(args.iter().chain(extra)).unwrap();
+            let Command::Read {
+                area: Some(area), ..
+            } = cli.cmd
+            else {
+                panic!("Expected read command");
+            };
+            area.into_request()
+        };
+        assert!(request(&["65000", "--qty", "1000"]).is_err());
+        assert!(request(&["64536", "--qty", "1000"]).is_ok());
+        // Address lists are always in range
+        assert!(request(&["65534,65535"]).is_ok());
+    }
+
+    #[test]
+    fn test_write_repeat_options() {
+        let args = [
+            "mb",
+            "write",
+            "holding",
+            "--ip",
+            "127.0.0.1",
+            "--addr",
+            "0",
+            "--value",
+            "1",
+        ];
+        let parse = |extra: &[&str]| Cli::try_parse_from(args.iter().chain(extra));
+        let Command::Write {
+            area: WriteArea::Holding { repeat, .. },
+        } = parse(&["--repeat", "100", "--interval", "200ms"])
+            .unwrap()
+            .cmd
+        else {
+            panic!("Expected holding write");
+        };
+        assert_eq!(repeat.repeat, Some(100));
+        assert_eq!(repeat.interval, Some(Duration::from_millis(200)));
+
+        assert!(parse(&["--interval", "1s"]).is_err());
+        assert!(parse(&["--repeat", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_condition() {
+        let condition = parse_condition(">=100").unwrap();
+        assert_eq!(condition.op, CompareOp::Ge);
+        assert!(condition.holds(100.0) && condition.holds(101.0) && !condition.holds(99.0));
+        assert_eq!(condition.to_string(), ">=100");
+
+        assert!(parse_condition(">100").unwrap().holds(100.5));
+        assert!(parse_condition("!= 0").unwrap().holds(1.0));
+        assert!(parse_condition("<-2.5").unwrap().holds(-3.0));
+        assert!(parse_condition("<=1").unwrap().holds(1.0));
+        assert!(parse_condition("==7").unwrap().holds(7.0));
+
+        assert!(parse_condition("100").is_err());
+        assert!(parse_condition(">=").is_err());
+        assert!(parse_condition("=>5").is_err());
+    }
+
+    #[test]
+    fn test_poll_until_requires_watch() {
+        let args = ["mb", "read", "holding", "--ip", "127.0.0.1", "--addr", "5"];
+        let parse = |extra: &[&str]| Cli::try_parse_from(args.iter().chain(extra));
+        assert!(parse(&["--poll-until", ">=100"]).is_err());
+        assert!(parse(&["--watch", "1s", "--poll-until", ">=100"]).is_ok());
+        assert!(parse(&["--watch", "1s", "--timeout-total", "60s"]).is_err());
+        assert!(parse(&[
+            "--watch",
+            "1s",
+            "--poll-until",
+            "<-1",
+            "--timeout-total",
+            "60s"
+        ])
+        .is_ok());
+    }
+
+    #[test]
+    fn test_parse_register_bit() {
+        assert_eq!(parse_address_spec("100.3"), Ok(AddressSpec::Bit(100, 3)));
+        assert_eq!(parse_address_spec("0.15"), Ok(AddressSpec::Bit(0, 15)));
+        assert!(parse_address_spec("0.16").is_err());
+        assert!(parse_address_spec("0.").is_err());
+        assert!(parse_address_spec("65536.0").is_err());
+
+        assert_eq!(
+            parse_write_address("7.1"),
+            Ok(WriteAddress {
+                register: 7,
+                bit: Some(1)
+            })
+        );
+        assert_eq!(parse_write_address("7").unwrap().bit, None);
+    }
+
+    #[test]
+    fn test_read_request_register_bit() {
+        let request = |area: &str, extra: &[&str]| {
+            let args = ["mb", "read", area, "--ip", "127.0.0.1", "--addr"];
+            let cli = Cli::try_parse_from(args.iter().chain(extra)).unwrap();
+            let Command::Read {
+                area: Some(area), ..
+            } = cli.cmd
+            else {
+                panic!("Expected read command");
+            };
+            area.into_request()
+        };
+        let bit = request("holding", &["100.3"]).unwrap();
+        assert_eq!((bit.start, bit.qty, bit.bit), (100, 1, Some(3)));
+
+        assert!(request("input", &["100.3"]).is_ok());
+        assert!(request("coil", &["100.3"]).is_err());
+        assert!(request("holding", &["100.3", "--qty", "2"]).is_err());
+        assert!(request("holding", &["100.3", "--format", "float32"]).is_err());
+    }
+
+    #[test]
+    fn test_read_request_step_reports_every_nth_address() {
+        let request = |extra: &[&str]| {
+            let args = ["mb", "read", "holding", "--ip", "127.0.0.1", "--addr"];
+            let cli = Cli::try_parse_from(args.iter().chain(extra)).unwrap();
+            let Command::Read {
+                area: Some(area), ..
+            } = cli.cmd
+            else {
+                panic!("Expected read command");
+            };
+            area.into_request()
+        };
+
+        let stepped = request(&["0", "--qty", "10", "--step", "2"]).unwrap();
+        assert_eq!(
+            stepped.addresses,
+            Some(vec![0, 2, 4, 6, 8, 10, 12, 14, 16, 18])
+        );
+        assert!(!stepped.is_requested(1));
+        assert_eq!(request(&["0", "--step", "1"]).unwrap().addresses, None);
+
+        assert!(request(&["0,4", "--step", "2"]).is_err());
+        assert!(request(&["0", "--qty", "4", "--step", "2", "--format", "float32"]).is_err());
+        assert!(request(&["65530", "--qty", "4", "--step", "2"]).is_err());
+    }
+
+    #[test]
+    fn test_address_base_is_zero_or_one() {
+        let parse = |base: &str| {
+            Cli::try_parse_from([
+                "mb",
+                "read",
+                "holding",
+                "--ip",
+                "127.0.0.1",
+                "--addr",
+                "0",
+                "--address-base",
+                base,
+            ])
+        };
+        assert!(parse("1").is_ok());
+        assert!(parse("2").is_err());
+    }
+
+    #[test]
+    fn test_time_ref_needs_output() {
+        let request = |extra: &[&str]| {
+            let args = ["mb", "read", "holding", "--ip", "127.0.0.1", "--addr", "0"];
+            let cli = Cli::try_parse_from(args.iter().chain(extra))?;
+            let Command::Read {
+                area: Some(area), ..
+            } = cli.cmd
+            else {
+                panic!("Expected read command");
+            };
+            Ok::<_, clap::Error>(area.into_request())
+        };
+
+        let wall = request(&["--output", "log.csv", "--time-ref", "wall"]).unwrap();
+        assert_eq!(wall.unwrap().options.time_ref, Some(TimeRef::Wall));
+        assert!(request(&["--time-ref", "wall"]).is_err());
+        let flushed = request(&["--output", "log.csv", "--flush-interval", "100"]).unwrap();
+        assert_eq!(flushed.unwrap().options.flush_interval, Some(100));
+        assert!(request(&["--flush-interval", "100"]).is_err());
+        assert!(request(&["--output", "log.csv", "--flush-interval", "0"]).is_err());
+        assert!(
+            request(&["--output", "log.csv", "--time-ref", "start", "--no-header"])
+                .unwrap()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_max_gap_needs_an_address_list() {
+        let request = |extra: &[&str]| {
+            let args = ["mb", "read", "holding", "--ip", "127.0.0.1", "--addr"];
+            let cli = Cli::try_parse_from(args.iter().chain(extra)).unwrap();
+            let Command::Read {
+                area: Some(area), ..
+            } = cli.cmd
+            else {
+                panic!("Expected read command");
+            };
+            area.into_request()
+        };
+
+        let listed = request(&["0,50", "--max-gap", "10"]).unwrap();
+        assert_eq!(listed.options.max_gap, Some(10));
+        assert!(request(&["0", "--qty", "10", "--step", "5", "--max-gap", "2"]).is_ok());
+        assert!(request(&["0", "--qty", "10", "--max-gap", "2"]).is_err());
+    }
+
+    #[test]
+    fn test_big_read_needs_confirmation() {
+        let request = |extra: &[&str]| {
+            let args = ["mb", "read", "coil", "--ip", "127.0.0.1", "--addr", "0"];
+            let cli = Cli::try_parse_from(args.iter().chain(extra)).unwrap();
+            let Command::Read {
+                area: Some(area), ..
+            } = cli.cmd
+            else {
+                panic!("Expected read command");
+            };
+            area.into_request().unwrap()
+        };
+
+        assert!(!request(&["--qty", "500"]).needs_confirmation());
+        assert!(request(&["--qty", "501"]).needs_confirmation());
+        assert!(!request(&["--qty", "2000", "--columns", "4"]).needs_confirmation());
+        assert!(!request(&["--qty", "2000", "--yes"]).needs_confirmation());
+        assert!(!request(&["--qty", "2000", "--output", "out.csv"]).needs_confirmation());
+        assert!(
+            request(&["--qty", "2000", "--output", "out.csv", "--also-stdout"])
+                .needs_confirmation()
+        );
+    }
+
+    #[test]
+    fn test_keepalive_must_fit_in_watch_interval() {
+        let request = |extra: &[&str]| {
+            let args = ["mb", "read", "holding", "--ip", "127.0.0.1", "--addr", "0"];
+            let cli = Cli::try_parse_from(args.iter().chain(extra))?;
+            let Command::Read {
+                area: Some(area), ..
+            } = cli.cmd
+            else {
+                panic!("Expected read command");
+            };
+            Ok::<_, clap::Error>(area.into_request())
+        };
+
+        let read = request(&["--watch", "60s", "--keepalive", "10"]).unwrap();
+        assert_eq!(read.unwrap().options.keepalive, Some(10));
+        assert!(request(&["--watch", "5s", "--keepalive", "10"])
+            .unwrap()
+            .is_err());
+        assert!(request(&["--watch", "5s", "--keepalive", "0"]).is_err());
+        // Only meaningful between watch polls
+        assert!(request(&["--keepalive", "10"]).is_err());
+    }
+
+    #[test]
+    fn test_single_value_reads_are_compact() {
+        let request = |extra: &[&str]| {
+            let args = ["mb", "read", "holding", "--ip", "127.0.0.1", "--addr", "7"];
+            let cli = Cli::try_parse_from(args.iter().chain(extra)).unwrap();
+            let Command::Read {
+                area: Some(area), ..
+            } = cli.cmd
+            else {
+                panic!("Expected read command");
+            };
+            area.into_request().unwrap()
+        };
+
+        assert!(request(&[]).is_compact());
+        assert!(!request(&["--qty", "2"]).is_compact());
+        assert!(!request(&["--verbose"]).is_compact());
+        assert!(!request(&["--quiet"]).is_compact());
+        assert!(!request(&["--timing"]).is_compact());
+        assert!(!request(&["--format", "hexdump"]).is_compact());
+        assert!(!request(&["--output", "out.csv"]).is_compact());
+        assert!(request(&["--output", "out.csv", "--also-stdout"]).is_compact());
+    }
+
+    #[test]
+    fn test_read_request_columns_only_for_contiguous_coils() {
+        let request = |area: &str, addr: &str| {
+            let cli = Cli::try_parse_from([
+                "mb",
+                "read",
+                area,
+                "--ip",
+                "127.0.0.1",
+                "--addr",
+                addr,
+                "--columns",
+                "16",
+            ])
+            .unwrap();
+            let Command::Read {
+                area: Some(area), ..
+            } = cli.cmd
+            else {
+                panic!("Expected read command");
+            };
+            area.into_request()
+        };
+        assert!(request("coil", "0").is_ok());
+        assert!(request("discrete", "0").is_ok());
+        assert!(request("holding", "0").is_err());
+        assert!(request("coil", "0,5").is_err());
+    }
+
+    #[test]
+    fn test_count_requires_watch() {
+        let args = ["mb", "read", "coil", "--ip", "127.0.0.1", "--addr", "0"];
+        let with_count = args.iter().copied().chain(["--count", "5"]);
+        assert!(Cli::try_parse_from(with_count).is_err());
+
+        let with_watch = args
+            .iter()
+            .copied()
+            .chain(["--count", "5", "--watch", "1s"]);
+        assert!(Cli::try_parse_from(with_watch).is_ok());
+
+        let zero_count = args
+            .iter()
+            .copied()
+            .chain(["--count", "0", "--watch", "1s"]);
+        assert!(Cli::try_parse_from(zero_count).is_err());
+    }
+
+    #[test]
+    fn test_read_request_rejects_format_for_coils() {
+        let cli = Cli::try_parse_from([
+            "mb",
+            "read",
+            "coil",
+            "--ip",
+            "127.0.0.1",
+            "--addr",
+            "0",
+            "--format",
+            "float32",
+        ])
+        .unwrap();
+        let Command::Read {
+            area: Some(area), ..
+        } = cli.cmd
+        else {
+            panic!("Expected read command");
+        };
+        assert!(area.into_request().is_err());
+    }
+
+    #[test]
+    fn test_read_request_format_packed_only_for_coils() {
+        let request = |area: &str| {
+            let cli = Cli::try_parse_from([
+                "mb",
+                "read",
+                area,
+                "--ip",
+                "127.0.0.1",
+                "--addr",
+                "0",
+                "--qty",
+                "16",
+                "--format",
+                "packed",
+            ])
+            .unwrap();
+            let Command::Read {
+                area: Some(area), ..
+            } = cli.cmd
+            else {
+                panic!("Expected read command");
+            };
+            area.into_request()
+        };
+        assert!(request("coil").is_ok());
+        assert!(request("discrete").is_ok());
+        assert!(request("holding").is_err());
+    }
+
+    #[test]
+    fn test_read_request_format_bool_matrix() {
+        let request = |area: &str, addr: &str, extra: &[&str]| {
+            let args = ["mb", "read", area, "--ip", "127.0.0.1", "--addr", addr];
+            let tail = ["--qty", "100", "--format", "bool-matrix"];
+            let cli = Cli::try_parse_from(args.iter().chain(&tail).chain(extra)).unwrap();
+            let Command::Read {
+                area: Some(area), ..
+            } = cli.cmd
+            else {
+                panic!("Expected read command");
+            };
+            area.into_request()
+        };
+        assert_eq!(request("discrete", "0", &[]).unwrap().printed_rows(), 4);
+        let wide = request("discrete", "0", &["--columns", "50"]).unwrap();
+        assert_eq!(wide.printed_rows(), 2);
+        assert!(request("input", "0", &[]).is_err());
+        assert!(request("coil", "0,5", &[]).is_err());
+    }
+
+    #[test]
+    fn test_read_request_format_hexdump_only_for_register_ranges() {
+        let request = |area: &str, addr: &str| {
+            let cli = Cli::try_parse_from([
+                "mb",
+                "read",
+                area,
+                "--ip",
+                "127.0.0.1",
+                "--addr",
+                addr,
+                "--format",
+                "hexdump",
+            ])
+            .unwrap();
+            let Command::Read {
+                area: Some(area), ..
+            } = cli.cmd
+            else {
+                panic!("Expected read command");
+            };
+            area.into_request()
+        };
+        assert!(request("holding", "0").is_ok());
+        assert!(request("input", "0").is_ok());
+        assert!(request("coil", "0").is_err());
+        assert!(request("holding", "0,8").is_err());
+    }
+
+    #[test]
+    fn test_ping_requires_no_read_arguments() {
+        let cli =
+            Cli::try_parse_from(["mb", "ping", "--ip", "127.0.0.1", "--timeout", "2"]).unwrap();
+        match cli.cmd {
+            Command::Ping { common } => assert_eq!(common.timeout, 2),
+            _ => panic!("expected ping command"),
+        }
+    }
+
+    #[test]
+    fn test_protocol_id_defaults_to_zero() {
+        let cli = Cli::try_parse_from(["mb", "ping", "--ip", "127.0.0.1"]).unwrap();
+        let Command::Ping { common } = cli.cmd else {
+            panic!("Expected ping command");
+        };
+        assert_eq!(common.protocol_id, 0);
+        let cli =
+            Cli::try_parse_from(["mb", "ping", "--ip", "127.0.0.1", "--protocol-id", "1"]).unwrap();
+        let Command::Ping { common } = cli.cmd else {
+            panic!("Expected ping command");
+        };
+        assert_eq!(common.protocol_id, 1);
+    }
+
+    #[test]
+    fn test_retry_on_exception_flags() {
+        let ping = |extra: &[&str]| {
+            let args = ["mb", "ping", "--ip", "127.0.0.1"];
+            let cli = Cli::try_parse_from(args.iter().chain(extra)).unwrap();
+            let Command::Ping { common } = cli.cmd else {
+                panic!("Expected ping command");
+            };
+            common
+        };
+        let common = ping(&[]);
+        assert!(!common.retry_on_exception);
+        assert_eq!(common.retries, 3);
+        let common = ping(&["--retry-on-exception", "--retries", "5"]);
+        assert!(common.retry_on_exception);
+        assert_eq!(common.retries, 5);
+    }
+
+    #[test]
+    fn test_diag_counters_parses_common_flags() {
+        let cli =
+            Cli::try_parse_from(["mb", "diag", "counters", "--device", "/dev/ttyUSB0"]).unwrap();
+        let Command::Diag {
+            op: DiagOp::Counters { common },
+        } = cli.cmd
+        else {
+            panic!("Expected diag counters command");
+        };
+        assert_eq!(common.target(), "/dev/ttyUSB0");
+        assert!(Cli::try_parse_from(["mb", "diag"]).is_err());
+    }
+
+    #[test]
+    fn test_selftest_needs_no_target() {
+        let cli = Cli::try_parse_from(["mb", "selftest", "--timeout", "2"]).unwrap();
+        let Command::Selftest { timeout, verbose } = cli.cmd else {
+            panic!("Expected selftest command");
+        };
+        assert_eq!(timeout, 2);
+        assert!(!verbose);
+        assert!(Cli::try_parse_from(["mb", "selftest", "--ip", "127.0.0.1"]).is_err());
+    }
+
+    #[test]
+    fn test_rts_delay_requires_device() {
+        assert!(
+            Cli::try_parse_from(["mb", "ping", "--ip", "127.0.0.1", "--rts-delay", "2"]).is_err()
+        );
+        let cli = Cli::try_parse_from([
+            "mb",
+            "ping",
+            "--device",
+            "/dev/ttyUSB0",
+            "--flow-control",
+            "rtscts",
+        ])
+        .unwrap();
+        let Command::Ping { common } = cli.cmd else {
+            panic!("Expected ping command");
+        };
+        assert_eq!(common.flow_control, FlowControl::Rtscts);
+        assert_eq!(common.rts_delay, None);
+    }
+
+    #[test]
+    fn test_client_unit_defaults_to_one() {
+        let cli = Cli::try_parse_from(["mb", "ping", "--ip", "127.0.0.1"]).unwrap();
+        let Command::Ping { common } = cli.cmd else {
+            panic!("Expected ping command");
+        };
+        assert_eq!(common.unit, 1);
+    }
+
+    #[test]
+    fn test_validate_unit() {
+        assert_eq!(validate_unit("1"), Ok(1));
+        assert_eq!(validate_unit("247"), Ok(247));
+        assert!(validate_unit("0").unwrap_err().contains("broadcast"));
+        assert!(validate_unit("248").unwrap_err().contains("reserved"));
+        assert!(validate_unit("256").is_err());
+        assert!(validate_unit("x").is_err());
+
+        // Client and server share the validator, except for the client's MBAP unit id 255
+        assert!(Cli::try_parse_from(["mb", "ping", "--ip", "127.0.0.1", "--unit", "0"]).is_err());
+        assert!(Cli::try_parse_from(["mb", "server", "--unit", "255"]).is_err());
+        assert_eq!(validate_client_unit("255"), Ok(MBAP_DIRECT_UNIT));
+        assert!(validate_client_unit("254")
+            .unwrap_err()
+            .contains("reserved"));
+        assert!(Cli::try_parse_from(["mb", "ping", "--ip", "127.0.0.1", "--unit", "255"]).is_ok());
+
+        // Surrounding spaces are ignored the same way by both
+        assert_eq!(validate_unit(" 5"), Ok(5));
+        assert_eq!(validate_client_unit(" 5"), Ok(5));
+        assert_eq!(validate_client_unit(" 255"), Ok(MBAP_DIRECT_UNIT));
+    }
+
+    #[test]
+    fn test_read_request_layout_sets_and_checks_qty() {
+        let path = std::env::temp_dir().join(format!("mb-layout-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[[field]]\nname = \"status\"\ntype = \"u16\"\n[[field]]\nname = \"flow\"\ntype = \"f32\"\n",
+        )
+        .unwrap();
+        let request = |extra: &[&str]| {
+            let mut args = vec!["mb", "read", "holding", "--ip", "127.0.0.1", "--addr", "10"];
+            args.extend(["--layout", path.to_str().unwrap()]);
+            args.extend(extra);
+            let Command::Read {
+                area: Some(area), ..
+            } = Cli::try_parse_from(args).unwrap().cmd
+            else {
+                panic!("Expected read command");
+            };
+            area.into_request()
+        };
+
+        assert_eq!(request(&[]).unwrap().qty, 3);
+        assert!(request(&["--qty", "3"]).is_ok());
+        assert!(request(&["--qty", "4"]).is_err());
+        assert!(request(&["--format", "float32"]).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_request_enron() {
+        let request = |area: &str, extra: &[&str]| {
+            let mut args = vec!["mb", "read", area, "--ip", "127.0.0.1", "--enron"];
+            args.extend(extra);
+            let Command::Read {
+                area: Some(area), ..
+            } = Cli::try_parse_from(args).unwrap().cmd
+            else {
+                panic!("Expected read command");
+            };
+            area.into_request()
+        };
+
+        assert!(request("holding", &["--addr", "5001", "--qty", "62"]).is_ok());
+        assert!(request("input", &["--addr", "7001", "--format", "float32"]).is_ok());
+        assert!(request("holding", &["--addr", "5001", "--qty", "63"]).is_err());
+        assert!(request("holding", &["--addr", "5001", "--format", "int64"]).is_err());
+        assert!(request("holding", &["--addr", "5001,5003"]).is_err());
+        assert!(request("coil", &["--addr", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_read_request_transform_needs_register_values() {
+        let path = std::env::temp_dir().join(format!("mb-transform-{}.csv", std::process::id()));
+        std::fs::write(&path, "address,scale,offset\n0,0.1,0\n").unwrap();
+        let request = |area: &str, extra: &[&str]| {
+            let mut args = vec!["mb", "read", area, "--ip", "127.0.0.1"];
+            args.extend(["--transform", path.to_str().unwrap()]);
+            args.extend(extra);
+            let Command::Read {
+                area: Some(area), ..
+            } = Cli::try_parse_from(args).unwrap().cmd
+            else {
+                panic!("Expected read command");
+            };
+            area.into_request()
+        };
+
+        assert!(request("holding", &["--addr", "0", "--qty", "4"]).is_ok());
+        assert!(request("input", &["--addr", "0", "--format", "float32"]).is_ok());
+        assert!(request("coil", &["--addr", "0"]).is_err());
+        assert!(request("holding", &["--addr", "0.1"]).is_err());
+        assert!(request("holding", &["--addr", "0", "--format", "hexdump"]).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_log_level_is_global() {
+        let cli = Cli::try_parse_from(["mb", "server"]).unwrap();
+        assert_eq!(cli.log_level, LogLevel::Info);
+        let cli = Cli::try_parse_from(["mb", "server", "--log-level", "trace"]).unwrap();
+        assert_eq!(cli.log_level, LogLevel::Trace);
+        assert!(LogLevel::Trace > LogLevel::Debug && LogLevel::Warn < LogLevel::Info);
+        assert!(Cli::try_parse_from(["mb", "server", "--log-level", "loud"]).is_err());
+    }
+
+    #[test]
+    fn test_quiet_conflicts_with_verbose() {
+        let quiet = [
+            "mb",
+            "read",
+            "holding",
+            "--ip",
+            "127.0.0.1",
+            "--addr",
+            "0",
+            "-q",
+        ];
+        assert!(Cli::try_parse_from(quiet).is_ok());
+        let both = [
+            "mb",
+            "read",
+            "holding",
+            "--ip",
+            "127.0.0.1",
+            "--addr",
+            "0",
+            "-q",
+            "-v",
+        ];
+        assert!(Cli::try_parse_from(both).is_err());
+    }
+
+    #[test]
+    fn test_parse_bit_string() {
+        assert_eq!(
+            parse_bit_string("1011"),
+            Ok(BitString(vec![true, false, true, true]))
+        );
+        assert!(parse_bit_string("").is_err());
+        assert!(parse_bit_string("10a1").unwrap_err().contains("'a'"));
+    }
+
+    #[test]
+    fn test_order_bits() {
+        let bits = parse_bit_string("1100").unwrap();
+        assert_eq!(
+            order_bits(bits.clone(), BitOrder::LsbFirst),
+            [true, true, false, false]
+        );
+        assert_eq!(
+            order_bits(bits, BitOrder::MsbFirst),
+            [false, false, true, true]
+        );
+    }
+
+    #[test]
+    fn test_write_coil_bits_conflicts_with_value() {
+        let base = ["mb", "write", "coil", "--ip", "127.0.0.1", "--addr", "0"];
+        let parse = |extra: &[&str]| Cli::try_parse_from(base.iter().chain(extra));
+        assert!(parse(&["--bits", "1011"]).is_ok());
+        assert!(parse(&["--bits", "1011", "--bit-order", "msb-first"]).is_ok());
+        assert!(parse(&["--bits", "1011", "--value", "1"]).is_err());
+        assert!(parse(&[]).is_err());
+    }
+
+    #[test]
+    fn test_write_holding_value_accepts_stdin_and_negative_numbers() {
+        let values = |value: &str| {
+            let cli = Cli::try_parse_from([
+                "mb",
+                "write",
+                "holding",
+                "--ip",
+                "127.0.0.1",
+                "--addr",
+                "0",
+                "--value",
+                value,
+            ])
+            .unwrap();
+            match cli.cmd {
+                Command::Write {
+                    area: WriteArea::Holding { values, .. },
+                } => values,
+                _ => panic!("Expected write holding command"),
+            }
+        };
+        assert_eq!(values("-"), ["-"]);
+        assert_eq!(values("-1.5,2"), ["-1.5", "2"]);
+
+        // Flags after a negative value are still parsed as flags
+        let cli = Cli::try_parse_from([
+            "mb",
+            "write",
+            "holding",
+            "--ip",
+            "127.0.0.1",
+            "--addr",
+            "0",
+            "--value",
+            "-1",
+            "--qty",
+            "3",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.cmd,
+            Command::Write {
+                area: WriteArea::Holding { qty: Some(3), .. }
+            }
+        ));
+    }
+
+    #[test]
+    fn test_write_holding_from_replaces_addr_and_value() {
+        let write = |extra: &[&str]| {
+            let args = ["mb", "write", "holding", "--ip", "127.0.0.1"];
+            Cli::try_parse_from(args.iter().chain(extra))
+        };
+        let cli = write(&["--from", "values.json"]).unwrap();
+        let Command::Write {
+            area: WriteArea::Holding { start, from, .. },
+        } = cli.cmd
+        else {
+            panic!("Expected write holding command");
+        };
+        assert_eq!(start, None);
+        assert_eq!(from, Some(PathBuf::from("values.json")));
+
+        assert!(write(&["--addr", "0"]).is_err());
+        assert!(write(&["--from", "values.json", "--addr", "0"]).is_err());
+        assert!(write(&["--from", "values.json", "--value", "1"]).is_err());
+    }
+
+    #[test]
+    fn test_expand_write_values() {
+        assert_eq!(
+            expand_write_values(vec![1, 2, 3], None, Area::Holding),
+            Ok(vec![1, 2, 3])
+        );
+        assert_eq!(
+            expand_write_values(vec![7], Some(4), Area::Holding),
+            Ok(vec![7, 7, 7, 7])
+        );
+        assert_eq!(
+            expand_write_values(vec![7], Some(1), Area::Coil),
+            Ok(vec![7])
+        );
+
+        let result = expand_write_values(vec![1, 2], Some(5), Area::Holding);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("single --value"));
+    }
+
+    #[test]
+    fn test_expand_write_values_enforces_write_limits() {
+        assert!(expand_write_values(vec![1; 1968], None, Area::Coil).is_ok());
+        let result = expand_write_values(vec![1; 1969], None, Area::Coil);
+        assert!(result
+            .unwrap_err()
+            .contains("1-1968 coils per request (FC 15)"));
+
+        assert!(expand_write_values(vec![0], Some(123), Area::Holding).is_ok());
+        let result = expand_write_values(vec![0], Some(124), Area::Holding);
+        assert!(result
+            .unwrap_err()
+            .contains("1-123 registers per request (FC 16)"));
+    }
+
+    #[test]
+    fn test_parse_watch_interval_valid() {
+        assert_eq!(
+            parse_watch_interval("500ms"),
+            Ok(Duration::from_millis(500))
+        );
+        assert_eq!(parse_watch_interval("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_watch_interval("1m"), Ok(Duration::from_secs(60)));
+        assert_eq!(parse_watch_interval("3"), Ok(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn test_parse_watch_interval_invalid() {
+        assert!(parse_watch_interval("").is_err());
+        assert!(parse_watch_interval("abc").is_err());
+        assert!(parse_watch_interval("5h").is_err());
+        assert!(parse_watch_interval("0").is_err());
+        assert!(parse_watch_interval("50ms").is_err());
+
+        let error_msg = parse_watch_interval("50ms").unwrap_err();
+        assert!(error_msg.contains("at least 100ms"));
+        let error_msg = parse_watch_interval("0s").unwrap_err();
+        assert!(error_msg.contains("greater than 0"));
+    }
+
+    #[test]
+    fn test_durations_have_no_polling_floor() {
+        assert_eq!(parse_duration("50ms"), Ok(Duration::from_millis(50)));
+        assert!(parse_duration("0ms").is_err());
+
+        let args = ["mb", "read", "holding", "--ip", "127.0.0.1", "--addr", "5"];
+        let parse = |extra: &[&str]| Cli::try_parse_from(args.iter().chain(extra));
+        assert!(parse(&[
+            "--watch",
+            "1s",
+            "--poll-until",
+            ">=1",
+            "--timeout-total",
+            "50ms"
+        ])
+        .is_ok());
+        assert!(parse(&["--watch", "50ms"]).is_err());
+    }
 }
@@ -1,68 +1,245 @@
-use crate::cli::Common;
+use crate::cli::{Area, Common, FlowControl, LogLevel, MBAP_DIRECT_UNIT};
+use crate::frame::FrameLog;
+use crate::logging::raise_log_level;
+use crate::output::{eprint_json, ErrorJson};
+use crate::protocol_id::ProtocolId;
+use crate::rts::RtsToggle;
+use crate::traffic::{enable_traffic_summary, ByteCount};
+use crate::udp::UdpTransport;
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
-use tokio::time::{timeout, Duration};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tokio::time::{sleep_until, timeout, Duration, Instant};
 use tokio_modbus::client;
 use tokio_modbus::prelude::*;
 
+/// Why a connection could not be opened, with a stable message and exit code per kind
+#[derive(Debug)]
+pub enum ConnectError {
+    /// The target actively refused the connection (nothing listening)
+    Refused { target: String },
+    /// No connection within `--timeout`
+    TimedOut { target: String, secs: u64 },
+    /// Any other I/O failure, such as a missing serial device
+    Io {
+        target: String,
+        source: std::io::Error,
+    },
+}
+
+impl ConnectError {
+    // Sort an I/O failure into the matching kind
+    fn from_io(common: &Common, source: std::io::Error) -> Self {
+        let target = common.target();
+        match source.kind() {
+            std::io::ErrorKind::ConnectionRefused => ConnectError::Refused { target },
+            std::io::ErrorKind::TimedOut => ConnectError::TimedOut {
+                target,
+                secs: common.timeout,
+            },
+            _ => ConnectError::Io { target, source },
+        }
+    }
+
+    /// Process exit code for this failure, so scripts can tell the cases apart
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ConnectError::Refused { .. } => 3,
+            ConnectError::TimedOut { .. } => 4,
+            ConnectError::Io { .. } => 5,
+        }
+    }
+
+    /// JSON object for `--output-format json`, e.g. {"error":"connection_refused",...}
+    pub fn json(&self) -> ErrorJson<'_> {
+        let (kind, target) = match self {
+            ConnectError::Refused { target } => ("connection_refused", target),
+            ConnectError::TimedOut { target, .. } => ("connection_timeout", target),
+            ConnectError::Io { target, .. } => ("connection_failed", target),
+        };
+        ErrorJson {
+            target: Some(target),
+            message: Some(self.to_string()),
+            ..ErrorJson::new(kind)
+        }
+    }
+}
+
+impl std::fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectError::Refused { target } => write!(f, "Connection refused by {target}"),
+            ConnectError::TimedOut { target, secs } => {
+                write!(f, "Connection to {target} timed out after {secs} seconds")
+            }
+            ConnectError::Io { target, source } => {
+                write!(f, "Failed to connect to {target}: {source}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConnectError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// Explain a serial device that does not exist, listing the ports that do so a mistyped name
+// (`/dev/ttyUSB9` for `/dev/ttyUSB0`) is easy to correct
+fn missing_device_message(description: &str, ports: &[String]) -> String {
+    if ports.is_empty() {
+        format!("{description}: no serial ports were found; check that the adapter is plugged in")
+    } else {
+        format!(
+            "{description}: no such serial device; available ports: {}",
+            ports.join(", ")
+        )
+    }
+}
+
+/// Turn a failure to open a serial port into an I/O error, adding what to do about the
+/// failures almost every new RTU user hits: a port held by another process (EBUSY, which
+/// serialport reports as `NoDevice`), a port the user may not open (EACCES) and a device name
+/// that doesn't exist, for which the ports that do are listed
+pub fn serial_open_error(error: tokio_serial::Error) -> std::io::Error {
+    use std::io::ErrorKind;
+    match error.kind() {
+        tokio_serial::ErrorKind::Io(ErrorKind::NotFound) => {
+            // Only consulted once the open has failed; the open itself decides what exists
+            let ports: Vec<String> = tokio_serial::available_ports()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|port| port.port_name)
+                .collect();
+            std::io::Error::new(
+                ErrorKind::NotFound,
+                missing_device_message(&error.description, &ports),
+            )
+        }
+        tokio_serial::ErrorKind::NoDevice => std::io::Error::other(format!(
+            "{}: the port is in use by another process; close whatever else has it open and try again",
+            error.description
+        )),
+        tokio_serial::ErrorKind::Io(ErrorKind::PermissionDenied) => std::io::Error::new(
+            ErrorKind::PermissionDenied,
+            format!(
+                "{}: try sudo, or add your user to the dialout group (sudo usermod -aG dialout $USER) and log in again",
+                error.description
+            ),
+        ),
+        _ => error.into(),
+    }
+}
+
 pub async fn connect_to_modbus(common: &Common) -> anyhow::Result<client::Context> {
+    if common.stats || common.verbose {
+        enable_traffic_summary();
+    }
+    if common.verbose {
+        raise_log_level(LogLevel::Debug);
+    }
     match (&common.ip, &common.device) {
+        (Some(ip), None) if common.udp => {
+            // Modbus TCP framing over UDP datagrams; there is no handshake, so this only binds
+            let socket_addr = SocketAddr::new(*ip, common.port);
+            debug!(
+                "Sending Modbus over UDP to {ip}:{} (Unit ID: {})...",
+                common.port, common.unit
+            );
+
+            let local_addr = if ip.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+            let socket = tokio::net::UdpSocket::bind(local_addr)
+                .await
+                .map_err(|e| ConnectError::from_io(common, e))?;
+            socket
+                .connect(socket_addr)
+                .await
+                .map_err(|e| ConnectError::from_io(common, e))?;
+            Ok(client::tcp::attach_slave(
+                ProtocolId::new(
+                    ByteCount::new(UdpTransport::new(socket)),
+                    common.protocol_id,
+                ),
+                Slave(common.unit),
+            ))
+        }
         (Some(ip), None) => {
             // TCP connection
             let socket_addr = SocketAddr::new(*ip, common.port);
-            if common.verbose {
-                println!(
-                    "Connecting to Modbus TCP server at {ip}:{} (Unit ID: {})...",
-                    common.port, common.unit
+            debug!(
+                "Connecting to Modbus TCP server at {ip}:{} (Unit ID: {})...",
+                common.port, common.unit
+            );
+            debug!(
+                "Requests carry MBAP unit id {}; a TCP-to-RTU gateway forwards them to serial slave {}",
+                common.unit, common.unit
+            );
+            if common.protocol_id != 0 {
+                debug!(
+                    "Requests carry nonstandard MBAP protocol id {}",
+                    common.protocol_id
                 );
             }
 
+            // The context keeps the unit id and stamps it on every request it sends
             let connect_timeout = Duration::from_secs(common.timeout);
-            match timeout(connect_timeout, client::tcp::connect(socket_addr)).await {
+            let slave = Slave(common.unit);
+            match timeout(connect_timeout, tokio::net::TcpStream::connect(socket_addr)).await {
                 Ok(connect_result) => match connect_result {
-                    Ok(mut ctx) => {
-                        ctx.set_slave(Slave(common.unit));
-                        if common.verbose {
-                            println!(
-                                "Successfully connected to Modbus TCP server at {ip}:{}",
-                                common.port
-                            );
-                        }
+                    Ok(stream) => {
+                        let stream = ProtocolId::new(ByteCount::new(stream), common.protocol_id);
+                        let ctx = client::tcp::attach_slave(stream, slave);
+                        debug!(
+                            "Successfully connected to Modbus TCP server at {ip}:{}",
+                            common.port
+                        );
                         Ok(ctx)
                     }
-                    Err(e) => {
-                        eprintln!("Failed to connect to {ip}:{} - Error: {e}", common.port);
-                        Err(e.into())
-                    }
+                    Err(e) => Err(ConnectError::from_io(common, e).into()),
                 },
-                Err(_) => {
-                    eprintln!(
-                        "Connection to {ip}:{} timed out after {} seconds",
-                        common.port, common.timeout
-                    );
-                    Err(anyhow::anyhow!("Connection timeout"))
+                Err(_) => Err(ConnectError::TimedOut {
+                    target: common.target(),
+                    secs: common.timeout,
                 }
+                .into()),
             }
         }
+        (None, Some(_))
+            if common.rts_delay.is_some() && common.flow_control == FlowControl::Rtscts =>
+        {
+            Err(anyhow::anyhow!(
+                "--rts-delay drives RTS itself and cannot be combined with --flow-control rtscts"
+            ))
+        }
         (None, Some(device)) => {
             // RTU connection
-            if common.verbose {
-                println!(
-                    "Connecting to Modbus RTU device at {} (Baud: {}, Parity: {:?}, Stop Bits: {:?}, Data Bits: {:?}, Unit ID: {})...",
-                    device.display(),
-                    common.baud,
-                    common.parity,
-                    common.stop_bits,
-                    common.data_bits,
-                    common.unit
-                );
+            if common.unit == MBAP_DIRECT_UNIT {
+                return Err(anyhow::anyhow!(
+                    "Invalid unit {MBAP_DIRECT_UNIT}: it is reserved on serial lines; it only addresses a Modbus TCP device directly (use 1-247 with --device)"
+                ));
             }
+            debug!(
+                "Connecting to Modbus RTU device at {} (Baud: {}, Parity: {:?}, Stop Bits: {:?}, Data Bits: {:?}, Unit ID: {})...",
+                device.display(),
+                common.baud,
+                common.parity,
+                common.stop_bits,
+                common.data_bits,
+                common.unit
+            );
 
             let connect_timeout = Duration::from_secs(common.timeout);
             match timeout(connect_timeout, async {
                 let builder = tokio_serial::new(device.to_string_lossy(), common.baud)
                     .parity(common.parity.into())
                     .stop_bits(common.stop_bits.into())
-                    .data_bits(common.data_bits.into());
+                    .data_bits(common.data_bits.into())
+                    .flow_control(common.flow_control.into());
                 tokio_serial::SerialStream::open(&builder)
             })
             .await
@@ -71,32 +248,42 @@ pub async fn connect_to_modbus(common: &Common) -> anyhow::Result<client::Contex
                     Ok(mut serial) => {
                         // Disable exclusive access for virtual ports
                         if let Err(e) = serial.set_exclusive(false) {
-                            if common.verbose {
-                                println!("Warning: Could not disable exclusive access: {e}");
-                            }
-                        }
-                        let ctx = client::rtu::attach_slave(serial, Slave(common.unit));
-                        if common.verbose {
-                            println!(
-                                "Successfully connected to Modbus RTU device at {}",
-                                device.display()
-                            );
+                            debug!("Warning: Could not disable exclusive access: {e}");
                         }
+                        let slave = Slave(common.unit);
+                        let ctx = match (
+                            common.rts_delay.map(Duration::from_millis),
+                            common.show_frame,
+                        ) {
+                            (Some(delay), true) => client::rtu::attach_slave(
+                                ByteCount::new(FrameLog::new(RtsToggle::new(serial, delay))),
+                                slave,
+                            ),
+                            (Some(delay), false) => client::rtu::attach_slave(
+                                ByteCount::new(RtsToggle::new(serial, delay)),
+                                slave,
+                            ),
+                            (None, true) => client::rtu::attach_slave(
+                                ByteCount::new(FrameLog::new(serial)),
+                                slave,
+                            ),
+                            (None, false) => {
+                                client::rtu::attach_slave(ByteCount::new(serial), slave)
+                            }
+                        };
+                        debug!(
+                            "Successfully connected to Modbus RTU device at {}",
+                            device.display()
+                        );
                         Ok(ctx)
                     }
-                    Err(e) => {
-                        eprintln!("Failed to connect to {} - Error: {e}", device.display());
-                        Err(e.into())
-                    }
+                    Err(e) => Err(ConnectError::from_io(common, serial_open_error(e)).into()),
                 },
-                Err(_) => {
-                    eprintln!(
-                        "Connection to {} timed out after {} seconds",
-                        device.display(),
-                        common.timeout
-                    );
-                    Err(anyhow::anyhow!("Connection timeout"))
+                Err(_) => Err(ConnectError::TimedOut {
+                    target: common.target(),
+                    secs: common.timeout,
                 }
+                .into()),
             }
         }
         (None, None) => Err(anyhow::anyhow!(
@@ -106,6 +293,96 @@ pub async fn connect_to_modbus(common: &Common) -> anyhow::Result<client::Contex
     }
 }
 
+/// Outcome counts of every operation run through the helpers below, for the exit summary
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ErrorTally {
+    pub operations: usize,
+    pub timeouts: usize,
+    pub transport_errors: usize,
+    /// Exception responses keyed by exception name (e.g. `IllegalDataAddress`)
+    pub exceptions: BTreeMap<String, usize>,
+}
+
+impl std::fmt::Display for ErrorTally {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plural = |n: usize, noun: &str| format!("{n} {noun}{}", if n == 1 { "" } else { "s" });
+        write!(f, "{}", plural(self.operations, "operation"))?;
+        if self.timeouts > 0 {
+            write!(f, ", {}", plural(self.timeouts, "timeout"))?;
+        }
+        if self.transport_errors > 0 {
+            write!(f, ", {}", plural(self.transport_errors, "connection error"))?;
+        }
+        for (name, count) in &self.exceptions {
+            write!(f, ", {count} {}", exception_label(name))?;
+        }
+        if self.timeouts + self.transport_errors + self.exceptions.len() == 0 {
+            write!(f, ", no errors")?;
+        }
+        Ok(())
+    }
+}
+
+// "IllegalDataAddress" -> "illegal data address"
+fn exception_label(name: &str) -> String {
+    let mut label = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && !label.is_empty() {
+            label.push(' ');
+        }
+        label.push(c.to_ascii_lowercase());
+    }
+    label
+}
+
+// None until a command asks for the exit summary, so single operations don't pay for it
+static ERROR_TALLY: Mutex<Option<ErrorTally>> = Mutex::new(None);
+
+// Start counting operation outcomes for the exit summary
+pub fn enable_error_summary() {
+    *ERROR_TALLY.lock().unwrap() = Some(ErrorTally::default());
+}
+
+// The counts gathered since `enable_error_summary`, if it was called
+pub fn take_error_summary() -> Option<ErrorTally> {
+    ERROR_TALLY.lock().unwrap().take()
+}
+
+fn record_outcome(update: impl FnOnce(&mut ErrorTally)) {
+    if let Some(tally) = ERROR_TALLY.lock().unwrap().as_mut() {
+        tally.operations += 1;
+        update(tally);
+    }
+}
+
+// Set from --output-format json; failures are then reported as one JSON object each
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+// Whether a failure has already been reported, so main doesn't print it a second time
+static FAILURE_REPORTED: AtomicBool = AtomicBool::new(false);
+
+// Report failures as JSON objects instead of prose
+pub fn enable_json_errors() {
+    JSON_ERRORS.store(true, Ordering::Relaxed);
+}
+
+pub fn json_errors() -> bool {
+    JSON_ERRORS.load(Ordering::Relaxed)
+}
+
+pub fn failure_reported() -> bool {
+    FAILURE_REPORTED.load(Ordering::Relaxed)
+}
+
+// Print a failure to stderr as prose or, in JSON mode, as the given object
+fn report_failure(text: String, json: ErrorJson) {
+    if json_errors() {
+        eprint_json(&json);
+        FAILURE_REPORTED.store(true, Ordering::Relaxed);
+    } else {
+        eprintln!("{text}");
+    }
+}
+
 // Generic helper for handling Modbus response errors with timeout
 pub async fn handle_modbus_response_with_timeout<T, E>(
     result: Result<Result<Result<T, E>, tokio_modbus::Error>, tokio::time::error::Elapsed>,
@@ -118,38 +395,342 @@ where
     match result {
         Ok(modbus_result) => match modbus_result {
             Ok(response) => match response {
-                Ok(data) => Ok(data),
+                Ok(data) => {
+                    record_outcome(|_| {});
+                    Ok(data)
+                }
                 Err(exception) => {
-                    eprintln!("Modbus exception response: {exception:?}");
+                    record_outcome(|tally| {
+                        *tally
+                            .exceptions
+                            .entry(format!("{exception:?}"))
+                            .or_default() += 1
+                    });
+                    let code = format!("{exception:?}");
+                    report_failure(
+                        format!("Modbus exception response: {code}"),
+                        ErrorJson {
+                            operation: Some(operation),
+                            exception: Some(&code),
+                            ..ErrorJson::new("exception")
+                        },
+                    );
                     Err(anyhow::anyhow!("Modbus exception: {:?}", exception))
                 }
             },
             Err(e) => {
-                eprintln!("Failed to {operation}: {e}");
+                record_outcome(|tally| tally.transport_errors += 1);
+                report_failure(
+                    format!("Failed to {operation}: {e}"),
+                    ErrorJson {
+                        operation: Some(operation),
+                        message: Some(e.to_string()),
+                        ..ErrorJson::new("transport")
+                    },
+                );
                 Err(e.into())
             }
         },
         Err(_) => {
-            eprintln!("Operation '{operation}' timed out after {timeout_secs} seconds");
+            record_outcome(|tally| tally.timeouts += 1);
+            report_failure(
+                format!("Operation '{operation}' timed out after {timeout_secs} seconds"),
+                ErrorJson {
+                    operation: Some(operation),
+                    seconds: Some(timeout_secs),
+                    ..ErrorJson::new("timeout")
+                },
+            );
             Err(anyhow::anyhow!("Operation timeout"))
         }
     }
 }
 
-// Helper function to perform Modbus operations with timeout
-pub async fn modbus_operation_with_timeout<T, E, F, Fut>(
-    operation: F,
+/// Timeout and retries for each Modbus operation, from the connection flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpSettings {
+    pub timeout_secs: u64,
+    /// Times an operation answered Busy or Acknowledge is sent again (--retry-on-exception)
+    pub exception_retries: u32,
+    /// Times an operation that got no reply is sent again (--udp, where datagrams get lost)
+    pub timeout_retries: u32,
+}
+
+impl OpSettings {
+    pub fn new(common: &Common) -> Self {
+        Self {
+            timeout_secs: common.timeout,
+            exception_retries: if common.retry_on_exception {
+                common.retries
+            } else {
+                0
+            },
+            timeout_retries: if common.udp { common.retries } else { 0 },
+        }
+    }
+}
+
+/// Exceptions that mean the device can't take the request yet, rather than that it is wrong
+pub fn is_retryable_exception(code: ExceptionCode) -> bool {
+    matches!(
+        code,
+        ExceptionCode::ServerDeviceBusy | ExceptionCode::Acknowledge
+    )
+}
+
+// Wait before retry `attempt` (from 1): 100ms, doubling up to 2s, giving a busy device time
+fn exception_retry_delay(attempt: u32) -> Duration {
+    Duration::from_millis(100 << attempt.saturating_sub(1).min(5)).min(Duration::from_secs(2))
+}
+
+// Helper function to perform Modbus operations with timeout. Operations answered with Busy or
+// Acknowledge are sent again up to `op.exception_retries` times, and operations that time out
+// up to `op.timeout_retries` times, before failing
+pub async fn modbus_operation_with_timeout<T, E, F>(
+    mut operation: F,
     operation_name: &str,
-    timeout_secs: u64,
+    op: OpSettings,
 ) -> anyhow::Result<T>
 where
-    F: FnOnce() -> Fut,
-    Fut: std::future::Future<Output = Result<Result<T, E>, tokio_modbus::Error>>,
-    E: std::fmt::Debug,
+    F: AsyncFnMut() -> Result<Result<T, E>, tokio_modbus::Error>,
+    E: std::fmt::Debug + Clone + Into<ExceptionCode>,
 {
+    let OpSettings {
+        timeout_secs,
+        exception_retries,
+        timeout_retries,
+    } = op;
     let op_timeout = Duration::from_secs(timeout_secs);
-    let result = timeout(op_timeout, operation()).await;
-    handle_modbus_response_with_timeout(result, operation_name, timeout_secs).await
+    let mut attempt = 0;
+    let mut resent = 0;
+    loop {
+        let result = timeout(op_timeout, operation()).await;
+        if let Ok(Ok(Err(exception))) = &result {
+            let code = exception.clone().into();
+            if is_retryable_exception(code) && attempt < exception_retries {
+                attempt += 1;
+                debug!("{operation_name}: device answered {code:?}, retry {attempt} of {exception_retries}");
+                tokio::time::sleep(exception_retry_delay(attempt)).await;
+                continue;
+            }
+        }
+        if result.is_err() && resent < timeout_retries {
+            resent += 1;
+            debug!("{operation_name}: no reply within {timeout_secs}s, resending ({resent} of {timeout_retries})");
+            continue;
+        }
+        return handle_modbus_response_with_timeout(result, operation_name, timeout_secs).await;
+    }
+}
+
+// Connect and issue a single one-register read, returning the round-trip time
+pub async fn ping(common: &Common) -> anyhow::Result<Duration> {
+    let started = std::time::Instant::now();
+    let mut ctx = connect_to_modbus(common).await?;
+    await_ping_reply(
+        async || ctx.read_holding_registers(0, 1).await,
+        OpSettings::new(common),
+    )
+    .await?;
+    Ok(started.elapsed())
+}
+
+// Send the ping read like any other operation, so --retries applies. An exception still counts
+// as a reply, since the device is reachable and answering, except Busy and Acknowledge: those
+// mean it isn't ready yet, so they are retried and fail the ping once the retries run out
+async fn await_ping_reply<T, F>(mut read: F, op: OpSettings) -> anyhow::Result<()>
+where
+    F: AsyncFnMut() -> Result<Result<T, ExceptionCode>, tokio_modbus::Error>,
+{
+    modbus_operation_with_timeout(
+        async || {
+            Ok(match read().await? {
+                Err(code) if !is_retryable_exception(code) => Ok(()),
+                reply => reply.map(drop),
+            })
+        },
+        "ping",
+        op,
+    )
+    .await
+}
+
+// Group sorted addresses into the fewest (start, qty) reads of at most `max_qty` each. With
+// `max_gap`, addresses more than that many unrequested addresses apart start a new read, trading
+// round trips for not fetching the values in between
+pub fn plan_read_spans(addresses: &[u16], max_qty: u16, max_gap: Option<u16>) -> Vec<(u16, u16)> {
+    let mut spans: Vec<(u16, u16)> = Vec::new();
+    for &addr in addresses {
+        match spans.last_mut() {
+            Some((start, qty))
+                if (addr - *start) < max_qty
+                    && max_gap.is_none_or(|gap| addr - (*start + *qty) <= gap) =>
+            {
+                *qty = addr - *start + 1
+            }
+            _ => spans.push((addr, 1)),
+        }
+    }
+    spans
+}
+
+/// One line describing a read request as it goes out, for `--verbose`, e.g.
+/// `Request: FC 03 read holding registers, unit 1, address 0, quantity 10`
+pub fn describe_read_request(area: Area, start: u16, qty: u16, unit: u8) -> String {
+    format!(
+        "Request: FC {:02} read {}, unit {unit}, address {start}, quantity {qty}",
+        area.read_function_code(),
+        area.label().to_lowercase()
+    )
+}
+
+// Read coils or discrete inputs for each span, pairing every value with its address
+pub async fn read_coil_spans(
+    ctx: &mut client::Context,
+    area: Area,
+    spans: &[(u16, u16)],
+    op: OpSettings,
+) -> anyhow::Result<Vec<(u16, bool)>> {
+    let mut rows = Vec::new();
+    for &(start, qty) in spans {
+        let values = match area {
+            Area::Discrete => {
+                modbus_operation_with_timeout(
+                    async || ctx.read_discrete_inputs(start, qty).await,
+                    "read discrete inputs",
+                    op,
+                )
+                .await?
+            }
+            _ => {
+                modbus_operation_with_timeout(
+                    async || ctx.read_coils(start, qty).await,
+                    "read coils",
+                    op,
+                )
+                .await?
+            }
+        };
+        rows.extend((0..).map(|i: u16| start.wrapping_add(i)).zip(values));
+    }
+    Ok(rows)
+}
+
+// Read holding or input registers for each span, pairing every value with its address
+pub async fn read_register_spans(
+    ctx: &mut client::Context,
+    area: Area,
+    spans: &[(u16, u16)],
+    op: OpSettings,
+) -> anyhow::Result<Vec<(u16, u16)>> {
+    let mut rows = Vec::new();
+    for &(start, qty) in spans {
+        let values = match area {
+            Area::Input => {
+                modbus_operation_with_timeout(
+                    async || ctx.read_input_registers(start, qty).await,
+                    "read input registers",
+                    op,
+                )
+                .await?
+            }
+            _ => {
+                modbus_operation_with_timeout(
+                    async || ctx.read_holding_registers(start, qty).await,
+                    "read holding registers",
+                    op,
+                )
+                .await?
+            }
+        };
+        rows.extend((0..).map(|i: u16| start.wrapping_add(i)).zip(values));
+    }
+    Ok(rows)
+}
+
+// True when an operation failed because the connection itself broke (not a Modbus exception)
+pub fn is_connection_error(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<tokio_modbus::Error>(),
+        Some(tokio_modbus::Error::Transport(_))
+    ) || err.downcast_ref::<std::io::Error>().is_some()
+        || err.downcast_ref::<ConnectError>().is_some()
+}
+
+// Reconnect with exponential backoff (1s doubling to 30s). Returns None if interrupted by Ctrl+C.
+pub async fn reconnect_with_backoff(common: &Common) -> Option<client::Context> {
+    let mut delay = Duration::from_secs(1);
+    let mut attempt = 1;
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = tokio::signal::ctrl_c() => return None,
+        }
+        debug!("Reconnect attempt {attempt} to {}...", common.target());
+        if let Ok(ctx) = connect_to_modbus(common).await {
+            info!("Reconnected to {}", common.target());
+            return Some(ctx);
+        }
+        delay = (delay * 2).min(Duration::from_secs(30));
+        attempt += 1;
+    }
+}
+
+// Sleep until the next watch poll is due. Returns false when not watching or on Ctrl+C.
+pub async fn wait_for_next_poll(watch: Option<Duration>) -> bool {
+    let Some(interval) = watch else {
+        return false;
+    };
+    println!();
+    tokio::select! {
+        _ = tokio::time::sleep(interval) => true,
+        _ = tokio::signal::ctrl_c() => false,
+    }
+}
+
+/// Wait out a watch interval like `wait_for_next_poll`, reading one value at `addr` every
+/// `keepalive` so devices and gateways that drop idle connections keep this one open. A failed
+/// keepalive read is reported and the rest of the interval passes quietly; the next poll
+/// reconnects if the connection is gone
+pub async fn wait_with_keepalive(
+    ctx: &mut client::Context,
+    watch: Option<Duration>,
+    keepalive: Duration,
+    area: Area,
+    addr: u16,
+    op: OpSettings,
+) -> bool {
+    let Some(interval) = watch else {
+        return false;
+    };
+    println!();
+    let deadline = Instant::now() + interval;
+    loop {
+        let next = Instant::now() + keepalive;
+        if next >= deadline {
+            break;
+        }
+        tokio::select! {
+            _ = sleep_until(next) => {}
+            _ = tokio::signal::ctrl_c() => return false,
+        }
+        let heartbeat = match area {
+            Area::Coil | Area::Discrete => {
+                read_coil_spans(ctx, area, &[(addr, 1)], op).await.map(drop)
+            }
+            Area::Holding | Area::Input => read_register_spans(ctx, area, &[(addr, 1)], op)
+                .await
+                .map(drop),
+        };
+        if let Err(e) = heartbeat {
+            warn!("keepalive read failed: {e}");
+            break;
+        }
+    }
+    tokio::select! {
+        _ = sleep_until(deadline) => true,
+        _ = tokio::signal::ctrl_c() => false,
+    }
 }
 
 #[cfg(test)]
@@ -157,6 +738,135 @@ mod tests {
     use super::*;
     use tokio_modbus::prelude::ExceptionCode;
 
+    #[test]
+    fn test_serial_open_error_explains_busy_and_denied_ports() {
+        let busy = serial_open_error(tokio_serial::Error::new(
+            tokio_serial::ErrorKind::NoDevice,
+            "Device or resource busy",
+        ));
+        assert_eq!(busy.kind(), std::io::ErrorKind::Other);
+        assert!(busy.to_string().contains("in use by another process"));
+
+        let denied = serial_open_error(tokio_serial::Error::new(
+            tokio_serial::ErrorKind::Io(std::io::ErrorKind::PermissionDenied),
+            "Permission denied",
+        ));
+        assert_eq!(denied.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(denied.to_string().contains("dialout"));
+
+        let missing = serial_open_error(tokio_serial::Error::new(
+            tokio_serial::ErrorKind::Io(std::io::ErrorKind::NotFound),
+            "No such file or directory",
+        ));
+        assert_eq!(missing.kind(), std::io::ErrorKind::NotFound);
+        assert!(missing
+            .to_string()
+            .starts_with("No such file or directory: "));
+    }
+
+    #[test]
+    fn test_missing_device_message_lists_ports() {
+        let ports = ["/dev/ttyUSB0".to_string(), "/dev/ttyACM0".to_string()];
+        assert_eq!(
+            missing_device_message("No such file or directory", &ports),
+            "No such file or directory: no such serial device; available ports: /dev/ttyUSB0, /dev/ttyACM0"
+        );
+        assert!(missing_device_message("No such file or directory", &[])
+            .contains("no serial ports were found"));
+    }
+
+    #[test]
+    fn test_retryable_exceptions_and_delay() {
+        assert!(is_retryable_exception(ExceptionCode::ServerDeviceBusy));
+        assert!(is_retryable_exception(ExceptionCode::Acknowledge));
+        assert!(!is_retryable_exception(ExceptionCode::IllegalDataAddress));
+        assert_eq!(exception_retry_delay(1), Duration::from_millis(100));
+        assert_eq!(exception_retry_delay(3), Duration::from_millis(400));
+        assert_eq!(exception_retry_delay(30), Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_operation_retried_on_busy_only() {
+        let mut calls = 0;
+        let result = modbus_operation_with_timeout(
+            async || {
+                calls += 1;
+                Ok(if calls < 3 {
+                    Err(ExceptionCode::ServerDeviceBusy)
+                } else {
+                    Ok(7u16)
+                })
+            },
+            "test operation",
+            OpSettings {
+                timeout_secs: 5,
+                exception_retries: 2,
+                timeout_retries: 0,
+            },
+        )
+        .await;
+        assert_eq!(result.unwrap(), 7);
+        assert_eq!(calls, 3);
+
+        // An illegal address won't change by asking again
+        let mut calls = 0;
+        let result: anyhow::Result<u16> = modbus_operation_with_timeout(
+            async || {
+                calls += 1;
+                Ok(Err(ExceptionCode::IllegalDataAddress))
+            },
+            "test operation",
+            OpSettings {
+                timeout_secs: 5,
+                exception_retries: 2,
+                timeout_retries: 0,
+            },
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_operation_resent_after_timeout() {
+        // The first request is lost; a zero timeout gives up on it as soon as it is pending
+        let mut calls = 0;
+        let result = modbus_operation_with_timeout(
+            async || {
+                calls += 1;
+                if calls == 1 {
+                    std::future::pending::<()>().await;
+                }
+                Ok(Ok::<_, ExceptionCode>(7u16))
+            },
+            "test operation",
+            OpSettings {
+                timeout_secs: 0,
+                exception_retries: 0,
+                timeout_retries: 1,
+            },
+        )
+        .await;
+        assert_eq!(result.unwrap(), 7);
+        assert_eq!(calls, 2);
+
+        // Without resends the timeout is the answer
+        let result: anyhow::Result<u16> = modbus_operation_with_timeout(
+            async || {
+                std::future::pending::<()>().await;
+                Ok(Ok::<_, ExceptionCode>(7))
+            },
+            "test operation",
+            OpSettings {
+                timeout_secs: 0,
+                exception_retries: 0,
+                timeout_retries: 0,
+            },
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_handle_modbus_response_with_timeout_success() {
         let runtime = tokio::runtime::Runtime::new().unwrap();
@@ -235,6 +945,214 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_is_connection_error() {
+        let io_error = std::io::Error::new(std::io::ErrorKind::BrokenPipe, "broken pipe");
+        let transport: anyhow::Error = tokio_modbus::Error::Transport(io_error).into();
+        assert!(is_connection_error(&transport));
+
+        let refused: anyhow::Error =
+            std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused").into();
+        assert!(is_connection_error(&refused));
+
+        let exception =
+            anyhow::anyhow!("Modbus exception: {:?}", ExceptionCode::IllegalDataAddress);
+        assert!(!is_connection_error(&exception));
+    }
+
+    fn tcp_common(port: u16) -> Common {
+        use clap::Parser;
+        let port = port.to_string();
+        let cli = crate::cli::Cli::try_parse_from([
+            "mb",
+            "ping",
+            "--ip",
+            "127.0.0.1",
+            "--port",
+            &port,
+            "--timeout",
+            "2",
+        ])
+        .unwrap();
+        match cli.cmd {
+            crate::cli::Command::Ping { common } => common,
+            _ => panic!("Expected ping command"),
+        }
+    }
+
+    #[test]
+    fn test_connect_error_kinds() {
+        let common = tcp_common(1502);
+        let io = |kind| std::io::Error::new(kind, "test");
+
+        let refused = ConnectError::from_io(&common, io(std::io::ErrorKind::ConnectionRefused));
+        assert_eq!(refused.to_string(), "Connection refused by 127.0.0.1:1502");
+        assert_eq!(refused.exit_code(), 3);
+
+        let timed_out = ConnectError::from_io(&common, io(std::io::ErrorKind::TimedOut));
+        assert_eq!(
+            timed_out.to_string(),
+            "Connection to 127.0.0.1:1502 timed out after 2 seconds"
+        );
+        assert_eq!(timed_out.exit_code(), 4);
+
+        let other = ConnectError::from_io(&common, io(std::io::ErrorKind::NotFound));
+        assert_eq!(
+            other.to_string(),
+            "Failed to connect to 127.0.0.1:1502: test"
+        );
+        assert_eq!(other.exit_code(), 5);
+    }
+
+    #[test]
+    fn test_op_settings_from_flags() {
+        let mut common = tcp_common(1502);
+        common.retries = 3;
+        let plain = OpSettings::new(&common);
+        assert_eq!(
+            (
+                plain.timeout_secs,
+                plain.exception_retries,
+                plain.timeout_retries
+            ),
+            (2, 0, 0)
+        );
+
+        common.retry_on_exception = true;
+        common.udp = true;
+        let retrying = OpSettings::new(&common);
+        assert_eq!(
+            (retrying.exception_retries, retrying.timeout_retries),
+            (3, 3)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ping_retries_first_failing_server() {
+        use crate::server::{ModbusData, ModbusService, ServerOptions};
+        use std::sync::atomic::AtomicUsize;
+        use std::sync::Arc;
+        use tokio_modbus::server::Service;
+
+        // Like `mb server --fail-first 2`: the first two requests are answered Busy
+        let fail_first = || {
+            let options = ServerOptions {
+                busy_requests: Arc::new(AtomicUsize::new(2)),
+                ..Default::default()
+            };
+            let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(1, 1, 1, 1)));
+            ModbusService::new(data, options)
+        };
+        let ping = async |service: &ModbusService, common: &Common| {
+            let request = || SlaveRequest {
+                slave: 1,
+                request: Request::ReadHoldingRegisters(0, 1),
+            };
+            await_ping_reply(
+                async || Ok(service.call(request()).await),
+                OpSettings::new(common),
+            )
+            .await
+        };
+
+        let mut common = tcp_common(1502);
+        common.retries = 2;
+        assert!(ping(&fail_first(), &common).await.is_err());
+
+        common.retry_on_exception = true;
+        assert!(ping(&fail_first(), &common).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rtu_refuses_mbap_direct_unit() {
+        let mut common = tcp_common(1502);
+        common.ip = None;
+        common.device = Some("/dev/ttyUSB0".into());
+        common.unit = MBAP_DIRECT_UNIT;
+        let error = connect_to_modbus(&common).await.unwrap_err();
+        assert!(error.to_string().contains("reserved on serial lines"));
+    }
+
+    #[test]
+    fn test_error_tally_display() {
+        let mut tally = ErrorTally {
+            operations: 1,
+            ..Default::default()
+        };
+        assert_eq!(tally.to_string(), "1 operation, no errors");
+
+        tally.operations = 120;
+        tally.timeouts = 3;
+        tally
+            .exceptions
+            .insert(format!("{:?}", ExceptionCode::IllegalDataAddress), 1);
+        assert_eq!(
+            tally.to_string(),
+            "120 operations, 3 timeouts, 1 illegal data address"
+        );
+    }
+
+    #[test]
+    fn test_plan_read_spans() {
+        assert_eq!(plan_read_spans(&[], 125, None), []);
+        assert_eq!(plan_read_spans(&[7], 125, None), [(7, 1)]);
+        assert_eq!(plan_read_spans(&[0, 5, 100, 101], 125, None), [(0, 102)]);
+        assert_eq!(
+            plan_read_spans(&[0, 5, 200, 201], 125, None),
+            [(0, 6), (200, 2)]
+        );
+        // A span never exceeds the per-request limit
+        assert_eq!(
+            plan_read_spans(&[0, 124, 125], 125, None),
+            [(0, 125), (125, 1)]
+        );
+    }
+
+    #[test]
+    fn test_plan_read_spans_max_gap() {
+        // 1-4 lie between 0 and 5: a gap of 4 is bridged, a smaller limit splits it
+        assert_eq!(
+            plan_read_spans(&[0, 5, 100, 101], 125, Some(4)),
+            [(0, 6), (100, 2)]
+        );
+        assert_eq!(
+            plan_read_spans(&[0, 5, 100, 101], 125, Some(3)),
+            [(0, 1), (5, 1), (100, 2)]
+        );
+        // Adjacent addresses have no gap, so even 0 keeps them in one read
+        assert_eq!(
+            plan_read_spans(&[0, 1, 2, 4], 125, Some(0)),
+            [(0, 3), (4, 1)]
+        );
+        // The per-request limit still applies within the gap
+        assert_eq!(
+            plan_read_spans(&[0, 124, 125], 125, Some(200)),
+            [(0, 125), (125, 1)]
+        );
+    }
+
+    #[test]
+    fn test_describe_read_request() {
+        assert_eq!(
+            describe_read_request(Area::Holding, 0, 10, 1),
+            "Request: FC 03 read holding registers, unit 1, address 0, quantity 10"
+        );
+        assert_eq!(
+            describe_read_request(Area::Discrete, 100, 16, 247),
+            "Request: FC 02 read discrete inputs, unit 247, address 100, quantity 16"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_next_poll_without_watch() {
+        assert!(!wait_for_next_poll(None).await);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_next_poll_with_watch() {
+        assert!(wait_for_next_poll(Some(Duration::from_millis(1))).await);
+    }
+
     // Test timeout configuration ranges
     #[test]
     fn test_timeout_duration_creation() {
@@ -247,4 +1165,15 @@ mod tests {
         let duration_max = Duration::from_secs(u64::MAX);
         assert_eq!(duration_max.as_secs(), u64::MAX);
     }
+
+    #[test]
+    fn test_connect_error_json() {
+        let refused = ConnectError::Refused {
+            target: "127.0.0.1:502".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_string(&refused.json()).unwrap(),
+            r#"{"error":"connection_refused","target":"127.0.0.1:502","message":"Connection refused by 127.0.0.1:502"}"#
+        );
+    }
 }
@@ -0,0 +1,210 @@
+use crate::client::handle_modbus_response_with_timeout;
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicU16, Ordering};
+use tokio::time::{timeout, Duration};
+use tokio_modbus::client;
+use tokio_modbus::prelude::*;
+
+// Function code for Diagnostics; tokio-modbus has no typed request for it
+pub const DIAGNOSTICS: u8 = 0x08;
+
+// Sub-function that zeroes every counter
+const CLEAR_COUNTERS: u16 = 0x0A;
+
+/// The counter sub-functions of FC 8, in the order the specification lists them
+pub const COUNTERS: [(u16, &str); 8] = [
+    (0x0B, "Bus messages"),
+    (0x0C, "Bus communication errors"),
+    (0x0D, "Bus exception errors"),
+    (0x0E, "Server messages"),
+    (0x0F, "Server no responses"),
+    (0x10, "Server NAKs"),
+    (0x11, "Server busy"),
+    (0x12, "Bus character overruns"),
+];
+
+/// Counters the built-in server keeps for FC 8. Like a device's, they are 16 bits wide and
+/// roll over. The server never sees bad frames, overruns or NAKs, so those always read 0
+#[derive(Debug, Default)]
+pub struct DiagCounters {
+    messages: AtomicU16,
+    exceptions: AtomicU16,
+    busy: AtomicU16,
+    no_responses: AtomicU16,
+}
+
+impl DiagCounters {
+    pub fn message_received(&self) {
+        self.messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn exception_sent(&self, code: ExceptionCode) {
+        self.exceptions.fetch_add(1, Ordering::Relaxed);
+        if code == ExceptionCode::ServerDeviceBusy {
+            self.busy.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// A message was handled without a reply, as broadcasts are
+    pub fn no_response_sent(&self) {
+        self.no_responses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        for counter in [
+            &self.messages,
+            &self.exceptions,
+            &self.busy,
+            &self.no_responses,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    // The value a counter sub-function reports, or None for sub-functions that aren't counters
+    fn value(&self, sub_function: u16) -> Option<u16> {
+        let messages = self.messages.load(Ordering::Relaxed);
+        match sub_function {
+            // Every message reaches this server, so it sees the whole bus
+            0x0B | 0x0E => Some(messages),
+            0x0D => Some(self.exceptions.load(Ordering::Relaxed)),
+            0x0F => Some(self.no_responses.load(Ordering::Relaxed)),
+            0x11 => Some(self.busy.load(Ordering::Relaxed)),
+            0x0C | 0x10 | 0x12 => Some(0),
+            _ => None,
+        }
+    }
+
+    /// Answer an FC 8 request: a counter's value, or an echo once the counters are cleared
+    pub fn respond(&self, request_data: &[u8]) -> Result<Vec<u8>, ExceptionCode> {
+        let &[sub_hi, sub_lo, 0, 0] = request_data else {
+            return Err(ExceptionCode::IllegalDataValue);
+        };
+        let sub_function = u16::from_be_bytes([sub_hi, sub_lo]);
+        if sub_function == CLEAR_COUNTERS {
+            self.clear();
+            return Ok(request_data.to_vec());
+        }
+        let value = self
+            .value(sub_function)
+            .ok_or(ExceptionCode::IllegalFunction)?;
+        Ok(response_data(sub_function, value))
+    }
+}
+
+// FC 8 request data: the sub-function and a zero data field
+pub fn request_data(sub_function: u16) -> Vec<u8> {
+    let mut data = sub_function.to_be_bytes().to_vec();
+    data.extend([0, 0]);
+    data
+}
+
+// FC 8 counter response data: the sub-function echoed, then the counter
+pub fn response_data(sub_function: u16, value: u16) -> Vec<u8> {
+    let mut data = sub_function.to_be_bytes().to_vec();
+    data.extend(value.to_be_bytes());
+    data
+}
+
+// The counter from an FC 8 response, which must echo the sub-function asked for
+pub fn parse_response(sub_function: u16, data: &[u8]) -> Result<u16, String> {
+    let &[sub_hi, sub_lo, value_hi, value_lo] = data else {
+        return Err(format!(
+            "Diagnostics response carries {} bytes instead of 4",
+            data.len()
+        ));
+    };
+    let echoed = u16::from_be_bytes([sub_hi, sub_lo]);
+    if echoed != sub_function {
+        return Err(format!(
+            "Diagnostics response is for sub-function {echoed:#06X}, not {sub_function:#06X}"
+        ));
+    }
+    Ok(u16::from_be_bytes([value_hi, value_lo]))
+}
+
+/// Query every counter sub-function, one request each. A counter the device answers with an
+/// exception is `None`, since devices need not support them all; other failures end the query
+pub async fn read_diagnostic_counters(
+    ctx: &mut client::Context,
+    timeout_secs: u64,
+) -> anyhow::Result<Vec<(&'static str, Option<u16>)>> {
+    let mut counters = Vec::new();
+    for (sub_function, name) in COUNTERS {
+        let request = Request::Custom(DIAGNOSTICS, Cow::Owned(request_data(sub_function)));
+        let result = timeout(Duration::from_secs(timeout_secs), ctx.call(request)).await;
+        if let Ok(Ok(Err(_))) = result {
+            counters.push((name, None));
+            continue;
+        }
+        match handle_modbus_response_with_timeout(result, "read diagnostic counter", timeout_secs)
+            .await?
+        {
+            Response::Custom(DIAGNOSTICS, data) => {
+                let value = parse_response(sub_function, &data).map_err(|e| anyhow::anyhow!(e))?;
+                counters.push((name, Some(value)));
+            }
+            other => {
+                return Err(anyhow::anyhow!(
+                    "Unexpected response to diagnostics: {other:?}"
+                ))
+            }
+        }
+    }
+    Ok(counters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostics_request_and_response() {
+        assert_eq!(request_data(0x0B), [0x00, 0x0B, 0x00, 0x00]);
+        let data = response_data(0x0B, 0x0123);
+        assert_eq!(data, [0x00, 0x0B, 0x01, 0x23]);
+        assert_eq!(parse_response(0x0B, &data), Ok(0x0123));
+        // The echo must match the sub-function asked for
+        assert!(parse_response(0x0C, &data).is_err());
+        assert!(parse_response(0x0B, &data[..3]).is_err());
+    }
+
+    #[test]
+    fn test_diag_counters_respond() {
+        let counters = DiagCounters::default();
+        for _ in 0..3 {
+            counters.message_received();
+        }
+        counters.exception_sent(ExceptionCode::IllegalDataAddress);
+        counters.exception_sent(ExceptionCode::ServerDeviceBusy);
+        counters.no_response_sent();
+
+        let read = |sub_function| {
+            let data = counters.respond(&request_data(sub_function)).unwrap();
+            parse_response(sub_function, &data).unwrap()
+        };
+        assert_eq!(read(0x0B), 3);
+        assert_eq!(read(0x0E), 3);
+        assert_eq!(read(0x0D), 2);
+        assert_eq!(read(0x11), 1);
+        assert_eq!(read(0x0F), 1);
+        assert_eq!(read(0x0C), 0);
+
+        // Clearing echoes the request and zeroes everything
+        let clear = request_data(CLEAR_COUNTERS);
+        assert_eq!(counters.respond(&clear), Ok(clear.clone()));
+        assert_eq!(read(0x0B), 0);
+        assert_eq!(read(0x0D), 0);
+        assert_eq!(read(0x0F), 0);
+
+        // Sub-functions that aren't counters, and malformed requests, are refused
+        assert_eq!(
+            counters.respond(&request_data(0x04)),
+            Err(ExceptionCode::IllegalFunction)
+        );
+        assert_eq!(
+            counters.respond(&[0x00, 0x0B]),
+            Err(ExceptionCode::IllegalDataValue)
+        );
+    }
+}
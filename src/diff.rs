@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+/// An address whose value differs between device A and device B.
+/// `None` means that device did not return a value for the address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Difference {
+    pub addr: u16,
+    pub a: Option<u16>,
+    pub b: Option<u16>,
+}
+
+/// Compare two reads of the same range, in address order
+pub fn compare(a: &[(u16, u16)], b: &[(u16, u16)]) -> Vec<Difference> {
+    let mut merged: BTreeMap<u16, (Option<u16>, Option<u16>)> = BTreeMap::new();
+    for &(addr, value) in a {
+        merged.entry(addr).or_default().0 = Some(value);
+    }
+    for &(addr, value) in b {
+        merged.entry(addr).or_default().1 = Some(value);
+    }
+    merged
+        .into_iter()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(addr, (a, b))| Difference { addr, a, b })
+        .collect()
+}
+
+/// Table of differing addresses with the A and B values side by side
+pub fn format_differences(differences: &[Difference]) -> Vec<String> {
+    let show = |value: Option<u16>| value.map_or("-".to_string(), |value| value.to_string());
+    let mut lines = vec![format!("{:>7}  {:>6}  {:>6}", "Address", "A", "B")];
+    lines.push(format!(
+        "{:>7}  {:>6}  {:>6}",
+        "-------", "------", "------"
+    ));
+    lines.extend(differences.iter().map(|difference| {
+        format!(
+            "{:>7}  {:>6}  {:>6}",
+            difference.addr,
+            show(difference.a),
+            show(difference.b)
+        )
+    }));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_identical() {
+        let rows = [(0, 1), (1, 2)];
+        assert!(compare(&rows, &rows).is_empty());
+    }
+
+    #[test]
+    fn test_compare_reports_changed_and_missing() {
+        let a = [(0, 1), (1, 2), (2, 3)];
+        let b = [(0, 1), (1, 5)];
+        assert_eq!(
+            compare(&a, &b),
+            vec![
+                Difference {
+                    addr: 1,
+                    a: Some(2),
+                    b: Some(5)
+                },
+                Difference {
+                    addr: 2,
+                    a: Some(3),
+                    b: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_differences() {
+        let lines = format_differences(&[Difference {
+            addr: 100,
+            a: Some(42),
+            b: None,
+        }]);
+        assert_eq!(lines[0], "Address       A       B");
+        assert_eq!(lines[2], "    100      42       -");
+    }
+}
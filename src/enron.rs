@@ -0,0 +1,120 @@
+use crate::cli::{Area, ValueFormat};
+use crate::client::{modbus_operation_with_timeout, OpSettings};
+use crate::table::{combine_words, Decoded, DecodedRow, TableOptions};
+use tokio_modbus::prelude::*;
+
+/// Most 32-bit Enron registers one read can return: 250 data bytes at 4 bytes each
+pub const MAX_ENRON_QTY: u16 = 62;
+
+// Split an Enron reply into one (address, [high word, low word]) pair per 32-bit register.
+// Enron addresses count whole 32-bit registers, so consecutive values are one address apart
+fn pair_words(start: u16, qty: u16, words: &[u16]) -> Result<Vec<(u16, [u16; 2])>, String> {
+    if words.len() != qty as usize * 2 {
+        return Err(format!(
+            "Requested {qty} 32-bit register(s) ({} words) but the device returned {} words; is it really using Enron Modbus?",
+            qty as usize * 2,
+            words.len()
+        ));
+    }
+    Ok(words
+        .chunks_exact(2)
+        .enumerate()
+        .map(|(i, pair)| (start.wrapping_add(i as u16), [pair[0], pair[1]]))
+        .collect())
+}
+
+/// Read `qty` Enron Modbus 32-bit registers with FC 03/04. The request is a standard one, but
+/// the quantity counts 32-bit registers and the reply carries four bytes for each
+pub async fn read_enron_registers(
+    ctx: &mut client::Context,
+    area: Area,
+    start: u16,
+    qty: u16,
+    op: OpSettings,
+) -> anyhow::Result<Vec<(u16, [u16; 2])>> {
+    let (request, operation) = match area {
+        Area::Input => (
+            Request::ReadInputRegisters(start, qty),
+            "read input registers",
+        ),
+        _ => (
+            Request::ReadHoldingRegisters(start, qty),
+            "read holding registers",
+        ),
+    };
+    // Called directly rather than through `read_holding_registers`, which expects one word per
+    // counted register
+    match modbus_operation_with_timeout(async || ctx.call(request.clone()).await, operation, op)
+        .await?
+    {
+        Response::ReadHoldingRegisters(words) | Response::ReadInputRegisters(words) => {
+            pair_words(start, qty, &words).map_err(|e| anyhow::anyhow!(e))
+        }
+        other => Err(anyhow::anyhow!(
+            "Unexpected response to {operation}: {other:?}"
+        )),
+    }
+}
+
+/// Decode Enron registers as unsigned 32-bit integers, or as floats with `--format float32`,
+/// honouring `--byte-swap` and `--word-order` like standard two-register values
+pub fn decode_enron(registers: &[(u16, [u16; 2])], options: &TableOptions) -> Vec<DecodedRow> {
+    registers
+        .iter()
+        .map(|&(addr, words)| {
+            let raw = words.map(|word| {
+                if options.byte_swap {
+                    word.swap_bytes()
+                } else {
+                    word
+                }
+            });
+            let bits = combine_words(&raw, options.word_order);
+            let value = match options.format {
+                ValueFormat::Float32 => Decoded::Float32(f32::from_bits(bits as u32)),
+                _ => Decoded::Unsigned(bits),
+            };
+            DecodedRow {
+                addr,
+                raw: raw.to_vec(),
+                value,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pair_words() {
+        let pairs = pair_words(5001, 2, &[0x0001, 0x0002, 0x4148, 0x0000]).unwrap();
+        assert_eq!(pairs, [(5001, [0x0001, 0x0002]), (5002, [0x4148, 0x0000])]);
+    }
+
+    #[test]
+    fn test_decode_enron() {
+        let registers = [(5001, [0x0001, 0x0002])];
+        let rows = decode_enron(&registers, &TableOptions::default());
+        assert_eq!(
+            (rows[0].addr, rows[0].value),
+            (5001, Decoded::Unsigned(0x0001_0002))
+        );
+
+        let float = TableOptions {
+            format: ValueFormat::Float32,
+            word_order: crate::cli::WordOrder::Little,
+            ..Default::default()
+        };
+        let rows = decode_enron(&[(7001, [0x0000, 0x4148])], &float);
+        assert_eq!(rows[0].value, Decoded::Float32(12.5));
+    }
+
+    #[test]
+    fn test_pair_words_rejects_standard_replies() {
+        // A standard device answers a quantity of 2 with two 16-bit words
+        let error = pair_words(7001, 2, &[1, 2]).unwrap_err();
+        assert!(error.contains("returned 2 words"));
+    }
+}
@@ -0,0 +1,130 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+
+// Events buffered per subscriber; a subscriber that falls further behind skips the oldest
+const EVENT_BUFFER: usize = 256;
+
+/// One server event, sent to subscribers as an SSE `event:` name and JSON `data:` line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub kind: &'static str,
+    pub data: String,
+}
+
+impl Event {
+    // Server-Sent Events frame for this event
+    fn frame(&self) -> String {
+        format!("event: {}\ndata: {}\n\n", self.kind, self.data)
+    }
+}
+
+/// Fan-out of read and write events to every connected `--events-port` subscriber
+#[derive(Debug, Clone)]
+pub struct EventStream {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for EventStream {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(EVENT_BUFFER).0,
+        }
+    }
+}
+
+impl EventStream {
+    /// Send an event to the current subscribers; with none connected it is dropped
+    pub fn publish(&self, kind: &'static str, data: String) {
+        let _ = self.sender.send(Event { kind, data });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+// Stream events to `GET /events` subscribers until they disconnect; every other path is a 404
+pub async fn serve_events(listener: TcpListener, events: EventStream) {
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!("Events endpoint: {e}");
+                continue;
+            }
+        };
+        tokio::spawn(stream_events(stream, events.subscribe()));
+    }
+}
+
+async fn stream_events(mut stream: TcpStream, mut receiver: broadcast::Receiver<Event>) {
+    let mut buffer = [0u8; 1024];
+    let len = stream.read(&mut buffer).await.unwrap_or(0);
+    if !String::from_utf8_lossy(&buffer[..len]).starts_with("GET /events ") {
+        let _ = stream
+            .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+            .await;
+        return;
+    }
+
+    let headers = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n";
+    if stream.write_all(headers.as_bytes()).await.is_err() {
+        return;
+    }
+    loop {
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => Event {
+                kind: "lagged",
+                data: serde_json::json!({ "skipped": skipped }).to_string(),
+            },
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if stream.write_all(event.frame().as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_frame() {
+        let event = Event {
+            kind: "write",
+            data: r#"{"unit":1}"#.to_string(),
+        };
+        assert_eq!(event.frame(), "event: write\ndata: {\"unit\":1}\n\n");
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_receive_published_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let events = EventStream::default();
+        tokio::spawn(serve_events(listener, events.clone()));
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(b"GET /events HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut received = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !String::from_utf8_lossy(&received).contains("\r\n\r\n") {
+            let len = client.read(&mut buffer).await.unwrap();
+            received.extend_from_slice(&buffer[..len]);
+        }
+        assert!(String::from_utf8_lossy(&received).contains("text/event-stream"));
+
+        events.publish("read", "{}".to_string());
+        while !String::from_utf8_lossy(&received).ends_with("\n\n") {
+            let len = client.read(&mut buffer).await.unwrap();
+            received.extend_from_slice(&buffer[..len]);
+        }
+        assert!(String::from_utf8_lossy(&received).ends_with("event: read\ndata: {}\n\n"));
+    }
+}
@@ -0,0 +1,99 @@
+use crate::client::{modbus_operation_with_timeout, OpSettings};
+use std::borrow::Cow;
+use tokio_modbus::client;
+use tokio_modbus::prelude::*;
+
+// Function code for Read FIFO Queue; tokio-modbus has no typed request for it
+pub const READ_FIFO_QUEUE: u8 = 0x18;
+
+/// Most values a FIFO queue may hold; the specification makes a longer queue an error
+pub const MAX_FIFO_COUNT: usize = 31;
+
+// FC24 request data: the FIFO pointer address
+pub fn request_data(pointer: u16) -> Vec<u8> {
+    pointer.to_be_bytes().to_vec()
+}
+
+// FC24 response data: byte count (of the FIFO count and values), FIFO count, then the values
+pub fn response_data(values: &[u16]) -> Vec<u8> {
+    let byte_count = 2 + 2 * values.len() as u16;
+    let mut data = byte_count.to_be_bytes().to_vec();
+    data.extend((values.len() as u16).to_be_bytes());
+    for value in values {
+        data.extend(value.to_be_bytes());
+    }
+    data
+}
+
+// Queue values from an FC24 response
+pub fn parse_response(data: &[u8]) -> Result<Vec<u16>, String> {
+    let [byte_hi, byte_lo, count_hi, count_lo, values @ ..] = data else {
+        return Err("FIFO queue response is too short".to_string());
+    };
+    let byte_count = u16::from_be_bytes([*byte_hi, *byte_lo]) as usize;
+    let count = u16::from_be_bytes([*count_hi, *count_lo]) as usize;
+    if byte_count != values.len() + 2 || values.len() != count * 2 {
+        return Err(format!(
+            "FIFO queue response claims {count} value(s) in {byte_count} bytes but carries {} bytes",
+            values.len()
+        ));
+    }
+    if count > MAX_FIFO_COUNT {
+        return Err(format!(
+            "FIFO queue holds {count} values, more than the {MAX_FIFO_COUNT} allowed"
+        ));
+    }
+    Ok(values
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect())
+}
+
+pub async fn read_fifo_queue(
+    ctx: &mut client::Context,
+    pointer: u16,
+    op: OpSettings,
+) -> anyhow::Result<Vec<u16>> {
+    let request = Request::Custom(READ_FIFO_QUEUE, Cow::Owned(request_data(pointer)));
+    match modbus_operation_with_timeout(
+        async || ctx.call(request.clone()).await,
+        "read FIFO queue",
+        op,
+    )
+    .await?
+    {
+        Response::Custom(READ_FIFO_QUEUE, data) => {
+            parse_response(&data).map_err(|e| anyhow::anyhow!(e))
+        }
+        other => Err(anyhow::anyhow!(
+            "Unexpected response to read FIFO queue: {other:?}"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fifo_request_and_response() {
+        assert_eq!(request_data(0x04DE), [0x04, 0xDE]);
+
+        let data = response_data(&[0x01B8, 0x1284]);
+        assert_eq!(data, [0x00, 0x06, 0x00, 0x02, 0x01, 0xB8, 0x12, 0x84]);
+        assert_eq!(parse_response(&data).unwrap(), [0x01B8, 0x1284]);
+        assert!(parse_response(&response_data(&[])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_fifo_response_errors() {
+        assert!(parse_response(&[0x00, 0x06]).is_err());
+        // Byte count disagrees with the data
+        assert!(parse_response(&[0x00, 0x08, 0x00, 0x02, 0x01, 0xB8, 0x12, 0x84]).is_err());
+        // FIFO count disagrees with the data
+        assert!(parse_response(&[0x00, 0x06, 0x00, 0x01, 0x01, 0xB8, 0x12, 0x84]).is_err());
+        // Longer than the specification allows
+        let values = [0u16; 32];
+        assert!(parse_response(&response_data(&values)).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+use crate::client::{modbus_operation_with_timeout, OpSettings};
+use std::borrow::Cow;
+use tokio_modbus::client;
+use tokio_modbus::prelude::*;
+
+// Function codes for file record access; tokio-modbus has no typed requests for these
+const READ_FILE_RECORD: u8 = 0x14;
+const WRITE_FILE_RECORD: u8 = 0x15;
+// Every sub-request uses reference type 6
+const REFERENCE_TYPE: u8 = 0x06;
+
+/// Most registers one FC20 response can carry within a 253-byte PDU
+pub const MAX_READ_RECORD_LEN: u16 = 124;
+/// Most registers one FC21 request can carry within a 253-byte PDU
+pub const MAX_WRITE_RECORD_LEN: u16 = 122;
+
+// Sub-request header shared by FC20 and FC21: reference type, file, record, length
+fn sub_request_header(file: u16, record: u16, length: u16) -> Vec<u8> {
+    let mut data = vec![REFERENCE_TYPE];
+    data.extend(file.to_be_bytes());
+    data.extend(record.to_be_bytes());
+    data.extend(length.to_be_bytes());
+    data
+}
+
+// FC20 request data for a single sub-request
+pub fn read_request_data(file: u16, record: u16, length: u16) -> Vec<u8> {
+    let sub_request = sub_request_header(file, record, length);
+    let mut data = vec![sub_request.len() as u8];
+    data.extend(sub_request);
+    data
+}
+
+// FC21 request data for a single sub-request carrying `values`
+pub fn write_request_data(file: u16, record: u16, values: &[u16]) -> Vec<u8> {
+    let mut sub_request = sub_request_header(file, record, values.len() as u16);
+    for value in values {
+        sub_request.extend(value.to_be_bytes());
+    }
+    let mut data = vec![sub_request.len() as u8];
+    data.extend(sub_request);
+    data
+}
+
+// Registers from an FC20 response holding one sub-response
+pub fn parse_read_response(data: &[u8]) -> Result<Vec<u16>, String> {
+    let (&total_len, rest) = data.split_first().ok_or("empty file record response")?;
+    if rest.len() != total_len as usize {
+        return Err(format!(
+            "file record response length {total_len} does not match the {} bytes received",
+            rest.len()
+        ));
+    }
+    let [file_len, reference_type, records @ ..] = rest else {
+        return Err("file record response is too short".to_string());
+    };
+    if *reference_type != REFERENCE_TYPE {
+        return Err(format!(
+            "unexpected reference type {reference_type} in file record response"
+        ));
+    }
+    // The sub-response length counts the reference type byte plus the record data
+    if *file_len as usize != records.len() + 1 || records.len() % 2 != 0 {
+        return Err("malformed file record sub-response".to_string());
+    }
+    Ok(records
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect())
+}
+
+pub async fn read_file_record(
+    ctx: &mut client::Context,
+    file: u16,
+    record: u16,
+    length: u16,
+    op: OpSettings,
+) -> anyhow::Result<Vec<u16>> {
+    let request = Request::Custom(
+        READ_FILE_RECORD,
+        Cow::Owned(read_request_data(file, record, length)),
+    );
+    match modbus_operation_with_timeout(
+        async || ctx.call(request.clone()).await,
+        "read file record",
+        op,
+    )
+    .await?
+    {
+        Response::Custom(READ_FILE_RECORD, data) => {
+            let values = parse_read_response(&data).map_err(|e| anyhow::anyhow!(e))?;
+            if values.len() != length as usize {
+                return Err(anyhow::anyhow!(
+                    "Requested {length} registers but the device returned {}",
+                    values.len()
+                ));
+            }
+            Ok(values)
+        }
+        other => Err(anyhow::anyhow!(
+            "Unexpected response to read file record: {other:?}"
+        )),
+    }
+}
+
+pub async fn write_file_record(
+    ctx: &mut client::Context,
+    file: u16,
+    record: u16,
+    values: &[u16],
+    op: OpSettings,
+) -> anyhow::Result<()> {
+    let data = write_request_data(file, record, values);
+    let request = Request::Custom(WRITE_FILE_RECORD, Cow::Borrowed(&data));
+    match modbus_operation_with_timeout(
+        async || ctx.call(request.clone()).await,
+        "write file record",
+        op,
+    )
+    .await?
+    {
+        // A normal FC21 response echoes the request
+        Response::Custom(WRITE_FILE_RECORD, echoed) if *echoed == *data => Ok(()),
+        other => Err(anyhow::anyhow!(
+            "Unexpected response to write file record: {other:?}"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_request_data() {
+        // Spec example: file 4, record 1, length 2
+        assert_eq!(
+            read_request_data(4, 1, 2),
+            [0x07, 0x06, 0x00, 0x04, 0x00, 0x01, 0x00, 0x02]
+        );
+    }
+
+    #[test]
+    fn test_write_request_data() {
+        // Spec example: file 4, record 7, values 0x06AF 0x04BE 0x100D
+        assert_eq!(
+            write_request_data(4, 7, &[0x06AF, 0x04BE, 0x100D]),
+            [0x0D, 0x06, 0x00, 0x04, 0x00, 0x07, 0x00, 0x03, 0x06, 0xAF, 0x04, 0xBE, 0x10, 0x0D]
+        );
+    }
+
+    #[test]
+    fn test_parse_read_response() {
+        let data = [0x06, 0x05, 0x06, 0x0D, 0xFE, 0x00, 0x20];
+        assert_eq!(parse_read_response(&data), Ok(vec![0x0DFE, 0x0020]));
+
+        assert!(parse_read_response(&[]).is_err());
+        assert!(parse_read_response(&[0x06, 0x05, 0x06, 0x0D]).is_err());
+        assert!(parse_read_response(&[0x04, 0x03, 0x07, 0x0D, 0xFE]).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Modbus RTU CRC16 (polynomial 0xA001, initial value 0xFFFF). It is sent low byte first.
+pub fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for &byte in bytes {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+// The CRC computed over a frame and the one in its last two bytes, if it is long enough to have one
+fn frame_crcs(frame: &[u8]) -> Option<(u16, u16)> {
+    let split = frame.len().checked_sub(2).filter(|&split| split > 0)?;
+    let (body, crc) = frame.split_at(split);
+    Some((crc16(body), u16::from_le_bytes([crc[0], crc[1]])))
+}
+
+// One line describing a raw frame, e.g. `TX 01 03 00 00 00 01 84 0A (CRC 0x0A84 ok)`
+pub fn describe_frame(direction: &str, frame: &[u8]) -> String {
+    let hex: Vec<String> = frame.iter().map(|byte| format!("{byte:02X}")).collect();
+    let check = match frame_crcs(frame) {
+        Some((computed, received)) if computed == received => format!("CRC 0x{computed:04X} ok"),
+        Some((computed, received)) => {
+            format!("CRC mismatch: computed 0x{computed:04X}, received 0x{received:04X}")
+        }
+        None => "too short for a CRC".to_string(),
+    };
+    format!("{direction} {} ({check})", hex.join(" "))
+}
+
+/// Serial transport that prints every request and response frame in hex to stderr.
+///
+/// Requests are printed when tokio-modbus flushes them. Response bytes are collected until
+/// they end in a valid CRC, or until the next request if they never do, so a garbled or
+/// truncated reply still shows up with the CRC it should have had.
+#[derive(Debug)]
+pub struct FrameLog<T> {
+    inner: T,
+    tx: Vec<u8>,
+    rx: Vec<u8>,
+}
+
+impl<T> FrameLog<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            tx: Vec::new(),
+            rx: Vec::new(),
+        }
+    }
+
+    fn log_rx(&mut self) {
+        if !self.rx.is_empty() {
+            eprintln!("{}", describe_frame("RX", &self.rx));
+            self.rx.clear();
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for FrameLog<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        this.rx.extend_from_slice(&buf.filled()[before..]);
+        if frame_crcs(&this.rx).is_some_and(|(computed, received)| computed == received) {
+            this.log_rx();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for FrameLog<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let written = ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+        // Whatever arrived since the last complete response belongs before this request
+        this.log_rx();
+        this.tx.extend_from_slice(&buf[..written]);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(Pin::new(&mut this.inner).poll_flush(cx))?;
+        if !this.tx.is_empty() {
+            eprintln!("{}", describe_frame("TX", &this.tx));
+            this.tx.clear();
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc16_known_frame() {
+        // Read holding register 0 from unit 1; the CRC goes on the wire as 84 0A
+        assert_eq!(crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01]), 0x0A84);
+    }
+
+    #[test]
+    fn test_describe_frame() {
+        assert_eq!(
+            describe_frame("TX", &[0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A]),
+            "TX 01 03 00 00 00 01 84 0A (CRC 0x0A84 ok)"
+        );
+        assert_eq!(
+            describe_frame("RX", &[0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0B]),
+            "RX 01 03 00 00 00 01 84 0B (CRC mismatch: computed 0x0A84, received 0x0B84)"
+        );
+        assert_eq!(
+            describe_frame("RX", &[0x01, 0x83]),
+            "RX 01 83 (too short for a CRC)"
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use crate::cli::WordOrder;
+use crate::table::{combine_words, Decoded};
+
+/// Type of one field in a register layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    U64,
+    I64,
+    F64,
+}
+
+impl FieldType {
+    fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "u16" => FieldType::U16,
+            "i16" => FieldType::I16,
+            "u32" => FieldType::U32,
+            "i32" => FieldType::I32,
+            "f32" => FieldType::F32,
+            "u64" => FieldType::U64,
+            "i64" => FieldType::I64,
+            "f64" => FieldType::F64,
+            _ => return None,
+        })
+    }
+
+    /// Number of registers the field occupies
+    pub fn width(self) -> usize {
+        match self {
+            FieldType::U16 | FieldType::I16 => 1,
+            FieldType::U32 | FieldType::I32 | FieldType::F32 => 2,
+            FieldType::U64 | FieldType::I64 | FieldType::F64 => 4,
+        }
+    }
+
+    fn decode(self, bits: u64) -> Decoded {
+        match self {
+            FieldType::U16 | FieldType::U32 | FieldType::U64 => Decoded::Unsigned(bits),
+            FieldType::I16 => Decoded::Signed(bits as u16 as i16 as i64),
+            FieldType::I32 => Decoded::Signed(bits as u32 as i32 as i64),
+            FieldType::I64 => Decoded::Signed(bits as i64),
+            FieldType::F32 => Decoded::Float32(f32::from_bits(bits as u32)),
+            FieldType::F64 => Decoded::Float64(f64::from_bits(bits)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub ty: FieldType,
+}
+
+/// Fields packed back to back in a block of registers, in file order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub fields: Vec<Field>,
+}
+
+/// One decoded field: its name, the address of its first register and the raw registers
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldValue {
+    pub name: String,
+    pub addr: u16,
+    pub raw: Vec<u16>,
+    pub value: Decoded,
+}
+
+impl Layout {
+    /// Parse the `[[field]]` tables of a layout file:
+    ///
+    /// ```toml
+    /// [[field]]
+    /// name = "status"
+    /// type = "u16"
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut fields = Vec::new();
+        let mut current: Option<(Option<String>, Option<FieldType>)> = None;
+        let finish = |current: Option<(Option<String>, Option<FieldType>)>,
+                      fields: &mut Vec<Field>|
+         -> Result<(), String> {
+            match current {
+                Some((Some(name), Some(ty))) => {
+                    fields.push(Field { name, ty });
+                    Ok(())
+                }
+                Some(_) => Err(format!(
+                    "field {} needs both a name and a type",
+                    fields.len() + 1
+                )),
+                None => Ok(()),
+            }
+        };
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line == "[[field]]" {
+                finish(current.take(), &mut fields)?;
+                current = Some((None, None));
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected key = \"value\"", number + 1));
+            };
+            let Some(field) = current.as_mut() else {
+                return Err(format!(
+                    "line {}: keys must follow a [[field]] header",
+                    number + 1
+                ));
+            };
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .ok_or_else(|| format!("line {}: values must be quoted strings", number + 1))?;
+            match key.trim() {
+                "name" => field.0 = Some(value.to_string()),
+                "type" => {
+                    field.1 = Some(FieldType::parse(value).ok_or_else(|| {
+                        format!(
+                            "line {}: unknown type '{value}' (expected u16, i16, u32, i32, f32, u64, i64 or f64)",
+                            number + 1
+                        )
+                    })?)
+                }
+                other => return Err(format!("line {}: unknown key '{other}'", number + 1)),
+            }
+        }
+        finish(current, &mut fields)?;
+
+        if fields.is_empty() {
+            return Err("layout has no [[field]] entries".to_string());
+        }
+        Ok(Self { fields })
+    }
+
+    /// Total number of registers covered by the layout
+    pub fn size(&self) -> usize {
+        self.fields.iter().map(|field| field.ty.width()).sum()
+    }
+
+    /// Decode a block of registers read from `start`; the block must match the layout size exactly
+    pub fn decode(
+        &self,
+        start: u16,
+        registers: &[u16],
+        word_order: WordOrder,
+    ) -> Result<Vec<FieldValue>, String> {
+        if registers.len() != self.size() {
+            return Err(format!(
+                "Layout covers {} registers but {} were read",
+                self.size(),
+                registers.len()
+            ));
+        }
+
+        let mut offset = 0;
+        Ok(self
+            .fields
+            .iter()
+            .map(|field| {
+                let raw = registers[offset..offset + field.ty.width()].to_vec();
+                let value = field.ty.decode(combine_words(&raw, word_order));
+                let addr = start.wrapping_add(offset as u16);
+                offset += field.ty.width();
+                FieldValue {
+                    name: field.name.clone(),
+                    addr,
+                    raw,
+                    value,
+                }
+            })
+            .collect())
+    }
+}
+
+// Value parser for `--layout`: read and parse the file up front so errors surface before connecting
+pub fn parse_layout_file(path: &str) -> Result<Layout, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read layout file '{path}': {e}"))?;
+    Layout::parse(&text).map_err(|e| format!("Invalid layout file '{path}': {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYOUT: &str = r#"
+# Flow meter status block
+[[field]]
+name = "status"
+type = "u16"
+
+[[field]]
+name = "temp"
+type = "i32"
+
+[[field]]
+name = "flow"   # litres per minute
+type = "f32"
+"#;
+
+    #[test]
+    fn test_parse_layout() {
+        let layout = Layout::parse(LAYOUT).unwrap();
+        assert_eq!(layout.fields.len(), 3);
+        assert_eq!(layout.fields[1].name, "temp");
+        assert_eq!(layout.fields[1].ty, FieldType::I32);
+        assert_eq!(layout.size(), 5);
+    }
+
+    #[test]
+    fn test_parse_layout_errors() {
+        assert!(Layout::parse("").is_err());
+        assert!(Layout::parse("name = \"x\"").is_err());
+        assert!(Layout::parse("[[field]]\nname = \"x\"").is_err());
+        assert!(Layout::parse("[[field]]\nname = \"x\"\ntype = \"u8\"").is_err());
+        assert!(Layout::parse("[[field]]\nname = x\ntype = \"u16\"").is_err());
+    }
+
+    #[test]
+    fn test_decode_layout() {
+        let layout = Layout::parse(LAYOUT).unwrap();
+        // status 7, temp -20 (0xFFFFFFEC), flow 12.5 (0x41480000)
+        let registers = [7, 0xFFFF, 0xFFEC, 0x4148, 0x0000];
+        let values = layout.decode(100, &registers, WordOrder::Big).unwrap();
+
+        assert_eq!(values[0].value, Decoded::Unsigned(7));
+        assert_eq!(
+            (values[1].addr, values[1].value),
+            (101, Decoded::Signed(-20))
+        );
+        assert_eq!(
+            (values[2].addr, values[2].value),
+            (103, Decoded::Float32(12.5))
+        );
+        assert_eq!(values[2].raw, [0x4148, 0x0000]);
+
+        assert!(layout.decode(100, &registers[..4], WordOrder::Big).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use crate::cli::LogLevel;
+use crate::client::json_errors;
+use crate::output::render_json;
+use serde::Serialize;
+use std::fmt;
+use std::sync::OnceLock;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::reload::Handle;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, Registry};
+
+// Changes the level after start-up, so --verbose can raise it once a subcommand is parsed
+static LEVEL_HANDLE: OnceLock<Handle<LevelFilter, Registry>> = OnceLock::new();
+
+/// Install the tracing subscriber that prints the tool's log events up to `level`.
+/// Every event goes to stderr, so stdout carries only results and stays safe to pipe; in
+/// JSON mode (set before this is called) each event is an object.
+pub fn init(level: LogLevel) {
+    let (filter, handle) = reload::Layer::new(level_filter(level));
+    let format = tracing_subscriber::fmt::layer()
+        .event_format(LogFormat)
+        .with_writer(std::io::stderr);
+    if tracing_subscriber::registry()
+        .with(filter)
+        .with(format)
+        .try_init()
+        .is_ok()
+    {
+        let _ = LEVEL_HANDLE.set(handle);
+    }
+}
+
+/// Print at least `level` events, keeping a more detailed level already set (for --verbose)
+pub fn raise_log_level(level: LogLevel) {
+    if let Some(handle) = LEVEL_HANDLE.get() {
+        let _ = handle.modify(|filter| *filter = (*filter).max(level_filter(level)));
+    }
+}
+
+fn level_filter(level: LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Error => LevelFilter::ERROR,
+        LogLevel::Warn => LevelFilter::WARN,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Debug => LevelFilter::DEBUG,
+        LogLevel::Trace => LevelFilter::TRACE,
+    }
+}
+
+// Formats each event the way the tool printed its diagnostics before tracing
+struct LogFormat;
+
+impl<S, N> FormatEvent<S, N> for LogFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut message = MessageVisitor::default();
+        event.record(&mut message);
+        let level = *event.metadata().level();
+        if json_errors() {
+            writeln!(writer, "{}", render_json(&LogJson::new(level, &message.0)))
+        } else {
+            writeln!(writer, "{}", log_line(level, &message.0))
+        }
+    }
+}
+
+// Collects the message of an event; the tool's events carry no other fields
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{value:?}");
+        }
+    }
+}
+
+// Text form of a log message: warnings and errors carry a prefix like the tool's other diagnostics
+fn log_line(level: Level, message: &str) -> String {
+    match level {
+        Level::ERROR => format!("Error: {message}"),
+        Level::WARN => format!("Warning: {message}"),
+        _ => message.to_string(),
+    }
+}
+
+// JSON object for one log message, e.g. {"level":"debug","message":"Write register 4: 7"}
+#[derive(Serialize)]
+struct LogJson<'a> {
+    level: String,
+    message: &'a str,
+}
+
+impl<'a> LogJson<'a> {
+    fn new(level: Level, message: &'a str) -> Self {
+        Self {
+            level: level.as_str().to_ascii_lowercase(),
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_json() {
+        assert_eq!(
+            serde_json::to_string(&LogJson::new(Level::DEBUG, "Write register 4: \"7\"")).unwrap(),
+            r#"{"level":"debug","message":"Write register 4: \"7\""}"#
+        );
+    }
+
+    #[test]
+    fn test_log_line_prefixes() {
+        assert_eq!(log_line(Level::ERROR, "refused"), "Error: refused");
+        assert_eq!(log_line(Level::WARN, "refused"), "Warning: refused");
+        assert_eq!(log_line(Level::INFO, "listening"), "listening");
+    }
+
+    #[test]
+    fn test_level_filters_order() {
+        // --verbose raises to debug but never lowers a more detailed level
+        assert!(level_filter(LogLevel::Warn) < level_filter(LogLevel::Info));
+        assert_eq!(
+            level_filter(LogLevel::Warn).max(level_filter(LogLevel::Debug)),
+            LevelFilter::DEBUG
+        );
+        assert_eq!(
+            level_filter(LogLevel::Trace).max(level_filter(LogLevel::Debug)),
+            LevelFilter::TRACE
+        );
+    }
+}
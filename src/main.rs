@@ -1,138 +1,983 @@
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio_modbus::client::{Reader, Writer};
+use tokio_modbus::ExceptionCode;
 
+// tracing's error! .. trace! macros, in scope in every module
+#[macro_use]
+extern crate tracing;
+
+mod bench;
 mod cli;
 mod client;
+mod diag;
+mod diff;
+mod enron;
+mod events;
+mod fifo;
+mod file_record;
+mod frame;
+mod layout;
+mod logging;
+mod metrics;
+mod output;
+mod protocol_id;
+mod repl;
+mod rts;
+mod selftest;
 mod server;
 mod table;
+mod traffic;
+mod transform;
+mod udp;
+mod webhook;
 
-use cli::{Cli, Command, ReadArea, WriteArea};
-use client::{connect_to_modbus, modbus_operation_with_timeout};
-use server::{run_rtu_server, run_tcp_server, ModbusData};
-use table::{print_coil_table, print_register_table};
+use bench::RepeatStats;
+use cli::{
+    check_address_range, expand_write_values, order_bits, parse_profile, Area, BitPattern, Cli,
+    Command, Common, DiagOp, FileOp, LogLevel, OutputFormat, Pattern, ReadRequest, TableStyle,
+    ValueFormat, WriteAddress, WriteArea, MAX_WRITE_REGISTERS,
+};
+use client::{
+    connect_to_modbus, describe_read_request, enable_error_summary, enable_json_errors,
+    failure_reported, handle_modbus_response_with_timeout, is_connection_error, json_errors,
+    modbus_operation_with_timeout, ping, plan_read_spans, read_coil_spans, read_register_spans,
+    reconnect_with_backoff, take_error_summary, wait_for_next_poll, wait_with_keepalive,
+    ConnectError, OpSettings,
+};
+use diag::read_diagnostic_counters;
+use enron::{decode_enron, read_enron_registers};
+use events::{serve_events, EventStream};
+use fifo::read_fifo_queue;
+use file_record::{read_file_record, write_file_record, MAX_WRITE_RECORD_LEN};
+use metrics::serve_metrics;
+use output::{
+    bit_csv_line, bit_json, coil_csv_line, coil_json, enable_pretty_json, enable_yaml_output,
+    enron_csv_header, eprint_json, layout_csv_line, layout_json, parse_expected_csv,
+    parse_write_json, print_json, register_csv_header, register_csv_line, register_json, CsvOutput,
+    ErrorJson, JsonWrite,
+};
+use server::{
+    run_rtu_server, run_tcp_server, spawn_heartbeat, Fill, ModbusData, SerialSettings,
+    ServerOptions,
+};
+use table::{
+    apply_byte_swap, decode_with_options, encode_values, format_read_summary, memory_map_table,
+    non_finite_values, print_bool_matrix, print_coil_rows, print_coil_table, print_compact_value,
+    print_decoded_rows, print_diag_counters, print_fifo_values, print_layout_values,
+    print_register_bit, print_register_rows, print_register_table, print_transformed_rows,
+    TableOptions,
+};
+use traffic::traffic_summary;
+use webhook::{write_bit_event, write_event};
 
+use anyhow::Context;
 use clap::Parser;
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+// Print the one-line summary above a read's table
+fn print_read_summary<T>(request: &ReadRequest, rows: &[(u16, T)], elapsed: Option<Duration>) {
+    if request.common.quiet {
+        return;
+    }
+    let base = request.options.address_base as u32;
+    let first = rows.first().map_or(request.start, |&(addr, _)| addr) as u32 + base;
+    let last = rows.last().map_or(request.start, |&(addr, _)| addr) as u32 + base;
+    println!(
+        "{}",
+        format_read_summary(
+            request.area.label(),
+            first,
+            last,
+            rows.len(),
+            &request.common.target(),
+            request.common.unit,
+            elapsed
+        )
+    );
+}
 
-    match cli.cmd {
-        Command::Read { area } => match area {
-            ReadArea::Coil { start, qty, common } => {
-                let mut client = connect_to_modbus(&common).await?;
-                let coils = modbus_operation_with_timeout(
-                    || client.read_coils(start, qty),
-                    "read coils",
-                    common.timeout,
-                )
-                .await?;
-                println!("Read {} coil(s) (Unit ID: {}):", coils.len(), common.unit);
-                print_coil_table(&coils, start);
+// Nonconforming devices sometimes answer with fewer values than asked for; say so
+fn warn_if_short(spans: &[(u16, u16)], returned: usize) {
+    let requested: usize = spans.iter().map(|&(_, qty)| qty as usize).sum();
+    if returned != requested {
+        warn!("requested {requested} value(s) but the device returned {returned}");
+    }
+}
+
+// Issue one round of reads for a request and print the results, returning how long the reads
+// took and the values read by address (decoded per --format or --layout, coils as 0/1) for
+// --poll-until and --expect
+async fn poll_once(
+    client: &mut tokio_modbus::client::Context,
+    request: &ReadRequest,
+    spans: &[(u16, u16)],
+    output: &mut Option<CsvOutput>,
+    last_coils: &mut Option<Vec<bool>>,
+    json: bool,
+) -> anyhow::Result<(Duration, Vec<(u16, f64)>)> {
+    let (options, common) = (&request.options, &request.common);
+    let table_options = TableOptions {
+        verbose: common.verbose,
+        grouping: !options.no_grouping,
+        byte_swap: options.byte_swap,
+        format: options.format,
+        word_order: options.word_order,
+        style: options.table_style,
+        quiet: common.quiet,
+        columns: options.columns.map(usize::from),
+        header: !options.no_header,
+        duration_unit: options.duration_unit.unwrap_or_default(),
+        address_base: options.address_base,
+    };
+
+    // Results go to the CSV file, the terminal, or both with --also-stdout
+    let show = output.is_none() || options.also_stdout;
+    // A single value prints as one `addr: value` line once the device has answered with exactly one
+    let compact = request.is_compact() && !json;
+    if !json && enabled!(tracing::Level::DEBUG) {
+        // Enron reads are a single request whose quantity counts 32-bit registers
+        let requests = if options.enron {
+            vec![(request.start, request.qty)]
+        } else {
+            spans.to_vec()
+        };
+        for (start, qty) in requests {
+            debug!(
+                "{}",
+                describe_read_request(request.area, start, qty, common.unit)
+            );
+        }
+    }
+    let started = Instant::now();
+    match request.area {
+        Area::Coil | Area::Discrete => {
+            let mut rows =
+                read_coil_spans(client, request.area, spans, OpSettings::new(common)).await?;
+            let elapsed = started.elapsed();
+            warn_if_short(spans, rows.len());
+            rows.retain(|&(addr, _)| request.is_requested(addr));
+            let compact = compact && rows.len() == 1;
+            if !json && !compact {
+                print_read_summary(request, &rows, options.timing.then_some(elapsed));
             }
-            ReadArea::Discrete { start, qty, common } => {
-                let mut client = connect_to_modbus(&common).await?;
-                let inputs = modbus_operation_with_timeout(
-                    || client.read_discrete_inputs(start, qty),
-                    "read discrete inputs",
-                    common.timeout,
-                )
-                .await?;
-                println!(
-                    "Read {} discrete input(s) (Unit ID: {}):",
-                    inputs.len(),
-                    common.unit
+            if let Some(csv) = output {
+                csv.write_lines(rows.iter().map(|&(addr, value)| coil_csv_line(addr, value)))?;
+            }
+            if show && json {
+                print_json(&coil_json(request.area, common.unit, &rows));
+            } else if show && compact {
+                let (addr, value) = rows[0];
+                print_compact_value(
+                    table_options.display_addr(addr),
+                    if value { "ON" } else { "OFF" },
                 );
-                print_coil_table(&inputs, start);
+            } else if show && options.format == ValueFormat::BoolMatrix {
+                let values: Vec<bool> = rows.iter().map(|&(_, value)| value).collect();
+                print_bool_matrix(
+                    &values,
+                    last_coils.as_deref(),
+                    request.start,
+                    &table_options,
+                );
+                *last_coils = Some(values);
+            } else if show {
+                print_coil_rows(&rows, &table_options);
             }
-            ReadArea::Holding { start, qty, common } => {
-                let mut client = connect_to_modbus(&common).await?;
-                let registers = modbus_operation_with_timeout(
-                    || client.read_holding_registers(start, qty),
-                    "read holding registers",
-                    common.timeout,
-                )
-                .await?;
-                println!(
-                    "Read {} holding register(s) (Unit ID: {}):",
-                    registers.len(),
-                    common.unit
+            let values = rows
+                .iter()
+                .map(|&(addr, value)| (addr, u8::from(value) as f64));
+            Ok((elapsed, values.collect()))
+        }
+        Area::Holding | Area::Input if options.enron => {
+            let registers = read_enron_registers(
+                client,
+                request.area,
+                request.start,
+                request.qty,
+                OpSettings::new(common),
+            )
+            .await?;
+            let elapsed = started.elapsed();
+            let compact = compact && registers.len() == 1;
+            if !json && !compact {
+                print_read_summary(request, &registers, options.timing.then_some(elapsed));
+            }
+            let decoded = decode_enron(&registers, &table_options);
+            if let Some(csv) = output {
+                csv.write_lines(
+                    decoded
+                        .iter()
+                        .map(|row| register_csv_line(row, options.format)),
+                )?;
+            }
+            if show && json {
+                print_json(&register_json(request.area, common.unit, &decoded));
+            } else if show && compact {
+                print_compact_value(
+                    table_options.display_addr(decoded[0].addr),
+                    &decoded[0].value.display(false),
                 );
-                print_register_table(&registers, start, common.verbose);
+            } else if show {
+                print_decoded_rows(&decoded, &table_options);
             }
-            ReadArea::Input { start, qty, common } => {
-                let mut client = connect_to_modbus(&common).await?;
-                let registers = modbus_operation_with_timeout(
-                    || client.read_input_registers(start, qty),
-                    "read input registers",
-                    common.timeout,
+            let values = decoded.iter().map(|row| (row.addr, row.value.as_f64()));
+            Ok((elapsed, values.collect()))
+        }
+        Area::Holding | Area::Input => {
+            let mut rows =
+                read_register_spans(client, request.area, spans, OpSettings::new(common)).await?;
+            let elapsed = started.elapsed();
+            warn_if_short(spans, rows.len());
+            rows.retain(|&(addr, _)| request.is_requested(addr));
+            let compact = compact && rows.len() == 1;
+            if !json && !compact {
+                print_read_summary(request, &rows, options.timing.then_some(elapsed));
+            }
+            if let Some(bit) = request.bit {
+                let Some(&(addr, register)) = apply_byte_swap(&rows, options.byte_swap).first()
+                else {
+                    return Ok((elapsed, Vec::new()));
+                };
+                let value = register & (1 << bit) != 0;
+                if let Some(csv) = output {
+                    csv.write_lines([bit_csv_line(addr, bit, value)])?;
+                }
+                if show && json {
+                    print_json(&bit_json(request.area, common.unit, addr, bit, value));
+                } else if show {
+                    print_register_bit(addr, bit, value, &table_options);
+                }
+                return Ok((elapsed, vec![(addr, u8::from(value) as f64)]));
+            }
+            if let Some(layout) = &options.layout {
+                let registers: Vec<u16> = apply_byte_swap(&rows, options.byte_swap)
+                    .into_iter()
+                    .map(|(_, value)| value)
+                    .collect();
+                let values = layout
+                    .decode(request.start, &registers, options.word_order)
+                    .map_err(|e| anyhow::anyhow!(e))?;
+                if let Some(csv) = output {
+                    csv.write_lines(values.iter().map(layout_csv_line))?;
+                }
+                if show && json {
+                    print_json(&layout_json(request.area, common.unit, &values));
+                } else if show {
+                    print_layout_values(&values, &table_options);
+                }
+                let values = values
+                    .iter()
+                    .map(|field| (field.addr, field.value.as_f64()));
+                return Ok((elapsed, values.collect()));
+            }
+            let width = options.format.width();
+            if rows.len() % width != 0 {
+                warn!(
+                    "{} register(s) is not a multiple of {width}; the trailing {} register(s) were not decoded",
+                    rows.len(),
+                    rows.len() % width
+                );
+            }
+            let decoded = decode_with_options(&rows, &table_options);
+            if let Some(csv) = output {
+                csv.write_lines(
+                    decoded
+                        .iter()
+                        .map(|row| register_csv_line(row, options.format)),
+                )?;
+            }
+            if show && json {
+                print_json(&register_json(request.area, common.unit, &decoded));
+            } else if show && compact && decoded.len() == 1 {
+                print_compact_value(
+                    table_options.display_addr(decoded[0].addr),
+                    &decoded[0].value.display(false),
+                );
+            } else if show {
+                match &options.transform {
+                    Some(transform) => print_transformed_rows(&rows, transform, &table_options),
+                    None => print_register_rows(&rows, &table_options),
+                }
+            }
+            let values = decoded.iter().map(|row| (row.addr, row.value.as_f64()));
+            Ok((elapsed, values.collect()))
+        }
+    }
+}
+
+// Start, values and format of a write holding --from file
+fn read_write_json(path: &Path, format: ValueFormat) -> anyhow::Result<JsonWrite> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Cannot read write values '{}': {e}", path.display()))?;
+    parse_write_json(&text, format)
+        .map_err(|e| anyhow::anyhow!("Invalid write values '{}': {e}", path.display()))
+}
+
+// Values of an --expect file by address
+fn read_expected(path: &Path) -> anyhow::Result<BTreeMap<u16, String>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Cannot read expected values '{}': {e}", path.display()))?;
+    parse_expected_csv(&text)
+        .map_err(|e| anyhow::anyhow!("Invalid expected values '{}': {e}", path.display()))
+}
+
+// Whether a value read matches the expected text. A float32 written to CSV keeps only f32
+// precision, so it matches when it parses to the same f32
+fn value_matches(expected: &str, actual: f64) -> bool {
+    expected.parse::<f64>().is_ok_and(|value| value == actual)
+        || expected
+            .parse::<f32>()
+            .is_ok_and(|value| value as f64 == actual)
+}
+
+// Compare a poll against the --expect file, printing the addresses that differ side by side.
+// Addresses the file doesn't list aren't checked
+fn check_expected(
+    expected: &BTreeMap<u16, String>,
+    values: &[(u16, f64)],
+    path: &Path,
+) -> anyhow::Result<()> {
+    let read: BTreeMap<u16, f64> = values.iter().copied().collect();
+    let mismatches: Vec<(u16, &str, Option<f64>)> = expected
+        .iter()
+        .map(|(&addr, expected)| (addr, expected.as_str(), read.get(&addr).copied()))
+        .filter(|&(_, expected, actual)| {
+            !actual.is_some_and(|actual| value_matches(expected, actual))
+        })
+        .collect();
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    println!("{:>7}  {:>10}  {:>10}", "Address", "Expected", "Read");
+    println!(
+        "{:>7}  {:>10}  {:>10}",
+        "-------", "----------", "----------"
+    );
+    for (addr, expected, actual) in &mismatches {
+        let actual = actual.map_or("-".to_string(), |actual| actual.to_string());
+        println!("{addr:>7}  {expected:>10}  {actual:>10}");
+    }
+    Err(anyhow::anyhow!(
+        "{} of {} expected value(s) differ from {}",
+        mismatches.len(),
+        expected.len(),
+        path.display()
+    ))
+}
+
+// Connect to one side of a diff and read every span, coils as 0/1
+async fn read_for_diff(
+    common: &Common,
+    area: Area,
+    spans: &[(u16, u16)],
+) -> anyhow::Result<Vec<(u16, u16)>> {
+    let mut client = connect_to_modbus(common).await?;
+    match area {
+        Area::Coil | Area::Discrete => {
+            Ok(
+                read_coil_spans(&mut client, area, spans, OpSettings::new(common))
+                    .await?
+                    .into_iter()
+                    .map(|(addr, value)| (addr, u16::from(value)))
+                    .collect(),
+            )
+        }
+        Area::Holding | Area::Input => {
+            read_register_spans(&mut client, area, spans, OpSettings::new(common)).await
+        }
+    }
+}
+
+// Rewrite `mb read --alias NAME --profile FILE [options]` as the `mb read <area> --addr --qty`
+// it stands for, so an alias read takes exactly the options of a plain read. `None` when the
+// command is not an alias read
+fn resolve_read_alias(cli: &Cli) -> anyhow::Result<Option<Cli>> {
+    let Command::Read {
+        area: None,
+        alias: Some(alias),
+        profile: Some(profile),
+        args,
+    } = &cli.cmd
+    else {
+        return Ok(None);
+    };
+    let text = std::fs::read_to_string(profile)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", profile.display()))?;
+    let aliases =
+        parse_profile(&text).map_err(|e| anyhow::anyhow!("{}: {e}", profile.display()))?;
+    let range = aliases.get(alias).ok_or_else(|| {
+        let mut names: Vec<&str> = aliases.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        anyhow::anyhow!(
+            "No alias '{alias}' in {}; it defines: {}",
+            profile.display(),
+            names.join(", ")
+        )
+    })?;
+    Ok(Some(
+        cli.alias_read(range, args).unwrap_or_else(|e| e.exit()),
+    ))
+}
+
+/// Values of one write command
+enum WritePayload<'a> {
+    Coils(&'a [bool]),
+    /// Registers and the width of each encoded value, so chunking never splits a value
+    Registers(&'a [u16], usize),
+}
+
+// Issue one write: FC 5/6 for a single value, FC 15/16 otherwise. Registers beyond one
+// request's limit are written in consecutive chunks
+async fn write_once(
+    client: &mut tokio_modbus::client::Context,
+    start: u16,
+    payload: &WritePayload<'_>,
+    op: OpSettings,
+) -> anyhow::Result<()> {
+    match *payload {
+        WritePayload::Coils(&[value]) => {
+            modbus_operation_with_timeout(
+                async || client.write_single_coil(start, value).await,
+                "write coil",
+                op,
+            )
+            .await
+        }
+        WritePayload::Coils(values) => {
+            modbus_operation_with_timeout(
+                async || client.write_multiple_coils(start, values).await,
+                "write coils",
+                op,
+            )
+            .await
+        }
+        WritePayload::Registers(&[value], _) => {
+            modbus_operation_with_timeout(
+                async || client.write_single_register(start, value).await,
+                "write register",
+                op,
+            )
+            .await
+        }
+        WritePayload::Registers(values, width) => {
+            let chunk_len = MAX_WRITE_REGISTERS / width * width;
+            for (i, chunk) in values.chunks(chunk_len).enumerate() {
+                let addr = start + (i * chunk_len) as u16;
+                modbus_operation_with_timeout(
+                    async || client.write_multiple_registers(addr, chunk).await,
+                    "write registers",
+                    op,
                 )
                 .await?;
-                println!(
-                    "Read {} input register(s) (Unit ID: {}):",
-                    registers.len(),
-                    common.unit
-                );
-                print_register_table(&registers, start, common.verbose);
             }
+            Ok(())
+        }
+    }
+}
+
+// Set or clear one bit of a holding register, leaving the other bits as they are. Uses
+// Mask Write Register (FC 22) so the change is atomic, falling back to reading the register
+// and writing it back (FC 3 + FC 6) for devices that don't implement FC 22
+async fn write_register_bit(
+    client: &mut tokio_modbus::client::Context,
+    addr: u16,
+    bit: u8,
+    on: bool,
+    op: OpSettings,
+) -> anyhow::Result<()> {
+    let mask = 1u16 << bit;
+    let (and_mask, or_mask) = (!mask, if on { mask } else { 0 });
+    let op_timeout = Duration::from_secs(op.timeout_secs);
+    let result = tokio::time::timeout(
+        op_timeout,
+        client.masked_write_register(addr, and_mask, or_mask),
+    )
+    .await;
+    if !matches!(result, Ok(Ok(Err(ExceptionCode::IllegalFunction)))) {
+        return handle_modbus_response_with_timeout(result, "mask write register", op.timeout_secs)
+            .await;
+    }
+
+    let current = modbus_operation_with_timeout(
+        async || client.read_holding_registers(addr, 1).await,
+        "read holding registers",
+        op,
+    )
+    .await?;
+    let Some(&current) = current.first() else {
+        return Err(anyhow::anyhow!(
+            "Device returned no value for register {addr}"
+        ));
+    };
+    modbus_operation_with_timeout(
+        async || {
+            client
+                .write_single_register(addr, (current & and_mask) | or_mask)
+                .await
         },
+        "write register",
+        op,
+    )
+    .await
+}
+
+// Issue the same write `times` times over one connection for --repeat. Failures are counted
+// rather than ending the run, so an endurance test sees how often the device drops writes
+async fn repeat_write(
+    client: &mut tokio_modbus::client::Context,
+    start: u16,
+    payload: &WritePayload<'_>,
+    common: &Common,
+    times: u32,
+    interval: Option<Duration>,
+) -> RepeatStats {
+    let mut stats = RepeatStats::default();
+    let started = Instant::now();
+    for i in 0..times {
+        if let Some(interval) = interval.filter(|_| i > 0) {
+            tokio::time::sleep(interval).await;
+        }
+        match write_once(client, start, payload, OpSettings::new(common)).await {
+            Ok(()) => stats.succeeded += 1,
+            Err(_) => stats.failed += 1,
+        }
+    }
+    stats.elapsed = started.elapsed();
+    stats
+}
+
+// Print the --repeat summary; any failed write makes the command fail
+fn report_repeat(stats: RepeatStats, json: bool) -> anyhow::Result<()> {
+    if json {
+        print_json(&stats.json());
+    } else {
+        println!("{stats}");
+    }
+    if stats.failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} writes failed",
+            stats.failed,
+            stats.succeeded + stats.failed
+        ));
+    }
+    Ok(())
+}
+
+// Values for `--value -`: separated by commas or whitespace, so one per line works too
+fn read_stdin_values() -> anyhow::Result<Vec<String>> {
+    let text = std::io::read_to_string(std::io::stdin())?;
+    let values: Vec<String> = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .collect();
+    if values.is_empty() {
+        return Err(anyhow::anyhow!("--value - read no values from stdin"));
+    }
+    Ok(values)
+}
+
+// Ask before dumping a large read into the terminal; returns whether to go ahead
+fn confirm_big_read(request: &ReadRequest) -> anyhow::Result<bool> {
+    if !request.needs_confirmation() || !std::io::stdout().is_terminal() {
+        return Ok(true);
+    }
+    print!(
+        "This read will print {} rows. Continue? [y/N] ",
+        request.printed_rows()
+    );
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let parsed = Cli::parse();
+    // Resolved first, since global options can follow the alias among the read's options
+    let (mut cli, alias_error) = match resolve_read_alias(&parsed) {
+        Ok(read) => (read.unwrap_or(parsed), None),
+        Err(e) => (parsed, Some(e)),
+    };
+    if cli.json_pretty {
+        cli.output_format = OutputFormat::Json;
+        enable_pretty_json();
+    }
+    if cli.output_format == OutputFormat::Yaml {
+        enable_yaml_output();
+    }
+    if cli.output_format != OutputFormat::Text {
+        enable_json_errors();
+    }
+    logging::init(cli.log_level);
+    let result = match alias_error {
+        Some(e) => Err(e),
+        None => run(cli).await,
+    };
+    // Printed on failure too, so a watch or bench that stops early still reports what it saw
+    if let Some(tally) = take_error_summary() {
+        eprintln!("Summary: {tally}");
+    }
+    if let Some(traffic) = traffic_summary() {
+        eprintln!("Traffic: {traffic}");
+    }
+    // In JSON mode every failure is one object on stderr; the Modbus helpers may have printed it already
+    if let (true, Err(e)) = (json_errors(), &result) {
+        let connect_error = e.downcast_ref::<ConnectError>();
+        if !failure_reported() {
+            match connect_error {
+                Some(connect_error) => eprint_json(&connect_error.json()),
+                None => eprint_json(&ErrorJson {
+                    message: Some(e.to_string()),
+                    ..ErrorJson::new("failed")
+                }),
+            }
+        }
+        std::process::exit(connect_error.map_or(1, ConnectError::exit_code));
+    }
+    // Connection failures get their own exit codes; everything else exits with 1
+    if let Some(connect_error) = result
+        .as_ref()
+        .err()
+        .and_then(|e| e.downcast_ref::<ConnectError>())
+    {
+        eprintln!("Error: {connect_error}");
+        std::process::exit(connect_error.exit_code());
+    }
+    result
+}
+
+async fn run(cli: Cli) -> anyhow::Result<()> {
+    // YAML is the same structured output, rendered differently when printed
+    let json = cli.output_format != OutputFormat::Text;
+    match cli.cmd {
+        Command::Read { area: None, .. } => {
+            return Err(anyhow::anyhow!("Name an area to read or pass --alias"))
+        }
+        Command::Read {
+            area: Some(area), ..
+        } => {
+            let request = area.into_request().map_err(|e| anyhow::anyhow!(e))?;
+            let (options, common) = (&request.options, &request.common);
+            if !confirm_big_read(&request)? {
+                return Err(anyhow::anyhow!(
+                    "Read cancelled; pass --yes or --output to skip the prompt"
+                ));
+            }
+            if options.watch.is_some() || common.verbose {
+                enable_error_summary();
+            }
+            let spans = match &request.addresses {
+                Some(addresses) => {
+                    plan_read_spans(addresses, request.area.max_read_qty(), options.max_gap)
+                }
+                None => vec![(request.start, request.qty)],
+            };
+
+            let mut output = match &options.output {
+                Some(path) if options.no_header => {
+                    Some(CsvOutput::append(path, request.area, options.time_ref)?)
+                }
+                Some(path) => {
+                    let header = match request.area {
+                        Area::Coil | Area::Discrete => "address,value",
+                        Area::Holding | Area::Input if request.bit.is_some() => "address,value",
+                        Area::Holding | Area::Input if options.layout.is_some() => {
+                            "field,address,value"
+                        }
+                        Area::Holding | Area::Input if options.enron => {
+                            enron_csv_header(options.format)
+                        }
+                        Area::Holding | Area::Input => register_csv_header(options.format),
+                    };
+                    Some(CsvOutput::create(
+                        path,
+                        request.area,
+                        header,
+                        options.time_ref,
+                    )?)
+                }
+                None => None,
+            }
+            .map(|csv| match options.flush_interval {
+                Some(rows) => csv.with_flush_interval(rows as usize),
+                None => csv,
+            });
+
+            // Read before connecting, so a bad golden file fails before any traffic
+            let expected = options.expect.as_deref().map(read_expected).transpose()?;
+            let mut client = connect_to_modbus(common).await?;
+            let deadline = options.timeout_total.map(|total| Instant::now() + total);
+            let mut polls = 0;
+            // The previous poll's coils, so --format bool-matrix can highlight what changed
+            let mut last_coils = None;
+            loop {
+                match poll_once(
+                    &mut client,
+                    &request,
+                    &spans,
+                    &mut output,
+                    &mut last_coils,
+                    json,
+                )
+                .await
+                {
+                    Ok((elapsed, values)) => {
+                        polls += 1;
+                        if let (Some(expected), Some(path)) = (&expected, &options.expect) {
+                            check_expected(expected, &values, path)?;
+                        }
+                        if let Some(condition) = options.poll_until {
+                            if !values.is_empty() && values.iter().all(|&(_, v)| condition.holds(v))
+                            {
+                                if !common.quiet {
+                                    eprintln!("Condition {condition} met after {polls} poll(s)");
+                                }
+                                return Ok(());
+                            }
+                        }
+                        if let Some(max_latency) = options.max_latency {
+                            if elapsed.as_millis() > max_latency as u128 {
+                                warn!(
+                                    "poll {polls} took {}ms, over --max-latency {max_latency}ms",
+                                    elapsed.as_millis()
+                                );
+                                if options.fail_on_slow {
+                                    return Err(anyhow::anyhow!(
+                                        "Poll {polls} exceeded --max-latency {max_latency}ms"
+                                    ));
+                                }
+                            }
+                        }
+                    }
+                    // A dropped connection shouldn't end a watch session; exceptions still do
+                    Err(e) if options.watch.is_some() && is_connection_error(&e) => {
+                        eprintln!("Connection lost: {e}. Reconnecting...");
+                        match reconnect_with_backoff(common).await {
+                            Some(reconnected) => {
+                                client = reconnected;
+                                continue;
+                            }
+                            None => break,
+                        }
+                    }
+                    Err(e) => return Err(e),
+                }
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    break;
+                }
+                if options.count.is_some_and(|count| polls >= count) {
+                    break;
+                }
+                let next_poll = match options.keepalive {
+                    Some(secs) => {
+                        wait_with_keepalive(
+                            &mut client,
+                            options.watch,
+                            Duration::from_secs(secs),
+                            request.area,
+                            request.start,
+                            OpSettings::new(common),
+                        )
+                        .await
+                    }
+                    None => wait_for_next_poll(options.watch).await,
+                };
+                if !next_poll {
+                    break;
+                }
+            }
+            // Reaching here with --poll-until means the watch ended before the condition held
+            if let Some(condition) = options.poll_until {
+                return Err(anyhow::anyhow!(
+                    "Condition {condition} not met after {polls} poll(s)"
+                ));
+            }
+        }
 
         Command::Write { area } => match area {
             WriteArea::Coil {
                 start,
                 values,
+                bits,
+                bit_order,
+                qty,
+                repeat,
                 common,
             } => {
+                let values = match bits {
+                    Some(bits) => order_bits(bits, bit_order)
+                        .into_iter()
+                        .map(u16::from)
+                        .collect(),
+                    None => values,
+                };
+                let values =
+                    expand_write_values(values, qty, Area::Coil).map_err(|e| anyhow::anyhow!(e))?;
+                check_address_range(start, values.len()).map_err(|e| anyhow::anyhow!(e))?;
+                if common.verbose {
+                    enable_error_summary();
+                }
                 let mut client = connect_to_modbus(&common).await?;
 
                 // Convert u16 values to bool values (0 = false, non-zero = true)
                 let bool_values: Vec<bool> = values.iter().map(|&v| v != 0).collect();
 
-                if bool_values.len() == 1 {
-                    // Single coil write (FC 5)
-                    modbus_operation_with_timeout(
-                        || client.write_single_coil(start, bool_values[0]),
-                        "write coil",
-                        common.timeout,
-                    )
-                    .await?;
-                    println!(
-                        "Wrote coil at address {start} with value {} (Unit ID: {})",
-                        if bool_values[0] { "ON" } else { "OFF" },
-                        common.unit
-                    );
-                } else {
-                    // Multiple coils write (FC 15)
-                    modbus_operation_with_timeout(
-                        || client.write_multiple_coils(start, &bool_values),
-                        "write coils",
-                        common.timeout,
-                    )
-                    .await?;
-                    println!(
-                        "Wrote {} coil(s) starting at address {} (Unit ID: {})",
-                        bool_values.len(),
+                let payload = WritePayload::Coils(&bool_values);
+                if let Some(times) = repeat.repeat {
+                    let stats = repeat_write(
+                        &mut client,
                         start,
-                        common.unit
-                    );
-                    print_coil_table(&bool_values, start);
+                        &payload,
+                        &common,
+                        times,
+                        repeat.interval,
+                    )
+                    .await;
+                    return report_repeat(stats, json);
+                }
+                write_once(&mut client, start, &payload, OpSettings::new(&common)).await?;
+
+                if json {
+                    print_json(&write_event(common.unit, "coil", start, &values));
+                } else if !common.quiet {
+                    if bool_values.len() == 1 {
+                        println!(
+                            "Wrote coil at address {start} with value {} (Unit ID: {})",
+                            if bool_values[0] { "ON" } else { "OFF" },
+                            common.unit
+                        );
+                    } else {
+                        println!(
+                            "Wrote {} coil(s) starting at address {} (Unit ID: {})",
+                            bool_values.len(),
+                            start,
+                            common.unit
+                        );
+                        print_coil_table(&bool_values, start, &TableOptions::default());
+                    }
                 }
             }
             WriteArea::Holding {
                 start,
                 values,
+                from,
+                qty,
+                format,
+                word_order,
+                byte_swap,
+                repeat,
                 common,
             } => {
+                let (start, values, format) = match (&from, start) {
+                    (Some(path), _) => {
+                        let write = read_write_json(path, format)?;
+                        let start = WriteAddress {
+                            register: write.start,
+                            bit: None,
+                        };
+                        (start, write.values, write.format)
+                    }
+                    (None, Some(start)) => (start, values, format),
+                    (None, None) => return Err(anyhow::anyhow!("Give --addr or --from")),
+                };
+                let WriteAddress {
+                    register: start,
+                    bit,
+                } = start;
+                if let Some(bit) = bit {
+                    let on = match values.as_slice() {
+                        [value] if value == "0" || value == "1" => value == "1",
+                        _ => {
+                            return Err(anyhow::anyhow!(
+                            "--addr register.bit writes one bit; give a single --value of 0 or 1"
+                        ))
+                        }
+                    };
+                    if qty.is_some() || format.width() > 1 || byte_swap || repeat.repeat.is_some() {
+                        return Err(anyhow::anyhow!(
+                            "--addr register.bit cannot be combined with --qty, --format, --byte-swap or --repeat"
+                        ));
+                    }
+                    if common.verbose {
+                        enable_error_summary();
+                    }
+                    let mut client = connect_to_modbus(&common).await?;
+                    write_register_bit(&mut client, start, bit, on, OpSettings::new(&common))
+                        .await?;
+                    if json {
+                        print_json(&write_bit_event(common.unit, start, bit, on));
+                    } else if !common.quiet {
+                        let action = if on { "Set" } else { "Cleared" };
+                        println!(
+                            "{action} bit {bit} of holding register {start} (Unit ID: {})",
+                            common.unit
+                        );
+                    }
+                    return Ok(());
+                }
+                if qty.is_some() && format.width() > 1 {
+                    return Err(anyhow::anyhow!(
+                        "--qty fills single registers and cannot be combined with a multi-register --format"
+                    ));
+                }
+                let from_stdin = values.iter().any(|value| value == "-");
+                let values = if from_stdin {
+                    if values.len() > 1 || qty.is_some() {
+                        return Err(anyhow::anyhow!(
+                            "--value - reads every value from stdin and cannot be combined with other values or --qty"
+                        ));
+                    }
+                    read_stdin_values()?
+                } else {
+                    values
+                };
+                let special = non_finite_values(&values, format);
+                if !special.is_empty() {
+                    warn!(
+                        "writing non-finite float value(s) {}; many devices reject or misinterpret NaN and infinity",
+                        special.join(", ")
+                    );
+                }
+                let values = encode_values(&values, format, word_order, byte_swap)
+                    .map_err(|e| anyhow::anyhow!(e))?;
+                // Piped or file input may be longer than one request; it is written in chunks below
+                let values = if from_stdin || from.is_some() {
+                    values
+                } else {
+                    expand_write_values(values, qty, Area::Holding)
+                        .map_err(|e| anyhow::anyhow!(e))?
+                };
+                check_address_range(start, values.len()).map_err(|e| anyhow::anyhow!(e))?;
+                if common.verbose {
+                    enable_error_summary();
+                }
                 let mut client = connect_to_modbus(&common).await?;
 
-                if values.len() == 1 {
-                    // Single register write (FC 6)
-                    modbus_operation_with_timeout(
-                        || client.write_single_register(start, values[0]),
-                        "write register",
-                        common.timeout,
+                let payload = WritePayload::Registers(&values, format.width());
+                if let Some(times) = repeat.repeat {
+                    let stats = repeat_write(
+                        &mut client,
+                        start,
+                        &payload,
+                        &common,
+                        times,
+                        repeat.interval,
                     )
-                    .await?;
+                    .await;
+                    return report_repeat(stats, json);
+                }
+                write_once(&mut client, start, &payload, OpSettings::new(&common)).await?;
+
+                if json {
+                    print_json(&write_event(common.unit, "holding", start, &values));
+                } else if !common.quiet && values.len() == 1 {
                     if common.verbose {
                         println!(
                             "Wrote holding register at address {} with value {} (0x{:04X}) (Unit ID: {})",
@@ -144,25 +989,259 @@ async fn main() -> anyhow::Result<()> {
                             start, values[0], common.unit
                         );
                     }
-                } else {
-                    // Multiple registers write (FC 16)
-                    modbus_operation_with_timeout(
-                        || client.write_multiple_registers(start, &values),
-                        "write registers",
-                        common.timeout,
-                    )
-                    .await?;
+                } else if !common.quiet {
                     println!(
                         "Wrote {} holding register(s) starting at address {} (Unit ID: {})",
                         values.len(),
                         start,
                         common.unit
                     );
-                    print_register_table(&values, start, common.verbose);
+                    print_register_table(
+                        &values,
+                        start,
+                        &TableOptions {
+                            verbose: common.verbose,
+                            ..Default::default()
+                        },
+                    );
                 }
             }
         },
 
+        Command::Diag {
+            op: DiagOp::Counters { common },
+        } => {
+            if common.verbose {
+                enable_error_summary();
+            }
+            let mut client = connect_to_modbus(&common).await?;
+            let counters = read_diagnostic_counters(&mut client, common.timeout).await?;
+            if !common.quiet {
+                println!(
+                    "Diagnostic counters from {} unit {}",
+                    common.target(),
+                    common.unit
+                );
+            }
+            print_diag_counters(
+                &counters,
+                &TableOptions {
+                    quiet: common.quiet,
+                    ..Default::default()
+                },
+            );
+        }
+
+        Command::Fifo { pointer, common } => {
+            if common.verbose {
+                enable_error_summary();
+            }
+            let mut client = connect_to_modbus(&common).await?;
+            let values = read_fifo_queue(&mut client, pointer, OpSettings::new(&common)).await?;
+            if !common.quiet {
+                println!(
+                    "FIFO queue at {pointer} from {} unit {}: {} value(s)",
+                    common.target(),
+                    common.unit,
+                    values.len()
+                );
+            }
+            print_fifo_values(
+                &values,
+                &TableOptions {
+                    verbose: common.verbose,
+                    quiet: common.quiet,
+                    ..Default::default()
+                },
+            );
+        }
+
+        Command::File { op } => {
+            let common = match &op {
+                FileOp::Read { common, .. } | FileOp::Write { common, .. } => common,
+            };
+            if common.verbose {
+                enable_error_summary();
+            }
+            match op {
+                FileOp::Read {
+                    file,
+                    record,
+                    length,
+                    common,
+                } => {
+                    let mut client = connect_to_modbus(&common).await?;
+                    let values = read_file_record(
+                        &mut client,
+                        file,
+                        record,
+                        length,
+                        OpSettings::new(&common),
+                    )
+                    .await?;
+                    if !common.quiet {
+                        println!(
+                            "File {file} records {record}-{} from {} unit {}",
+                            record as u32 + length as u32 - 1,
+                            common.target(),
+                            common.unit
+                        );
+                    }
+                    print_register_table(
+                        &values,
+                        record,
+                        &TableOptions {
+                            verbose: common.verbose,
+                            quiet: common.quiet,
+                            ..Default::default()
+                        },
+                    );
+                }
+                FileOp::Write {
+                    file,
+                    record,
+                    values,
+                    common,
+                } => {
+                    if values.len() > MAX_WRITE_RECORD_LEN as usize {
+                        return Err(anyhow::anyhow!(
+                        "Invalid quantity {}: a file record write carries at most {MAX_WRITE_RECORD_LEN} registers (FC 21)",
+                        values.len()
+                    ));
+                    }
+                    let mut client = connect_to_modbus(&common).await?;
+                    write_file_record(&mut client, file, record, &values, OpSettings::new(&common))
+                        .await?;
+                    if !common.quiet {
+                        println!(
+                        "Wrote {} register(s) to file {file} starting at record {record} (Unit ID: {})",
+                        values.len(),
+                        common.unit
+                    );
+                    }
+                }
+            }
+        }
+
+        Command::Bench {
+            area,
+            addr,
+            qty,
+            duration,
+            common,
+        } => {
+            if qty > area.max_read_qty() {
+                return Err(anyhow::anyhow!(
+                    "{} allow at most {} values per read",
+                    area.label(),
+                    area.max_read_qty()
+                ));
+            }
+            enable_error_summary();
+            let mut client = connect_to_modbus(&common).await?;
+            println!(
+                "Benchmarking {} {addr}-{} on {} for {}ms...",
+                area.label(),
+                addr as u32 + qty as u32 - 1,
+                common.target(),
+                duration.as_millis()
+            );
+
+            let spans = [(addr, qty)];
+            let mut latencies = Vec::new();
+            let started = Instant::now();
+            while started.elapsed() < duration {
+                let read_started = Instant::now();
+                match area {
+                    Area::Coil | Area::Discrete => {
+                        read_coil_spans(&mut client, area, &spans, OpSettings::new(&common))
+                            .await?;
+                    }
+                    Area::Holding | Area::Input => {
+                        read_register_spans(&mut client, area, &spans, OpSettings::new(&common))
+                            .await?;
+                    }
+                }
+                latencies.push(read_started.elapsed());
+            }
+            println!("{}", bench::summarize(latencies, started.elapsed()));
+        }
+
+        Command::Repl { common } => {
+            enable_error_summary();
+            repl::run_repl(&common).await?
+        }
+
+        Command::Ping { common } => {
+            let elapsed = ping(&common).await?;
+            debug!(
+                "Reply from {} unit {} in {}ms",
+                common.target(),
+                common.unit,
+                elapsed.as_millis()
+            );
+        }
+
+        Command::Selftest { timeout, verbose } => {
+            let checks = selftest::run_selftest(timeout, verbose).await?;
+            for check in &checks {
+                println!("{check}");
+            }
+            println!("{}", selftest::summary(&checks));
+            if !checks.iter().all(|check| check.passed()) {
+                anyhow::bail!("Self-test failed");
+            }
+        }
+
+        Command::Diff {
+            area,
+            addr,
+            qty,
+            ip_a,
+            ip_b,
+            port_a,
+            port_b,
+            common,
+        } => {
+            check_address_range(addr, qty as usize).map_err(|e| anyhow::anyhow!(e))?;
+            let last = addr + (qty - 1);
+            let addresses: Vec<u16> = (addr..=last).collect();
+            let spans = plan_read_spans(&addresses, area.max_read_qty(), None);
+            let device = |ip, port: Option<u16>| Common {
+                ip: Some(ip),
+                port: port.unwrap_or(common.port),
+                ..common.clone()
+            };
+            let (device_a, device_b) = (device(ip_a, port_a), device(ip_b, port_b));
+            let values_a = read_for_diff(&device_a, area, &spans)
+                .await
+                .with_context(|| format!("Device A ({})", device_a.target()))?;
+            let values_b = read_for_diff(&device_b, area, &spans)
+                .await
+                .with_context(|| format!("Device B ({})", device_b.target()))?;
+
+            if !common.quiet {
+                println!(
+                    "Comparing {} {addr}-{last}: A = {}, B = {}",
+                    area.label(),
+                    device_a.target(),
+                    device_b.target()
+                );
+            }
+            let differences = diff::compare(&values_a, &values_b);
+            if differences.is_empty() {
+                println!("no differences");
+            } else {
+                for line in diff::format_differences(&differences) {
+                    println!("{line}");
+                }
+                return Err(anyhow::anyhow!(
+                    "{} of {qty} address(es) differ",
+                    differences.len()
+                ));
+            }
+        }
+
         Command::Server {
             ip,
             device,
@@ -171,70 +1250,206 @@ async fn main() -> anyhow::Result<()> {
             parity,
             stop_bits,
             data_bits,
-            unit: _,
+            flow_control,
+            unit,
             num_coils,
             num_discrete,
             num_holding,
             num_input,
-            verbose: _,
+            sparse,
+            pattern,
+            seed,
+            jitter,
+            coil_pattern,
+            discrete_pattern,
+            verbose,
+            clamp_reads,
+            base_addr,
+            prometheus_port,
+            webhook,
+            idle_timeout,
+            events_port,
+            max_connections,
+            write_delay,
+            print_map,
+            fifo,
+            fail_first,
+            heartbeat_reg,
         } => {
+            if verbose {
+                logging::raise_log_level(LogLevel::Debug);
+            }
             // Auto-detect TCP vs RTU based on arguments
             // Create shared data storage
-            let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(
-                num_coils,
-                num_discrete,
-                num_holding,
-                num_input,
-            )));
+            let data = if sparse {
+                ModbusData::sparse(num_coils, num_discrete, num_holding, num_input)
+            } else {
+                ModbusData::new(num_coils, num_discrete, num_holding, num_input)
+            }
+            .with_holding_fill(Fill {
+                pattern,
+                seed,
+                jitter,
+            })
+            .with_coil_fill(coil_pattern)
+            .with_discrete_fill(discrete_pattern);
+            let data = Arc::new(tokio::sync::RwLock::new(data));
+            if let Some(addr) = heartbeat_reg {
+                let index = addr
+                    .checked_sub(base_addr)
+                    .filter(|&index| index < num_holding)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "--heartbeat-reg {addr} is outside the {num_holding} holding register(s) starting at {base_addr}"
+                        )
+                    })?;
+                spawn_heartbeat(data.clone(), index as usize);
+            }
+            let options = ServerOptions {
+                clamp_reads,
+                base_addr,
+                webhook,
+                events: events_port.map(|_| EventStream::default()),
+                idle_timeout: idle_timeout.map(Duration::from_secs),
+                max_connections: max_connections.map(|max| max as usize),
+                write_delay: write_delay.unwrap_or_default(),
+                fifo_pointers: fifo,
+                busy_requests: Arc::new(AtomicUsize::new(fail_first.unwrap_or(0))),
+                unit: Some(unit),
+                ..Default::default()
+            };
+
+            if let Some(metrics_port) = prometheus_port {
+                let metrics_ip = ip.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+                let listener =
+                    tokio::net::TcpListener::bind(SocketAddr::new(metrics_ip, metrics_port))
+                        .await?;
+                println!(
+                    "Serving Prometheus metrics on http://{metrics_ip}:{metrics_port}/metrics"
+                );
+                tokio::spawn(serve_metrics(listener, options.metrics.clone()));
+            }
+            if let (Some(events_port), Some(events)) = (events_port, &options.events) {
+                let events_ip = ip.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+                let listener =
+                    tokio::net::TcpListener::bind(SocketAddr::new(events_ip, events_port)).await?;
+                println!("Streaming events on http://{events_ip}:{events_port}/events");
+                tokio::spawn(serve_events(listener, events.clone()));
+            }
 
             // Print common configuration
             let print_config = || {
                 println!("Configuration:");
-                println!(
-                    "  Coils: {} (addresses 0-{})",
-                    num_coils,
-                    num_coils.saturating_sub(1)
-                );
-                println!(
-                    "  Discrete Inputs: {} (addresses 0-{})",
-                    num_discrete,
-                    num_discrete.saturating_sub(1)
-                );
-                println!(
-                    "  Holding Registers: {} (addresses 0-{})",
-                    num_holding,
-                    num_holding.saturating_sub(1)
-                );
-                println!(
-                    "  Input Registers: {} (addresses 0-{})",
-                    num_input,
-                    num_input.saturating_sub(1)
-                );
-                println!("  Initialization: Each address value equals its address");
+                println!("  Unit ID: {unit}");
+                if !print_map {
+                    println!(
+                        "  Coils: {} (addresses {}-{})",
+                        num_coils,
+                        base_addr,
+                        base_addr as u32 + num_coils.saturating_sub(1) as u32
+                    );
+                    println!(
+                        "  Discrete Inputs: {} (addresses {}-{})",
+                        num_discrete,
+                        base_addr,
+                        base_addr as u32 + num_discrete.saturating_sub(1) as u32
+                    );
+                    println!(
+                        "  Holding Registers: {} (addresses {}-{})",
+                        num_holding,
+                        base_addr,
+                        base_addr as u32 + num_holding.saturating_sub(1) as u32
+                    );
+                    println!(
+                        "  Input Registers: {} (addresses {}-{})",
+                        num_input,
+                        base_addr,
+                        base_addr as u32 + num_input.saturating_sub(1) as u32
+                    );
+                }
+                match pattern {
+                    Pattern::Address => {
+                        println!("  Initialization: Each address value equals its address")
+                    }
+                    Pattern::Ramp => println!(
+                        "  Initialization: Holding registers ramp from 0 to 65535; input registers equal their address"
+                    ),
+                    Pattern::Alternating => println!(
+                        "  Initialization: Holding registers alternate 0 and 65535; input registers equal their address"
+                    ),
+                    Pattern::Random => println!(
+                        "  Initialization: Holding registers are random (seed {seed}); input registers equal their address"
+                    ),
+                }
+                for (area, bits) in [
+                    ("Coils", coil_pattern),
+                    ("Discrete inputs", discrete_pattern),
+                ] {
+                    match bits {
+                        BitPattern::AllOff => {}
+                        BitPattern::AllOn => println!("  Initialization: {area} are all on"),
+                        BitPattern::Alternating => {
+                            println!("  Initialization: {area} alternate on and off")
+                        }
+                    }
+                }
+                if jitter > 0 {
+                    println!(
+                        "  Jitter: holding registers vary by up to {jitter}% of full scale (seed {seed})"
+                    );
+                }
+                if let Some(addr) = heartbeat_reg {
+                    println!("  Heartbeat: holding register {addr} counts up once a second");
+                }
+                if let Some(count) = fail_first {
+                    println!("  Cold start: the first {count} request(s) get Server Device Busy");
+                }
                 println!();
+                if print_map {
+                    let areas = [
+                        ("Coils", num_coils),
+                        ("Discrete Inputs", num_discrete),
+                        ("Holding Registers", num_holding),
+                        ("Input Registers", num_input),
+                    ];
+                    print!("{}", memory_map_table(base_addr, &areas, TableStyle::Plain));
+                    println!();
+                }
             };
 
+            let requested_port = if port == 0 {
+                "an OS-assigned port".to_string()
+            } else {
+                port.to_string()
+            };
             match (ip, device) {
                 (Some(ip_addr), None) => {
                     // TCP Server
-                    println!("Starting Modbus TCP server on {ip_addr}:{port}");
+                    println!("Starting Modbus TCP server on {ip_addr} port {requested_port}");
                     print_config();
-                    run_tcp_server(ip_addr, port, data).await?;
+                    run_tcp_server(ip_addr, port, data, options).await?;
                 }
                 (None, Some(device_path)) => {
                     // RTU Server
                     println!("Starting Modbus RTU server on {}", device_path.display());
                     print_config();
-                    run_rtu_server(&device_path, baud, &parity, &stop_bits, &data_bits, data)
-                        .await?;
+                    let settings = SerialSettings {
+                        baud,
+                        parity,
+                        stop_bits,
+                        data_bits,
+                        flow_control,
+                    };
+                    run_rtu_server(&device_path, settings, data, options).await?;
                 }
                 (None, None) => {
                     // Default to TCP on 0.0.0.0:502
-                    use std::net::{IpAddr, Ipv4Addr};
                     let ip_addr = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
-                    println!("Starting Modbus TCP server on {ip_addr}:{port} (default)");
+                    println!(
+                        "Starting Modbus TCP server on {ip_addr} port {requested_port} (default)"
+                    );
                     print_config();
-                    run_tcp_server(ip_addr, port, data).await?;
+                    run_tcp_server(ip_addr, port, data, options).await?;
                 }
                 (Some(_), Some(_)) => {
                     // This should be prevented by clap conflicts
@@ -0,0 +1,136 @@
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_modbus::prelude::Request;
+
+// Operation labels reported as `modbus_requests_total{op="..."}`
+const OPS: [&str; 9] = [
+    "read_coils",
+    "read_discrete",
+    "read_holding",
+    "read_input",
+    "write_coil",
+    "write_register",
+    "write_coils",
+    "write_registers",
+    "other",
+];
+
+/// Request and connection counters shared between the Modbus server and the metrics endpoint
+#[derive(Debug, Default)]
+pub struct ServerMetrics {
+    requests: [AtomicU64; OPS.len()],
+    active_connections: AtomicU64,
+}
+
+impl ServerMetrics {
+    pub fn record_request(&self, request: &Request<'_>) {
+        let op = match request {
+            Request::ReadCoils(..) => 0,
+            Request::ReadDiscreteInputs(..) => 1,
+            Request::ReadHoldingRegisters(..) => 2,
+            Request::ReadInputRegisters(..) => 3,
+            Request::WriteSingleCoil(..) => 4,
+            Request::WriteSingleRegister(..) => 5,
+            Request::WriteMultipleCoils(..) => 6,
+            Request::WriteMultipleRegisters(..) => 7,
+            _ => 8,
+        };
+        self.requests[op].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_opened(&self) {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self) {
+        self.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        text.push_str("# HELP modbus_requests_total Modbus requests handled by the server.\n");
+        text.push_str("# TYPE modbus_requests_total counter\n");
+        for (op, count) in OPS.iter().zip(&self.requests) {
+            let _ = writeln!(
+                text,
+                "modbus_requests_total{{op=\"{op}\"}} {}",
+                count.load(Ordering::Relaxed)
+            );
+        }
+        text.push_str("# HELP modbus_active_connections Currently connected Modbus TCP clients.\n");
+        text.push_str("# TYPE modbus_active_connections gauge\n");
+        let _ = writeln!(
+            text,
+            "modbus_active_connections {}",
+            self.active_connections.load(Ordering::Relaxed)
+        );
+        text
+    }
+}
+
+// Answer `GET /metrics` with the current counters; every other path is a 404
+pub async fn serve_metrics(listener: TcpListener, metrics: std::sync::Arc<ServerMetrics>) {
+    loop {
+        let (mut stream, _) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!("Metrics endpoint: {e}");
+                continue;
+            }
+        };
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let mut buffer = [0u8; 1024];
+            let len = stream.read(&mut buffer).await.unwrap_or(0);
+            let request_line = String::from_utf8_lossy(&buffer[..len]);
+
+            let response = if request_line.starts_with("GET /metrics ") {
+                let body = metrics.render();
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+            } else {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string()
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_counts_requests_by_op() {
+        let metrics = ServerMetrics::default();
+        metrics.record_request(&Request::ReadHoldingRegisters(0, 1));
+        metrics.record_request(&Request::ReadHoldingRegisters(5, 2));
+        metrics.record_request(&Request::WriteSingleCoil(0, true));
+        metrics.record_request(&Request::ReportServerId);
+
+        let text = metrics.render();
+        assert!(text.contains("modbus_requests_total{op=\"read_holding\"} 2\n"));
+        assert!(text.contains("modbus_requests_total{op=\"write_coil\"} 1\n"));
+        assert!(text.contains("modbus_requests_total{op=\"read_coils\"} 0\n"));
+        assert!(text.contains("modbus_requests_total{op=\"other\"} 1\n"));
+        assert!(text.contains("# TYPE modbus_requests_total counter"));
+    }
+
+    #[test]
+    fn test_render_active_connections_gauge() {
+        let metrics = ServerMetrics::default();
+        metrics.connection_opened();
+        metrics.connection_opened();
+        metrics.connection_closed();
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE modbus_active_connections gauge"));
+        assert!(text.contains("modbus_active_connections 1\n"));
+    }
+}
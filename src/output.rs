@@ -0,0 +1,805 @@
+use crate::cli::{Area, TimeRef, ValueFormat};
+use crate::layout::FieldValue;
+use crate::table::{format_utc, Decoded, DecodedRow};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Instant, SystemTime};
+
+// Whether `--json-pretty` asked for indented JSON instead of one object per line
+static PRETTY_JSON: AtomicBool = AtomicBool::new(false);
+
+// Whether `--output-format yaml` asked for the JSON results as YAML documents
+static YAML_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+pub fn enable_pretty_json() {
+    PRETTY_JSON.store(true, Ordering::Relaxed);
+}
+
+pub fn enable_yaml_output() {
+    YAML_OUTPUT.store(true, Ordering::Relaxed);
+}
+
+/// Render a result as JSON the way the output flags ask for
+pub fn render_json(value: &impl Serialize) -> String {
+    render(
+        value,
+        PRETTY_JSON.load(Ordering::Relaxed),
+        YAML_OUTPUT.load(Ordering::Relaxed),
+    )
+}
+
+// Compact JSON, indented JSON with two spaces per level, or a YAML document that starts with
+// `---`, so repeated results form a YAML stream
+fn render(value: &impl Serialize, pretty: bool, yaml: bool) -> String {
+    if yaml {
+        let body = serde_yaml::to_string(value).expect("results serialize to YAML");
+        format!("---\n{}", body.trim_end())
+    } else if pretty {
+        serde_json::to_string_pretty(value).expect("results serialize to JSON")
+    } else {
+        serde_json::to_string(value).expect("results serialize to JSON")
+    }
+}
+
+/// Print a JSON result to stdout, indented under `--json-pretty` or as YAML
+pub fn print_json(value: &impl Serialize) {
+    println!("{}", render_json(value));
+}
+
+/// Print a JSON failure to stderr, indented under `--json-pretty` or as YAML
+pub fn eprint_json(value: &impl Serialize) {
+    eprintln!("{}", render_json(value));
+}
+
+/// Holding registers to write from a `write holding --from` JSON file
+#[derive(Debug, PartialEq)]
+pub struct JsonWrite {
+    pub start: u16,
+    /// Values as written in the file, for the --format encoders
+    pub values: Vec<String>,
+    pub format: ValueFormat,
+}
+
+// A value to write: a JSON number, or a string the --format encoders parse such as "0x1F"
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum WriteValue {
+    Number(serde_json::Number),
+    Text(String),
+}
+
+impl WriteValue {
+    fn into_text(self) -> String {
+        match self {
+            WriteValue::Number(number) => number.to_string(),
+            WriteValue::Text(text) => text,
+        }
+    }
+}
+
+// One entry of "values": a bare value, or a `{"address":..,"value":..}` from a read capture
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum WriteItem {
+    Value(WriteValue),
+    Read { address: u16, value: WriteValue },
+}
+
+// A `--from` file; other members, such as a capture's "area" and "unit", are ignored
+#[derive(Debug, Deserialize)]
+struct WriteFile {
+    start: Option<u16>,
+    format: Option<String>,
+    values: Vec<WriteItem>,
+}
+
+/// Parse a `--from` file: `{"start":100,"values":[1,2,3]}`, optionally with a `"format"` such
+/// as `"float32"` that overrides `format`, or a holding register read captured with
+/// `--output-format json`, whose values must form one contiguous block of `format` values.
+/// Malformed JSON is reported with its line and column
+pub fn parse_write_json(text: &str, format: ValueFormat) -> Result<JsonWrite, String> {
+    let file: WriteFile = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let format = match file.format {
+        Some(name) => {
+            ValueFormat::from_str(&name, true).map_err(|_| format!("unknown format \"{name}\""))?
+        }
+        None => format,
+    };
+    if file.values.is_empty() {
+        return Err("\"values\" is empty".to_string());
+    }
+
+    let mut start = None;
+    let mut values = Vec::new();
+    for (i, item) in file.values.into_iter().enumerate() {
+        match item {
+            WriteItem::Read { address, value } => {
+                let first = *start.get_or_insert(address);
+                if address as usize != first as usize + i * format.width() {
+                    return Err(format!(
+                        "address {address} does not follow on from the value before it"
+                    ));
+                }
+                values.push(value.into_text());
+            }
+            WriteItem::Value(value) => values.push(value.into_text()),
+        }
+    }
+    let start = file
+        .start
+        .or(start)
+        .ok_or_else(|| "missing \"start\"".to_string())?;
+    Ok(JsonWrite {
+        start,
+        values,
+        format,
+    })
+}
+
+// CSV header for register reads; multi-register formats keep the raw registers alongside the value
+pub fn register_csv_header(format: ValueFormat) -> &'static str {
+    match format {
+        ValueFormat::Gray | ValueFormat::Bcd => "address,raw,value",
+        ValueFormat::Duration => "address,raw,duration",
+        ValueFormat::U16 | ValueFormat::Packed | ValueFormat::Hexdump | ValueFormat::BoolMatrix => {
+            "address,value"
+        }
+        ValueFormat::Float32 | ValueFormat::ModiconFloat => "address,reg_hi,reg_lo,float",
+        ValueFormat::Epoch32 => "address,reg_hi,reg_lo,epoch",
+        ValueFormat::Duration32 => "address,reg_hi,reg_lo,duration",
+        ValueFormat::Int64 => "address,reg0,reg1,reg2,reg3,int64",
+        ValueFormat::Uint64 => "address,reg0,reg1,reg2,reg3,uint64",
+        ValueFormat::Float64 => "address,reg0,reg1,reg2,reg3,float64",
+    }
+}
+
+// CSV header for Enron reads, whose 32-bit registers always carry both raw words
+pub fn enron_csv_header(format: ValueFormat) -> &'static str {
+    match format {
+        ValueFormat::Float32 => "address,reg_hi,reg_lo,float",
+        _ => "address,reg_hi,reg_lo,value",
+    }
+}
+
+// CSV line for one layout field: name, address of its first register and decoded value
+pub fn layout_csv_line(field: &FieldValue) -> String {
+    format!(
+        "{},{},{}",
+        field.name,
+        field.addr,
+        field.value.display(false)
+    )
+}
+
+pub fn register_csv_line(row: &DecodedRow, format: ValueFormat) -> String {
+    let mut fields = vec![row.addr.to_string()];
+    fields.extend(row.raw.iter().map(|raw| raw.to_string()));
+    // Single-register formats that convert the value keep the raw register alongside it too
+    let converted = matches!(
+        format,
+        ValueFormat::Gray | ValueFormat::Bcd | ValueFormat::Duration
+    );
+    if row.raw.len() > 1 || converted {
+        fields.push(row.value.display(false));
+    }
+    fields.join(",")
+}
+
+pub fn coil_csv_line(addr: u16, value: bool) -> String {
+    format!("{addr},{}", u8::from(value))
+}
+
+// CSV line for one bit of a register, addressed as `register.bit`
+pub fn bit_csv_line(addr: u16, bit: u8, value: bool) -> String {
+    format!("{addr}.{bit},{}", u8::from(value))
+}
+
+/// A failure for `--output-format json`, e.g. {"error":"timeout","operation":"read holding registers"}.
+/// Only the fields that apply to the kind of failure are written
+#[derive(Debug, Default, Serialize)]
+pub struct ErrorJson<'a> {
+    pub error: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exception: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl<'a> ErrorJson<'a> {
+    pub fn new(error: &'a str) -> Self {
+        Self {
+            error,
+            ..Default::default()
+        }
+    }
+}
+
+/// One read poll for `--output-format json`, e.g. {"area":"holding","unit":1,"values":[..]}
+#[derive(Debug, Serialize)]
+pub struct ReadJson<T> {
+    pub area: &'static str,
+    pub unit: u8,
+    pub values: Vec<T>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegisterJson {
+    pub address: u16,
+    pub value: Decoded,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CoilJson {
+    pub address: u16,
+    pub value: bool,
+}
+
+// One bit of a holding or input register, read with `--addr register.bit`
+#[derive(Debug, Serialize)]
+pub struct BitJson {
+    pub address: u16,
+    pub bit: u8,
+    pub value: bool,
+}
+
+/// A `--layout` read, whose values are named fields instead of addresses
+#[derive(Debug, Serialize)]
+pub struct LayoutJson<'a> {
+    pub area: &'static str,
+    pub unit: u8,
+    pub fields: Vec<FieldJson<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldJson<'a> {
+    pub name: &'a str,
+    pub address: u16,
+    pub value: Decoded,
+}
+
+pub fn register_json(area: Area, unit: u8, rows: &[DecodedRow]) -> ReadJson<RegisterJson> {
+    ReadJson {
+        area: area.key(),
+        unit,
+        values: rows
+            .iter()
+            .map(|row| RegisterJson {
+                address: row.addr,
+                value: row.value,
+            })
+            .collect(),
+    }
+}
+
+pub fn coil_json(area: Area, unit: u8, rows: &[(u16, bool)]) -> ReadJson<CoilJson> {
+    ReadJson {
+        area: area.key(),
+        unit,
+        values: rows
+            .iter()
+            .map(|&(address, value)| CoilJson { address, value })
+            .collect(),
+    }
+}
+
+pub fn bit_json(area: Area, unit: u8, address: u16, bit: u8, value: bool) -> ReadJson<BitJson> {
+    ReadJson {
+        area: area.key(),
+        unit,
+        values: vec![BitJson {
+            address,
+            bit,
+            value,
+        }],
+    }
+}
+
+pub fn layout_json(area: Area, unit: u8, fields: &[FieldValue]) -> LayoutJson<'_> {
+    LayoutJson {
+        area: area.key(),
+        unit,
+        fields: fields
+            .iter()
+            .map(|field| FieldJson {
+                name: &field.name,
+                address: field.addr,
+                value: field.value,
+            })
+            .collect(),
+    }
+}
+
+// ISO 8601 UTC timestamp with milliseconds, e.g. 2023-11-14T22:13:20.250Z, so polls less
+// than a second apart stay distinct
+fn format_utc_millis(time: SystemTime) -> String {
+    let since_epoch = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = format_utc(since_epoch.as_secs());
+    format!(
+        "{}.{:03}Z",
+        secs.trim_end_matches('Z'),
+        since_epoch.subsec_millis()
+    )
+}
+
+/// CSV file that read results are appended to, one row per value. Every row starts with
+/// the area it was read from, so captures of several areas can't be mixed up later, after
+/// the time of the read under `--time-ref`
+pub struct CsvOutput {
+    writer: BufWriter<File>,
+    area: Area,
+    time_ref: Option<TimeRef>,
+    started: Instant,
+    // Rows to buffer before flushing, and how many are buffered now
+    flush_interval: usize,
+    unflushed: usize,
+}
+
+impl CsvOutput {
+    /// Create (or truncate) the file and write the header row. Times relative to the start are
+    /// preceded by a `# start:` comment with the wall-clock start, so they can be converted back
+    pub fn create(
+        path: &Path,
+        area: Area,
+        header: &str,
+        time_ref: Option<TimeRef>,
+    ) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {e}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        match time_ref {
+            Some(TimeRef::Start) => {
+                writeln!(writer, "# start: {}", format_utc_millis(SystemTime::now()))?;
+                writeln!(writer, "seconds,area,{header}")?;
+            }
+            Some(TimeRef::Wall) => writeln!(writer, "time,area,{header}")?,
+            None => writeln!(writer, "area,{header}")?,
+        }
+        Ok(Self {
+            writer,
+            area,
+            time_ref,
+            started: Instant::now(),
+            flush_interval: 1,
+            unflushed: 0,
+        })
+    }
+
+    /// Open the file for appending rows without a header, creating it if needed, so several
+    /// runs can build one CSV
+    pub fn append(path: &Path, area: Area, time_ref: Option<TimeRef>) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| anyhow::anyhow!("Failed to open {}: {e}", path.display()))?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            area,
+            time_ref,
+            started: Instant::now(),
+            flush_interval: 1,
+            unflushed: 0,
+        })
+    }
+
+    /// Flush only once `rows` rows are waiting instead of after every poll, trading how much
+    /// a crash can lose for fewer writes when logging fast. Whatever is left is flushed on drop
+    pub fn with_flush_interval(mut self, rows: usize) -> Self {
+        self.flush_interval = rows.max(1);
+        self
+    }
+
+    /// Append lines and flush so the file is usable while a watch is still running (with a
+    /// flush interval, once enough rows are waiting). Lines written together are one poll and
+    /// share a time
+    pub fn write_lines(&mut self, lines: impl IntoIterator<Item = String>) -> anyhow::Result<()> {
+        let time = match self.time_ref {
+            Some(TimeRef::Wall) => format!("{},", format_utc_millis(SystemTime::now())),
+            Some(TimeRef::Start) => format!("{:.3},", self.started.elapsed().as_secs_f64()),
+            None => String::new(),
+        };
+        for line in lines {
+            writeln!(self.writer, "{time}{},{line}", self.area.key())?;
+            self.unflushed += 1;
+        }
+        if self.unflushed >= self.flush_interval {
+            self.writer.flush()?;
+            self.unflushed = 0;
+        }
+        Ok(())
+    }
+}
+
+/// Expected values from a CSV file for `--expect`, keyed by address. Reads captures made with
+/// `--output` (any time and area columns, raw register columns and layout field names are
+/// skipped: the address is the first whole number and the value the last column) as well as
+/// plain `address,value` lines. Coils may be written ON/OFF. A header row, blank lines and `#`
+/// comments are ignored; when an address appears more than once the last row counts
+pub fn parse_expected_csv(text: &str) -> Result<BTreeMap<u16, String>, String> {
+    let mut expected = BTreeMap::new();
+    let lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+    for (row, (index, line)) in lines.enumerate() {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let (value, leading) = fields
+            .split_last()
+            .expect("split yields at least one field");
+        let addr = leading.iter().find_map(|field| field.parse::<u16>().ok());
+        let Some(addr) = addr else {
+            // Only the first row may be a header
+            if row == 0 {
+                continue;
+            }
+            return Err(format!("line {}: no address in '{line}'", index + 1));
+        };
+        let value = match value.to_ascii_uppercase().as_str() {
+            "ON" | "TRUE" => "1".to_string(),
+            "OFF" | "FALSE" => "0".to_string(),
+            _ if value.parse::<f64>().is_ok() => value.to_string(),
+            _ => return Err(format!("line {}: invalid value '{value}'", index + 1)),
+        };
+        expected.insert(addr, value);
+    }
+    Ok(expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::WordOrder;
+    use crate::enron::decode_enron;
+    use crate::table::{decode_rows, TableOptions};
+
+    fn json(value: &impl Serialize) -> String {
+        serde_json::to_string(value).unwrap()
+    }
+
+    #[test]
+    fn test_parse_write_json() {
+        let write = parse_write_json(r#"{"start":100,"values":[1,2,3]}"#, ValueFormat::U16);
+        assert_eq!(
+            write,
+            Ok(JsonWrite {
+                start: 100,
+                values: vec!["1".into(), "2".into(), "3".into()],
+                format: ValueFormat::U16,
+            })
+        );
+
+        // The file's format wins over the command line's
+        let write = parse_write_json(
+            r#"{"start": 0, "format": "float32", "values": [1.5, -2]}"#,
+            ValueFormat::U16,
+        )
+        .unwrap();
+        assert_eq!(write.format, ValueFormat::Float32);
+        assert_eq!(write.values, ["1.5", "-2"]);
+
+        // A float32 read captured with --output-format json replays as the same write
+        let capture = r#"{"area":"holding","unit":1,"values":[{"address":10,"value":1.5},{"address":12,"value":2}]}"#;
+        let write = parse_write_json(capture, ValueFormat::Float32).unwrap();
+        assert_eq!(write.start, 10);
+        assert_eq!(write.values, ["1.5", "2"]);
+        assert!(parse_write_json(capture, ValueFormat::U16)
+            .unwrap_err()
+            .contains("address 12 does not follow"));
+
+        // String values have their escapes decoded before the encoders see them
+        let write = parse_write_json(r#"{"start":0,"values":["\u0030x10"]}"#, ValueFormat::U16);
+        assert_eq!(write.unwrap().values, ["0x10"]);
+
+        assert!(parse_write_json(r#"{"values":[1]}"#, ValueFormat::U16).is_err());
+        assert!(parse_write_json(r#"{"start":0,"values":[]}"#, ValueFormat::U16).is_err());
+        assert!(parse_write_json(r#"{"start":0,"values":[null]}"#, ValueFormat::U16).is_err());
+        assert!(parse_write_json(r#"{"start":70000,"values":[1]}"#, ValueFormat::U16).is_err());
+        assert!(parse_write_json("[1,2]", ValueFormat::U16).is_err());
+        // Malformed JSON is rejected with where it went wrong
+        assert!(
+            parse_write_json("{\"start\":0,\n\"values\":[1,]}", ValueFormat::U16)
+                .unwrap_err()
+                .contains("line 2 column")
+        );
+        assert!(
+            parse_write_json(r#"{"start":0,"values":[1]} trailing"#, ValueFormat::U16).is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_expected_csv() {
+        // A capture from --output --time-ref start, with float rows keeping their registers
+        let capture =
+            "# start: 2026-10-16T08:00:00.000Z\nseconds,area,address,reg_hi,reg_lo,float\n\
+                       0.000,holding,0,16840,0,12.5\n0.000,holding,2,0,0,0\n";
+        let expected = parse_expected_csv(capture).unwrap();
+        assert_eq!(
+            expected.into_iter().collect::<Vec<_>>(),
+            [(0, "12.5".to_string()), (2, "0".to_string())]
+        );
+
+        // Plain lines, layout field names, ON/OFF, and a later row replacing an earlier one
+        let expected = parse_expected_csv("5,1\nholding,temperature,7,21.5\n9,ON\n5,2\n").unwrap();
+        assert_eq!(expected[&5], "2");
+        assert_eq!(expected[&7], "21.5");
+        assert_eq!(expected[&9], "1");
+
+        assert_eq!(
+            parse_expected_csv("address,value\n3,hot\n"),
+            Err("line 2: invalid value 'hot'".to_string())
+        );
+        assert_eq!(
+            parse_expected_csv("3,1\nfour,1\n"),
+            Err("line 2: no address in 'four,1'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_register_csv_u16() {
+        let rows = decode_rows(&[(100, 42)], ValueFormat::U16, WordOrder::Big);
+        assert_eq!(register_csv_header(ValueFormat::U16), "address,value");
+        assert_eq!(register_csv_line(&rows[0], ValueFormat::U16), "100,42");
+    }
+
+    #[test]
+    fn test_register_csv_converted_formats_keep_raw_register() {
+        let rows = decode_rows(&[(7, 0x1234)], ValueFormat::Bcd, WordOrder::Big);
+        assert_eq!(register_csv_header(ValueFormat::Bcd), "address,raw,value");
+        assert_eq!(register_csv_line(&rows[0], ValueFormat::Bcd), "7,4660,1234");
+    }
+
+    #[test]
+    fn test_register_csv_float32_keeps_raw_registers() {
+        let rows = decode_rows(
+            &[(0, 0x4048), (1, 0xF5C3)],
+            ValueFormat::Float32,
+            WordOrder::Big,
+        );
+        assert_eq!(
+            register_csv_header(ValueFormat::Float32),
+            "address,reg_hi,reg_lo,float"
+        );
+        assert_eq!(
+            register_csv_line(&rows[0], ValueFormat::Float32),
+            "0,16456,62915,3.14"
+        );
+    }
+
+    #[test]
+    fn test_enron_csv_header_matches_rows() {
+        let options = TableOptions::default();
+        let rows = decode_enron(&[(5001, [0x0001, 0x0002])], &options);
+        assert_eq!(
+            enron_csv_header(ValueFormat::U16),
+            "address,reg_hi,reg_lo,value"
+        );
+        assert_eq!(
+            register_csv_line(&rows[0], ValueFormat::U16),
+            "5001,1,2,65538"
+        );
+
+        let options = TableOptions {
+            format: ValueFormat::Float32,
+            ..TableOptions::default()
+        };
+        let rows = decode_enron(&[(7001, [0x4048, 0xF5C3])], &options);
+        assert_eq!(
+            enron_csv_header(ValueFormat::Float32),
+            "address,reg_hi,reg_lo,float"
+        );
+        assert_eq!(
+            register_csv_line(&rows[0], ValueFormat::Float32),
+            "7001,16456,62915,3.14"
+        );
+    }
+
+    #[test]
+    fn test_format_utc_millis() {
+        let time = SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(1_700_000_000_250);
+        assert_eq!(format_utc_millis(time), "2023-11-14T22:13:20.250Z");
+    }
+
+    #[test]
+    fn test_csv_output_time_columns() {
+        let path = std::env::temp_dir().join(format!("mb-csv-time-ref-{}.csv", std::process::id()));
+
+        let mut csv =
+            CsvOutput::create(&path, Area::Holding, "address,value", Some(TimeRef::Start)).unwrap();
+        csv.write_lines(["0,42".to_string(), "1,43".to_string()])
+            .unwrap();
+        drop(csv);
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("# start: ") && lines[0].ends_with('Z'));
+        assert_eq!(lines[1], "seconds,area,address,value");
+        assert!(lines[2].starts_with("0.0") && lines[2].ends_with(",holding,0,42"));
+
+        let mut csv =
+            CsvOutput::create(&path, Area::Coil, "address,value", Some(TimeRef::Wall)).unwrap();
+        csv.write_lines([coil_csv_line(5, true)]).unwrap();
+        drop(csv);
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "time,area,address,value");
+        assert!(lines[1].ends_with("Z,coil,5,1"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_render_pretty_json() {
+        let rows = decode_rows(&[(0, 42)], ValueFormat::U16, WordOrder::Big);
+        let expected = r#"{
+  "area": "holding",
+  "unit": 1,
+  "values": [
+    {
+      "address": 0,
+      "value": 42
+    }
+  ]
+}"#;
+        assert_eq!(
+            render(&register_json(Area::Holding, 1, &rows), true, false),
+            expected
+        );
+
+        // Brackets and commas inside strings are left alone
+        let failed = ErrorJson {
+            message: Some("a,{\"b\":[1]}".to_string()),
+            ..ErrorJson::new("failed")
+        };
+        assert_eq!(
+            render(&failed, true, false),
+            "{\n  \"error\": \"failed\",\n  \"message\": \"a,{\\\"b\\\":[1]}\"\n}"
+        );
+        assert_eq!(
+            render(&failed, false, false),
+            r#"{"error":"failed","message":"a,{\"b\":[1]}"}"#
+        );
+    }
+
+    #[test]
+    fn test_render_yaml() {
+        let rows = decode_rows(
+            &[(0, 0x4048), (1, 0xF5C3), (2, 0x7FC0), (3, 0x0000)],
+            ValueFormat::Float32,
+            WordOrder::Big,
+        );
+        let expected = "---
+area: holding
+unit: 1
+values:
+- address: 0
+  value: 3.14
+- address: 2
+  value: null";
+        assert_eq!(
+            render(&register_json(Area::Holding, 1, &rows), false, true),
+            expected
+        );
+
+        // Strings YAML would read as something else are quoted
+        let failed = ErrorJson {
+            message: Some("a: {\"b\":[1]}".to_string()),
+            ..ErrorJson::new("true")
+        };
+        assert_eq!(
+            render(&failed, false, true),
+            "---\nerror: 'true'\nmessage: 'a: {\"b\":[1]}'"
+        );
+    }
+
+    #[test]
+    fn test_coil_csv_line() {
+        assert_eq!(coil_csv_line(5, true), "5,1");
+        assert_eq!(coil_csv_line(6, false), "6,0");
+    }
+
+    #[test]
+    fn test_bit_lines() {
+        assert_eq!(bit_csv_line(100, 3, true), "100.3,1");
+        assert_eq!(
+            json(&bit_json(Area::Holding, 1, 100, 3, false)),
+            r#"{"area":"holding","unit":1,"values":[{"address":100,"bit":3,"value":false}]}"#
+        );
+    }
+
+    #[test]
+    fn test_error_json() {
+        let timeout = ErrorJson {
+            operation: Some("read holding registers"),
+            seconds: Some(5),
+            ..ErrorJson::new("timeout")
+        };
+        assert_eq!(
+            json(&timeout),
+            r#"{"error":"timeout","operation":"read holding registers","seconds":5}"#
+        );
+        assert_eq!(json(&ErrorJson::new("failed")), r#"{"error":"failed"}"#);
+
+        // Messages are escaped, whatever the device or OS put in them
+        let failed = ErrorJson {
+            message: Some("a\"b\\c\nd\u{1}".to_string()),
+            ..ErrorJson::new("failed")
+        };
+        assert_eq!(
+            json(&failed),
+            r#"{"error":"failed","message":"a\"b\\c\nd\u0001"}"#
+        );
+    }
+
+    #[test]
+    fn test_read_json_lines() {
+        let rows = decode_rows(
+            &[(0, 0x4048), (1, 0xF5C3)],
+            ValueFormat::Float32,
+            WordOrder::Big,
+        );
+        assert_eq!(
+            json(&register_json(Area::Holding, 1, &rows)),
+            r#"{"area":"holding","unit":1,"values":[{"address":0,"value":3.14}]}"#
+        );
+        assert_eq!(
+            json(&coil_json(Area::Coil, 2, &[(5, true), (6, false)])),
+            r#"{"area":"coil","unit":2,"values":[{"address":5,"value":true},{"address":6,"value":false}]}"#
+        );
+    }
+
+    #[test]
+    fn test_csv_output_prefixes_area() {
+        let path = std::env::temp_dir().join(format!("mb-csv-area-{}.csv", std::process::id()));
+        let mut csv = CsvOutput::create(&path, Area::Discrete, "address,value", None).unwrap();
+        csv.write_lines([coil_csv_line(3, true)]).unwrap();
+        drop(csv);
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text, "area,address,value\ndiscrete,3,1\n");
+    }
+
+    #[test]
+    fn test_csv_output_flush_interval() {
+        let path = std::env::temp_dir().join(format!("mb-csv-flush-{}.csv", std::process::id()));
+        let mut csv = CsvOutput::create(&path, Area::Coil, "address,value", None)
+            .unwrap()
+            .with_flush_interval(3);
+        csv.write_lines([coil_csv_line(0, true), coil_csv_line(1, false)])
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        csv.write_lines([coil_csv_line(0, true)]).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 4);
+
+        // Rows still waiting are written when the capture ends
+        csv.write_lines([coil_csv_line(1, true)]).unwrap();
+        drop(csv);
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(text.ends_with("coil,1,1\n"));
+    }
+
+    #[test]
+    fn test_csv_output_append_skips_header() {
+        let path = std::env::temp_dir().join(format!("mb-csv-append-{}.csv", std::process::id()));
+        let mut csv = CsvOutput::create(&path, Area::Coil, "address,value", None).unwrap();
+        csv.write_lines([coil_csv_line(0, true)]).unwrap();
+        drop(csv);
+        let mut csv = CsvOutput::append(&path, Area::Coil, None).unwrap();
+        csv.write_lines([coil_csv_line(0, false)]).unwrap();
+        drop(csv);
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text, "area,address,value\ncoil,0,1\ncoil,0,0\n");
+    }
+}
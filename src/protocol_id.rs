@@ -0,0 +1,156 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+// Bytes of the MBAP header up to and including its length field, which counts the rest
+const MBAP_PREFIX_LEN: usize = 6;
+
+// Position within a stream of MBAP frames, advanced byte by byte since frames may arrive or
+// leave in pieces
+#[derive(Debug, Clone, Copy, Default)]
+struct FrameCursor {
+    pos: usize,
+    len_hi: u8,
+    frame_len: usize,
+}
+
+impl FrameCursor {
+    // Overwrite the protocol identifier (header bytes 2 and 3) of every frame in `bytes`
+    fn stamp(&mut self, bytes: &mut [u8], protocol_id: u16) {
+        let [hi, lo] = protocol_id.to_be_bytes();
+        for byte in bytes {
+            match self.pos {
+                2 => *byte = hi,
+                3 => *byte = lo,
+                4 => self.len_hi = *byte,
+                5 => {
+                    self.frame_len =
+                        MBAP_PREFIX_LEN + u16::from_be_bytes([self.len_hi, *byte]) as usize
+                }
+                _ => {}
+            }
+            self.pos += 1;
+            if self.pos >= MBAP_PREFIX_LEN && self.pos == self.frame_len {
+                self.pos = 0;
+            }
+        }
+    }
+}
+
+/// Client transport that sends a nonstandard MBAP protocol identifier and restores 0 on the
+/// replies, since the client rejects any other value. Passes bytes through untouched for 0.
+#[derive(Debug)]
+pub struct ProtocolId<T> {
+    inner: T,
+    protocol_id: u16,
+    outgoing: FrameCursor,
+    incoming: FrameCursor,
+}
+
+impl<T> ProtocolId<T> {
+    pub fn new(inner: T, protocol_id: u16) -> Self {
+        Self {
+            inner,
+            protocol_id,
+            outgoing: FrameCursor::default(),
+            incoming: FrameCursor::default(),
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for ProtocolId<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        if this.protocol_id != 0 {
+            this.incoming.stamp(&mut buf.filled_mut()[before..], 0);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for ProtocolId<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.protocol_id == 0 {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        }
+        // Stamp a copy from where the last write stopped, then advance only past what was taken
+        let mut stamped = buf.to_vec();
+        let mut ahead = this.outgoing;
+        ahead.stamp(&mut stamped, this.protocol_id);
+        let written = ready!(Pin::new(&mut this.inner).poll_write(cx, &stamped))?;
+        this.outgoing
+            .stamp(&mut stamped[..written], this.protocol_id);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Read holding register 0, transaction 1, unit 1
+    const REQUEST: [u8; 12] = [0, 1, 0, 0, 0, 6, 1, 3, 0, 0, 0, 1];
+
+    #[test]
+    fn test_stamp_follows_frames_split_across_writes() {
+        let mut cursor = FrameCursor::default();
+        let mut frames = [REQUEST, REQUEST].concat();
+        let (first, rest) = frames.split_at_mut(3);
+        cursor.stamp(first, 0x1234);
+        cursor.stamp(rest, 0x1234);
+        assert_eq!(&frames[..12], &[0, 1, 0x12, 0x34, 0, 6, 1, 3, 0, 0, 0, 1]);
+        assert_eq!(&frames[12..16], &[0, 1, 0x12, 0x34]);
+        assert_eq!(&frames[16..], &REQUEST[4..]);
+    }
+
+    #[tokio::test]
+    async fn test_protocol_id_rewritten_both_ways() {
+        let (client, mut device) = tokio::io::duplex(64);
+        let mut client = ProtocolId::new(client, 7);
+
+        client.write_all(&REQUEST).await.unwrap();
+        let mut request = [0u8; 12];
+        device.read_exact(&mut request).await.unwrap();
+        assert_eq!(&request[2..4], &[0, 7]);
+
+        // A nonstandard gateway echoes its protocol id; the client only accepts 0
+        device
+            .write_all(&[0, 1, 0, 7, 0, 5, 1, 3, 2, 0, 42])
+            .await
+            .unwrap();
+        let mut response = [0u8; 11];
+        client.read_exact(&mut response).await.unwrap();
+        assert_eq!(response, [0, 1, 0, 0, 0, 5, 1, 3, 2, 0, 42]);
+    }
+
+    #[tokio::test]
+    async fn test_zero_protocol_id_passes_through() {
+        let (client, mut device) = tokio::io::duplex(64);
+        let mut client = ProtocolId::new(client, 0);
+        device.write_all(&[0, 1, 0, 9]).await.unwrap();
+        let mut response = [0u8; 4];
+        client.read_exact(&mut response).await.unwrap();
+        assert_eq!(response, [0, 1, 0, 9]);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::cli::{Area, Common};
+use crate::client::{connect_to_modbus, read_coil_spans, read_register_spans, OpSettings};
+use crate::table::{print_coil_table, print_register_table, TableOptions};
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::io::Write;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+const HELP: &str = "Commands:
+  read <area> <addr> [qty]   Read from coil, discrete, holding or input
+  next [area] [qty]          Continue after the last read of an area (default: last area and qty)
+  help                       Show this help
+  quit                       Leave the shell";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ReplCommand {
+    Read {
+        area: Area,
+        addr: u16,
+        qty: Option<u16>,
+    },
+    Next {
+        area: Option<Area>,
+        qty: Option<u16>,
+    },
+    Help,
+    Quit,
+}
+
+fn parse_command(line: &str) -> Result<ReplCommand, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let area = |s: &str| Area::from_str(s, true).map_err(|_| format!("Unknown area '{s}'"));
+    let number = |s: &str| {
+        s.parse::<u16>()
+            .map_err(|_| format!("Invalid number '{s}'"))
+    };
+
+    match words.as_slice() {
+        ["read", a, addr] => Ok(ReplCommand::Read {
+            area: area(a)?,
+            addr: number(addr)?,
+            qty: None,
+        }),
+        ["read", a, addr, qty] => Ok(ReplCommand::Read {
+            area: area(a)?,
+            addr: number(addr)?,
+            qty: Some(number(qty)?),
+        }),
+        ["next"] => Ok(ReplCommand::Next {
+            area: None,
+            qty: None,
+        }),
+        ["next", a] => Ok(ReplCommand::Next {
+            area: Some(area(a)?),
+            qty: None,
+        }),
+        ["next", a, qty] => Ok(ReplCommand::Next {
+            area: Some(area(a)?),
+            qty: Some(number(qty)?),
+        }),
+        ["help"] => Ok(ReplCommand::Help),
+        ["quit"] | ["exit"] => Ok(ReplCommand::Quit),
+        _ => Err("Unrecognized command; type 'help' for the list".to_string()),
+    }
+}
+
+/// Where each area's last read ended, so `next` can continue from it
+#[derive(Debug, Default)]
+struct ReplState {
+    last: HashMap<Area, (u16, u16)>,
+    last_area: Option<Area>,
+}
+
+impl ReplState {
+    // Resolve a `next` into an absolute (area, addr, qty)
+    fn resolve_next(
+        &self,
+        area: Option<Area>,
+        qty: Option<u16>,
+    ) -> Result<(Area, u16, u16), String> {
+        let area = area
+            .or(self.last_area)
+            .ok_or("Nothing read yet; use 'read' first")?;
+        let &(addr, last_qty) = self
+            .last
+            .get(&area)
+            .ok_or_else(|| format!("No previous {} read", area.label().to_lowercase()))?;
+        let next = addr
+            .checked_add(last_qty)
+            .ok_or("The last read ended at the top of the address space")?;
+        Ok((area, next, qty.unwrap_or(last_qty)))
+    }
+
+    fn record(&mut self, area: Area, addr: u16, qty: u16) {
+        self.last.insert(area, (addr, qty));
+        self.last_area = Some(area);
+    }
+}
+
+async fn read_and_print(
+    client: &mut tokio_modbus::client::Context,
+    area: Area,
+    addr: u16,
+    qty: u16,
+    common: &Common,
+) -> anyhow::Result<()> {
+    if qty == 0 || qty > area.max_read_qty() {
+        return Err(anyhow::anyhow!(
+            "Quantity must be 1-{} for {}",
+            area.max_read_qty(),
+            area.label().to_lowercase()
+        ));
+    }
+
+    let options = TableOptions {
+        verbose: common.verbose,
+        ..Default::default()
+    };
+    println!("{} {addr}-{}", area.label(), addr as u32 + qty as u32 - 1);
+    match area {
+        Area::Coil | Area::Discrete => {
+            let rows =
+                read_coil_spans(client, area, &[(addr, qty)], OpSettings::new(common)).await?;
+            let values: Vec<bool> = rows.into_iter().map(|(_, value)| value).collect();
+            print_coil_table(&values, addr, &options);
+        }
+        Area::Holding | Area::Input => {
+            let rows =
+                read_register_spans(client, area, &[(addr, qty)], OpSettings::new(common)).await?;
+            let values: Vec<u16> = rows.into_iter().map(|(_, value)| value).collect();
+            print_register_table(&values, addr, &options);
+        }
+    }
+    Ok(())
+}
+
+// Interactive shell over one persistent connection
+pub async fn run_repl(common: &Common) -> anyhow::Result<()> {
+    let mut client = connect_to_modbus(common).await?;
+    let mut state = ReplState::default();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    println!(
+        "Connected to {} unit {}. Type 'help' for commands.",
+        common.target(),
+        common.unit
+    );
+
+    loop {
+        print!("mb> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next_line().await? else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (area, addr, qty) = match parse_command(&line) {
+            Ok(ReplCommand::Read { area, addr, qty }) => (area, addr, qty.unwrap_or(1)),
+            Ok(ReplCommand::Next { area, qty }) => match state.resolve_next(area, qty) {
+                Ok(resolved) => resolved,
+                Err(e) => {
+                    eprintln!("{e}");
+                    continue;
+                }
+            },
+            Ok(ReplCommand::Help) => {
+                println!("{HELP}");
+                continue;
+            }
+            Ok(ReplCommand::Quit) => break,
+            Err(e) => {
+                eprintln!("{e}");
+                continue;
+            }
+        };
+
+        // Errors are reported and the session carries on
+        match read_and_print(&mut client, area, addr, qty, common).await {
+            Ok(()) => state.record(area, addr, qty),
+            Err(e) => eprintln!("Error: {e}"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            parse_command("read holding 100 10"),
+            Ok(ReplCommand::Read {
+                area: Area::Holding,
+                addr: 100,
+                qty: Some(10)
+            })
+        );
+        assert_eq!(
+            parse_command("  next  coil "),
+            Ok(ReplCommand::Next {
+                area: Some(Area::Coil),
+                qty: None
+            })
+        );
+        assert_eq!(parse_command("exit"), Ok(ReplCommand::Quit));
+        assert!(parse_command("read holdings 1").is_err());
+        assert!(parse_command("read holding x").is_err());
+        assert!(parse_command("frobnicate").is_err());
+    }
+
+    #[test]
+    fn test_resolve_next_continues_per_area() {
+        let mut state = ReplState::default();
+        assert!(state.resolve_next(None, None).is_err());
+
+        state.record(Area::Holding, 100, 10);
+        state.record(Area::Coil, 0, 16);
+        assert_eq!(state.resolve_next(None, None), Ok((Area::Coil, 16, 16)));
+        assert_eq!(
+            state.resolve_next(Some(Area::Holding), Some(5)),
+            Ok((Area::Holding, 110, 5))
+        );
+        assert!(state.resolve_next(Some(Area::Input), None).is_err());
+
+        state.record(Area::Input, 65530, 6);
+        assert!(state.resolve_next(Some(Area::Input), None).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{sleep, Sleep};
+use tokio_serial::{SerialPort, SerialStream};
+
+#[derive(Debug)]
+enum RtsState {
+    /// RTS released, receiving
+    Idle,
+    /// RTS asserted, waiting out the delay before the first byte goes out
+    Asserting(Pin<Box<Sleep>>),
+    /// Frame bytes are being written
+    Sending,
+    /// Frame flushed, waiting out the delay before RTS is released
+    Releasing(Pin<Box<Sleep>>),
+}
+
+/// Serial port that raises RTS around every outgoing frame, for RS-485 converters that use
+/// RTS as their transmit enable.
+///
+/// RTS is asserted `delay` before the first byte of a frame and released `delay` after the
+/// frame is flushed, so the converter has switched direction before and after the line is driven.
+#[derive(Debug)]
+pub struct RtsToggle {
+    inner: SerialStream,
+    delay: Duration,
+    state: RtsState,
+}
+
+impl RtsToggle {
+    pub fn new(inner: SerialStream, delay: Duration) -> Self {
+        Self {
+            inner,
+            delay,
+            state: RtsState::Idle,
+        }
+    }
+
+    fn set_rts(&mut self, level: bool) -> io::Result<()> {
+        self.inner
+            .write_request_to_send(level)
+            .map_err(io::Error::from)
+    }
+
+    // Finish releasing RTS from the previous frame; Ready once the port is back to receiving
+    fn poll_release(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let RtsState::Releasing(delay) = &mut self.state {
+            ready!(delay.as_mut().poll(cx));
+            self.set_rts(false)?;
+            self.state = RtsState::Idle;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for RtsToggle {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for RtsToggle {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_release(cx))?;
+        loop {
+            match &mut this.state {
+                RtsState::Idle => {
+                    this.set_rts(true)?;
+                    this.state = RtsState::Asserting(Box::pin(sleep(this.delay)));
+                }
+                RtsState::Asserting(delay) => {
+                    ready!(delay.as_mut().poll(cx));
+                    this.state = RtsState::Sending;
+                }
+                RtsState::Sending => return Pin::new(&mut this.inner).poll_write(cx, buf),
+                RtsState::Releasing(_) => unreachable!("released above"),
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(Pin::new(&mut this.inner).poll_flush(cx))?;
+        if matches!(this.state, RtsState::Sending) {
+            this.state = RtsState::Releasing(Box::pin(sleep(this.delay)));
+        }
+        this.poll_release(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,242 @@
+use crate::cli::{Area, Common};
+use crate::client::{
+    connect_to_modbus, modbus_operation_with_timeout, read_coil_spans, read_register_spans,
+    OpSettings,
+};
+use crate::server::{serve_tcp, ModbusData, ServerOptions};
+use clap::{Args, FromArgMatches};
+use std::fmt;
+use std::sync::Arc;
+use tokio_modbus::client::Writer;
+
+// Size of every area on the in-process server
+const AREA_SIZE: u16 = 100;
+
+/// Outcome of one step of the self-test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub failure: Option<String>,
+}
+
+impl Check {
+    fn new(name: &'static str, result: anyhow::Result<()>) -> Self {
+        Self {
+            name,
+            failure: result.err().map(|e| format!("{e:#}")),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.failure {
+            None => write!(f, "PASS  {}", self.name),
+            Some(failure) => write!(f, "FAIL  {}: {failure}", self.name),
+        }
+    }
+}
+
+/// The closing line of the report, e.g. `7/8 checks passed`
+pub fn summary(checks: &[Check]) -> String {
+    let passed = checks.iter().filter(|check| check.passed()).count();
+    format!("{passed}/{} checks passed", checks.len())
+}
+
+// Compare the values of a read against what the server should hold
+fn expect<T: PartialEq + fmt::Debug + Copy>(
+    rows: &[(u16, T)],
+    expected: &[T],
+) -> anyhow::Result<()> {
+    let actual: Vec<T> = rows.iter().map(|&(_, value)| value).collect();
+    if actual != expected {
+        anyhow::bail!("expected {expected:?}, got {actual:?}");
+    }
+    Ok(())
+}
+
+// Client flags pointing at the in-process server, parsed the way the real commands parse theirs
+fn loopback_common(port: u16, timeout_secs: u64, verbose: bool) -> anyhow::Result<Common> {
+    let port = port.to_string();
+    let timeout = timeout_secs.to_string();
+    let mut args = vec![
+        "selftest",
+        "--ip",
+        "127.0.0.1",
+        "--port",
+        &port,
+        "--timeout",
+        &timeout,
+    ];
+    if verbose {
+        args.push("--verbose");
+    }
+    let matches =
+        Common::augment_args(clap::Command::new("selftest")).try_get_matches_from(args)?;
+    Ok(Common::from_arg_matches(&matches)?)
+}
+
+/// Start the built-in server on an ephemeral loopback port and exercise every area through the
+/// client, returning one result per check. Errors only when the server itself cannot start.
+pub async fn run_selftest(timeout_secs: u64, verbose: bool) -> anyhow::Result<Vec<Check>> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let common = loopback_common(listener.local_addr()?.port(), timeout_secs, verbose)?;
+    let data = ModbusData::new(AREA_SIZE, AREA_SIZE, AREA_SIZE, AREA_SIZE);
+    let options = ServerOptions {
+        quiet: true,
+        ..Default::default()
+    };
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(serve_tcp(
+        listener,
+        Arc::new(tokio::sync::RwLock::new(data)),
+        options,
+        Box::pin(async move {
+            stopped.await.ok();
+        }),
+    ));
+
+    let mut checks = Vec::new();
+    match connect_to_modbus(&common).await {
+        Ok(mut ctx) => {
+            checks.push(Check::new("connect", Ok(())));
+            run_checks(&mut ctx, OpSettings::new(&common), &mut checks).await;
+        }
+        Err(e) => checks.push(Check::new("connect", Err(e))),
+    }
+
+    stop.send(()).ok();
+    server.await??;
+    Ok(checks)
+}
+
+async fn run_checks(
+    ctx: &mut tokio_modbus::client::Context,
+    op: OpSettings,
+    checks: &mut Vec<Check>,
+) {
+    // A fresh server holds each input register's address and every discrete input off
+    let result = async {
+        let rows = read_register_spans(ctx, Area::Input, &[(0, 10)], op).await?;
+        expect(&rows, &(0..10).collect::<Vec<u16>>())
+    }
+    .await;
+    checks.push(Check::new("read input registers", result));
+
+    let result = async {
+        let rows = read_coil_spans(ctx, Area::Discrete, &[(0, 8)], op).await?;
+        expect(&rows, &[false; 8])
+    }
+    .await;
+    checks.push(Check::new("read discrete inputs", result));
+
+    let result = async {
+        modbus_operation_with_timeout(
+            async || ctx.write_single_register(20, 0xBEEF).await,
+            "Write single register",
+            op,
+        )
+        .await?;
+        let rows = read_register_spans(ctx, Area::Holding, &[(20, 1)], op).await?;
+        expect(&rows, &[0xBEEF])
+    }
+    .await;
+    checks.push(Check::new(
+        "write and read back one holding register",
+        result,
+    ));
+
+    let result = async {
+        let values = [0x1234, 0xABCD, 0x0000, 0xFFFF];
+        modbus_operation_with_timeout(
+            async || ctx.write_multiple_registers(30, &values).await,
+            "Write multiple registers",
+            op,
+        )
+        .await?;
+        let rows = read_register_spans(ctx, Area::Holding, &[(30, 4)], op).await?;
+        expect(&rows, &values)
+    }
+    .await;
+    checks.push(Check::new("write and read back holding registers", result));
+
+    let result = async {
+        modbus_operation_with_timeout(
+            async || ctx.write_single_coil(7, true).await,
+            "Write single coil",
+            op,
+        )
+        .await?;
+        let rows = read_coil_spans(ctx, Area::Coil, &[(7, 1)], op).await?;
+        expect(&rows, &[true])
+    }
+    .await;
+    checks.push(Check::new("write and read back one coil", result));
+
+    let result = async {
+        let values = [true, false, true, true, false, false, true, false, true];
+        modbus_operation_with_timeout(
+            async || ctx.write_multiple_coils(10, &values).await,
+            "Write multiple coils",
+            op,
+        )
+        .await?;
+        let rows = read_coil_spans(ctx, Area::Coil, &[(10, 9)], op).await?;
+        expect(&rows, &values)
+    }
+    .await;
+    checks.push(Check::new("write and read back coils", result));
+
+    // The last register plus one past the end must come back as an exception, not data
+    let result = async {
+        match read_register_spans(ctx, Area::Holding, &[(AREA_SIZE - 1, 2)], op).await {
+            Ok(rows) => anyhow::bail!("expected an exception, got {} registers", rows.len()),
+            Err(_) => Ok(()),
+        }
+    }
+    .await;
+    checks.push(Check::new("reject a read past the end", result));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_display() {
+        assert_eq!(Check::new("connect", Ok(())).to_string(), "PASS  connect");
+        let failed = Check::new("connect", Err(anyhow::anyhow!("Connection refused")));
+        assert!(!failed.passed());
+        assert_eq!(failed.to_string(), "FAIL  connect: Connection refused");
+    }
+
+    #[test]
+    fn test_summary_counts_passes() {
+        let checks = [
+            Check::new("a", Ok(())),
+            Check::new("b", Err(anyhow::anyhow!("no"))),
+            Check::new("c", Ok(())),
+        ];
+        assert_eq!(summary(&checks), "2/3 checks passed");
+    }
+
+    #[test]
+    fn test_expect_compares_values_only() {
+        assert!(expect(&[(10, 1u16), (11, 2)], &[1, 2]).is_ok());
+        let err = expect(&[(10, true)], &[false]).unwrap_err();
+        assert_eq!(err.to_string(), "expected [false], got [true]");
+    }
+
+    #[test]
+    fn test_loopback_common_targets_port() {
+        let common = loopback_common(15020, 2, true).unwrap();
+        assert_eq!(common.target(), "127.0.0.1:15020");
+        assert_eq!(common.timeout, 2);
+        assert!(common.verbose);
+        assert_eq!(common.unit, 1);
+    }
+}
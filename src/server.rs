@@ -1,188 +1,843 @@
-use crate::cli::{DataBits, Parity, StopBits};
+use crate::cli::{BitPattern, DataBits, FlowControl, Parity, Pattern, StopBits, MBAP_DIRECT_UNIT};
+use crate::client::serial_open_error;
+use crate::diag::{DiagCounters, DIAGNOSTICS};
+use crate::events::EventStream;
+use crate::fifo::{response_data, MAX_FIFO_COUNT, READ_FIFO_QUEUE};
+use crate::metrics::ServerMetrics;
+use crate::webhook::{notify, read_event_json, write_event_json, WebhookUrl};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
 use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{Instant, Sleep};
 use tokio_modbus::prelude::*;
 use tokio_modbus::server::{rtu, tcp::Server, Service};
 
+/// How registers are filled before any client writes (`--pattern`, `--seed` and `--jitter`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Fill {
+    pub pattern: Pattern,
+    pub seed: u64,
+    /// Noise added to every value, in percent of full scale
+    pub jitter: u8,
+}
+
+impl Fill {
+    // Initial value of register `index` in an area of `len` registers
+    fn register(self, index: usize, len: usize) -> u16 {
+        let value = match self.pattern {
+            Pattern::Address => index as u16,
+            Pattern::Ramp if len > 1 => (index * u16::MAX as usize / (len - 1)) as u16,
+            Pattern::Ramp => 0,
+            Pattern::Alternating => [0, u16::MAX][index % 2],
+            Pattern::Random => splitmix64(self.seed.wrapping_add(index as u64)) as u16,
+        };
+        self.jittered(value, index)
+    }
+
+    // Move a value by up to the jitter either way, clamped to the register's range. The noise
+    // comes from the seed like `random`, so a sparse area and a reset see the same values
+    fn jittered(self, value: u16, index: usize) -> u16 {
+        let amplitude = u16::MAX as i64 * self.jitter as i64 / 100;
+        if amplitude == 0 {
+            return value;
+        }
+        let noise = splitmix64(!self.seed.wrapping_add(index as u64)) % (2 * amplitude as u64 + 1);
+        (value as i64 + noise as i64 - amplitude).clamp(0, u16::MAX as i64) as u16
+    }
+}
+
+// One step of the SplitMix64 generator; a pure function of its input, so a sparse area can
+// recompute any address without storing it
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Value a never-written address holds, per the fill of its area
+pub trait InitialValue: Copy + Default {
+    type Fill: Copy + Default + std::fmt::Debug + PartialEq;
+
+    fn initial(index: usize, len: usize, fill: Self::Fill) -> Self;
+}
+
+impl InitialValue for bool {
+    type Fill = BitPattern;
+
+    fn initial(index: usize, _len: usize, fill: BitPattern) -> Self {
+        match fill {
+            BitPattern::AllOff => false,
+            BitPattern::AllOn => true,
+            BitPattern::Alternating => index.is_multiple_of(2),
+        }
+    }
+}
+
+impl InitialValue for u16 {
+    type Fill = Fill;
+
+    fn initial(index: usize, len: usize, fill: Fill) -> Self {
+        fill.register(index, len)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Backing<T> {
+    /// Every value, allocated up front
+    Dense(Vec<T>),
+    /// Only the values clients have written
+    Sparse(HashMap<u16, T>),
+}
+
+/// Storage for one area: allocated up front, or only where a client has written
+#[derive(Debug, Clone, PartialEq)]
+pub struct Store<T: InitialValue> {
+    len: usize,
+    fill: T::Fill,
+    backing: Backing<T>,
+}
+
+impl<T: InitialValue> Store<T> {
+    pub fn dense(len: u16) -> Self {
+        Self::dense_with(len as usize, T::Fill::default())
+    }
+
+    fn dense_with(len: usize, fill: T::Fill) -> Self {
+        Self {
+            len,
+            fill,
+            backing: Backing::Dense((0..len).map(|i| T::initial(i, len, fill)).collect()),
+        }
+    }
+
+    pub fn sparse(len: u16) -> Self {
+        Self {
+            len: len as usize,
+            fill: T::Fill::default(),
+            backing: Backing::Sparse(HashMap::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Value at `index`, which must be below `len`
+    pub fn get(&self, index: usize) -> T {
+        match &self.backing {
+            Backing::Dense(values) => values[index],
+            Backing::Sparse(written) => written
+                .get(&(index as u16))
+                .copied()
+                .unwrap_or_else(|| T::initial(index, self.len, self.fill)),
+        }
+    }
+
+    /// Store `value` at `index`, which must be below `len`
+    pub fn set(&mut self, index: usize, value: T) {
+        match &mut self.backing {
+            Backing::Dense(values) => values[index] = value,
+            Backing::Sparse(written) => {
+                written.insert(index as u16, value);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub fn to_vec(&self) -> Vec<T> {
+        (0..self.len).map(|index| self.get(index)).collect()
+    }
+
+    /// Refill with `fill`, dropping every write
+    pub fn refill(&mut self, fill: T::Fill) {
+        self.fill = fill;
+        self.reset();
+    }
+
+    /// Forget every write, keeping the size, fill and storage mode
+    pub fn reset(&mut self) {
+        match &mut self.backing {
+            Backing::Dense(_) => *self = Self::dense_with(self.len, self.fill),
+            Backing::Sparse(written) => written.clear(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ModbusData {
-    pub coils: Vec<bool>,
-    pub discrete_inputs: Vec<bool>,
-    pub holding_registers: Vec<u16>,
-    pub input_registers: Vec<u16>,
+    pub coils: Store<bool>,
+    pub discrete_inputs: Store<bool>,
+    pub holding_registers: Store<u16>,
+    pub input_registers: Store<u16>,
+    /// FIFO queues read with FC 24, keyed by pointer address; empty until values are pushed
+    pub fifo_queues: HashMap<u16, VecDeque<u16>>,
 }
 
 impl ModbusData {
     pub fn new(num_coils: u16, num_discrete: u16, num_holding: u16, num_input: u16) -> Self {
         Self {
-            coils: vec![false; num_coils as usize],
-            discrete_inputs: vec![false; num_discrete as usize],
-            holding_registers: (0..num_holding).collect(),
-            input_registers: (0..num_input).collect(),
+            coils: Store::dense(num_coils),
+            discrete_inputs: Store::dense(num_discrete),
+            holding_registers: Store::dense(num_holding),
+            input_registers: Store::dense(num_input),
+            fifo_queues: HashMap::new(),
+        }
+    }
+
+    /// Like `new`, but only written addresses take memory, so full 65535-address areas are cheap
+    pub fn sparse(num_coils: u16, num_discrete: u16, num_holding: u16, num_input: u16) -> Self {
+        Self {
+            coils: Store::sparse(num_coils),
+            discrete_inputs: Store::sparse(num_discrete),
+            holding_registers: Store::sparse(num_holding),
+            input_registers: Store::sparse(num_input),
+            fifo_queues: HashMap::new(),
+        }
+    }
+
+    /// Start the holding registers from `fill` instead of their addresses
+    pub fn with_holding_fill(mut self, fill: Fill) -> Self {
+        self.holding_registers.refill(fill);
+        self
+    }
+
+    /// Start the coils from `pattern` instead of all off
+    pub fn with_coil_fill(mut self, pattern: BitPattern) -> Self {
+        self.coils.refill(pattern);
+        self
+    }
+
+    /// Start the discrete inputs from `pattern` instead of all off
+    pub fn with_discrete_fill(mut self, pattern: BitPattern) -> Self {
+        self.discrete_inputs.refill(pattern);
+        self
+    }
+
+    /// Restore every area to the values it starts with, keeping the current sizes
+    pub fn reset(&mut self) {
+        self.coils.reset();
+        self.discrete_inputs.reset();
+        self.holding_registers.reset();
+        self.input_registers.reset();
+        self.fifo_queues.clear();
+    }
+
+    /// Append a value to the FIFO queue at `pointer`, dropping the oldest once it is full
+    pub fn push_fifo(&mut self, pointer: u16, value: u16) {
+        let queue = self.fifo_queues.entry(pointer).or_default();
+        if queue.len() == MAX_FIFO_COUNT {
+            queue.pop_front();
         }
+        queue.push_back(value);
+    }
+
+    /// Advance the heartbeat register at `index` by one, wrapping at 65535
+    pub fn beat(&mut self, index: usize) {
+        let value = self.holding_registers.get(index);
+        self.holding_registers.set(index, value.wrapping_add(1));
+    }
+}
+
+/// Advance the holding register at `index` once a second for as long as the server runs, as a
+/// liveness signal for clients
+pub fn spawn_heartbeat(data: Arc<tokio::sync::RwLock<ModbusData>>, index: usize) {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(Duration::from_secs(1));
+        // The first tick completes at once; the register starts counting a second in
+        ticks.tick().await;
+        loop {
+            ticks.tick().await;
+            data.write().await.beat(index);
+        }
+    });
+}
+
+// Reset the data to its initial values whenever the process receives SIGHUP
+#[cfg(unix)]
+fn spawn_reset_on_sighup(data: Arc<tokio::sync::RwLock<ModbusData>>) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            data.write().await.reset();
+            info!("Received SIGHUP: data reset to initial values");
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn spawn_reset_on_sighup(_data: Arc<tokio::sync::RwLock<ModbusData>>) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// How long the simulated device takes to apply a write, per writable area
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteDelay {
+    pub coils: Option<Duration>,
+    pub holding: Option<Duration>,
+}
+
+impl WriteDelay {
+    // Delay for a request; reads and other functions are never delayed
+    fn for_request(&self, request: &Request<'_>) -> Option<Duration> {
+        match request {
+            Request::WriteSingleCoil(..) | Request::WriteMultipleCoils(..) => self.coils,
+            Request::WriteSingleRegister(..) | Request::WriteMultipleRegisters(..) => self.holding,
+            _ => None,
+        }
+    }
+}
+
+// Value parser for `--write-delay`: milliseconds for every write (`200`), or per area
+// (`coil=50,holding=200`)
+pub fn parse_write_delay(s: &str) -> Result<WriteDelay, String> {
+    let millis = |text: &str| {
+        text.trim()
+            .parse::<u64>()
+            .map(Duration::from_millis)
+            .map_err(|_| format!("Invalid delay '{text}': expected milliseconds (e.g. 200)"))
+    };
+    if !s.contains('=') {
+        let delay = millis(s)?;
+        return Ok(WriteDelay {
+            coils: Some(delay),
+            holding: Some(delay),
+        });
+    }
+
+    let mut delay = WriteDelay::default();
+    for part in s.split(',') {
+        let (area, ms) = part
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid write delay '{part}': expected area=ms"))?;
+        match area.trim() {
+            "coil" | "coils" => delay.coils = Some(millis(ms)?),
+            "holding" => delay.holding = Some(millis(ms)?),
+            other => {
+                return Err(format!(
+                    "Unknown area '{other}' in --write-delay: only coil and holding are writable"
+                ))
+            }
+        }
+    }
+    Ok(delay)
+}
+
+/// Behavior switches for the simulated device
+#[derive(Debug, Clone, Default)]
+pub struct ServerOptions {
+    /// Zero-pad reads that run past the end of an area instead of rejecting them
+    pub clamp_reads: bool,
+    /// Protocol address of the first element in every area; lower addresses are rejected
+    pub base_addr: u16,
+    /// Request and connection counters, exposed by `--prometheus-port`
+    pub metrics: Arc<ServerMetrics>,
+    /// Endpoint notified of every successful write
+    pub webhook: Option<WebhookUrl>,
+    /// Subscribers to every successful read and write, served by `--events-port`
+    pub events: Option<EventStream>,
+    /// Close TCP connections that send nothing for this long
+    pub idle_timeout: Option<Duration>,
+    /// Refuse TCP connections beyond this many open at once
+    pub max_connections: Option<usize>,
+    /// Wait this long before applying and acknowledging a write
+    pub write_delay: WriteDelay,
+    /// Holding registers whose written values are also queued for FC 24 reads
+    pub fifo_pointers: Vec<u16>,
+    /// Requests still to be answered with `ServerDeviceBusy`, shared by every connection, like
+    /// a device that is still starting up
+    pub busy_requests: Arc<AtomicUsize>,
+    /// Counters reported by Diagnostics (FC 8), shared by every connection
+    pub diagnostics: Arc<DiagCounters>,
+    /// Log nothing about connections, for a server run inside another command
+    pub quiet: bool,
+    /// Unit id the server answers as (`--unit`); requests for other units are ignored like on a
+    /// serial line. `None` answers every unit
+    pub unit: Option<u8>,
+}
+
+// Publish a read to the event stream, if one is being served
+fn announce_read(events: &Option<EventStream>, json: impl FnOnce() -> String) {
+    if let Some(events) = events {
+        events.publish("read", json());
+    }
+}
+
+// Hand a write to the webhook and the event stream, whichever are enabled
+fn announce_write(
+    webhook: &Option<WebhookUrl>,
+    events: &Option<EventStream>,
+    json: impl FnOnce() -> String,
+) {
+    if webhook.is_none() && events.is_none() {
+        return;
+    }
+    let json = json();
+    if let Some(events) = events {
+        events.publish("write", json.clone());
+    }
+    if let Some(url) = webhook {
+        notify(url, json);
+    }
+}
+
+// Translate a protocol address into an index relative to the configured base address
+fn offset(addr: u16, base: u16) -> Result<u16, ExceptionCode> {
+    addr.checked_sub(base)
+        .ok_or(ExceptionCode::IllegalDataAddress)
+}
+
+// Copy `qty` values starting at `addr`, zero-padding past the end when clamping
+fn read_range<T: InitialValue>(
+    values: &Store<T>,
+    addr: u16,
+    qty: u16,
+    clamp: bool,
+) -> Result<Vec<T>, ExceptionCode> {
+    let start = addr as usize;
+    let end = start + qty as usize;
+    if end <= values.len() || (clamp && start < values.len()) {
+        Ok((start..end)
+            .map(|index| {
+                if index < values.len() {
+                    values.get(index)
+                } else {
+                    T::default()
+                }
+            })
+            .collect())
+    } else {
+        Err(ExceptionCode::IllegalDataAddress)
     }
 }
 
 #[derive(Clone)]
 pub struct ModbusService {
     data: Arc<tokio::sync::RwLock<ModbusData>>,
+    options: ServerOptions,
 }
 
 impl ModbusService {
-    pub fn new(data: Arc<tokio::sync::RwLock<ModbusData>>) -> Self {
-        Self { data }
+    pub fn new(data: Arc<tokio::sync::RwLock<ModbusData>>, options: ServerOptions) -> Self {
+        Self { data, options }
     }
-}
 
-impl Service for ModbusService {
-    type Request = Request<'static>;
-    type Response = Response;
-    type Exception = ExceptionCode;
-    type Future = std::pin::Pin<
-        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Exception>> + Send>,
-    >;
+    // Whether a request for `unit` is addressed to this server: its own unit, a broadcast, or
+    // 255, which TCP clients send to address a device directly
+    fn answers_unit(&self, unit: u8) -> bool {
+        self.options
+            .unit
+            .is_none_or(|own| [own, BROADCAST_UNIT, MBAP_DIRECT_UNIT].contains(&unit))
+    }
 
-    fn call(&self, req: Self::Request) -> Self::Future {
+    // Answer a request. Only with `take_busy_slot` can it be one of the `--fail-first` requests
+    // answered Busy: a broadcast passes false, since it gets no reply and the write would just
+    // be lost
+    fn respond(
+        &self,
+        req: SlaveRequest<'static>,
+        take_busy_slot: bool,
+    ) -> <Self as Service>::Future {
         let data = self.data.clone();
-        Box::pin(async move {
+        let clamp = self.options.clamp_reads;
+        let base = self.options.base_addr;
+        let webhook = self.options.webhook.clone();
+        let events = self.options.events.clone();
+        let fifo_pointers = self.options.fifo_pointers.clone();
+        let SlaveRequest {
+            slave: unit,
+            request,
+        } = req;
+        trace!("Request for unit {unit}: {request:?}");
+        self.options.metrics.record_request(&request);
+        let diagnostics = self.options.diagnostics.clone();
+        diagnostics.message_received();
+        let cold_start = take_busy_slot.then(|| {
+            self.options
+                .busy_requests
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                    left.checked_sub(1)
+                })
+        });
+        if let Some(Ok(left)) = cold_start {
+            debug!(
+                "Answered {request:?} with ServerDeviceBusy ({} more to fail)",
+                left - 1
+            );
+            diagnostics.exception_sent(ExceptionCode::ServerDeviceBusy);
+            return Box::pin(async { Err(ExceptionCode::ServerDeviceBusy) });
+        }
+        let write_delay = self.options.write_delay.for_request(&request);
+        let counters = diagnostics.clone();
+        let response = async move {
+            // Like a device that takes time to apply a setpoint: readers keep seeing the old
+            // value until the delay is over, so the lock is only taken afterwards
+            if let Some(delay) = write_delay {
+                tokio::time::sleep(delay).await;
+            }
             let mut data = data.write().await;
 
-            let response = match req {
+            let response = match request {
                 Request::ReadCoils(addr, qty) => {
                     // Note: We don't have access to client IP in the service layer
-                    println!("Read {qty} coil(s) starting at {addr}");
-                    let start = addr as usize;
-                    let end = start + qty as usize;
-                    if end <= data.coils.len() {
-                        let coils = data.coils[start..end].to_vec();
-                        Response::ReadCoils(coils)
-                    } else {
-                        return Err(ExceptionCode::IllegalDataAddress);
-                    }
+                    debug!("Read {qty} coil(s) starting at {addr}");
+                    let values = read_range(&data.coils, offset(addr, base)?, qty, clamp)?;
+                    announce_read(&events, || read_event_json(unit, "coils", addr, qty));
+                    Response::ReadCoils(values)
                 }
                 Request::ReadDiscreteInputs(addr, qty) => {
-                    println!("Read {qty} discrete input(s) starting at {addr}");
-                    let start = addr as usize;
-                    let end = start + qty as usize;
-                    if end <= data.discrete_inputs.len() {
-                        let inputs = data.discrete_inputs[start..end].to_vec();
-                        Response::ReadDiscreteInputs(inputs)
-                    } else {
-                        return Err(ExceptionCode::IllegalDataAddress);
-                    }
+                    debug!("Read {qty} discrete input(s) starting at {addr}");
+                    let values =
+                        read_range(&data.discrete_inputs, offset(addr, base)?, qty, clamp)?;
+                    announce_read(&events, || read_event_json(unit, "discrete", addr, qty));
+                    Response::ReadDiscreteInputs(values)
                 }
                 Request::ReadHoldingRegisters(addr, qty) => {
-                    println!("Read {qty} holding register(s) starting at {addr}");
-                    let start = addr as usize;
-                    let end = start + qty as usize;
-                    if end <= data.holding_registers.len() {
-                        let registers = data.holding_registers[start..end].to_vec();
-                        Response::ReadHoldingRegisters(registers)
-                    } else {
-                        return Err(ExceptionCode::IllegalDataAddress);
-                    }
+                    debug!("Read {qty} holding register(s) starting at {addr}");
+                    let values =
+                        read_range(&data.holding_registers, offset(addr, base)?, qty, clamp)?;
+                    announce_read(&events, || read_event_json(unit, "holding", addr, qty));
+                    Response::ReadHoldingRegisters(values)
                 }
                 Request::ReadInputRegisters(addr, qty) => {
-                    println!("Read {qty} input register(s) starting at {addr}");
-                    let start = addr as usize;
-                    let end = start + qty as usize;
-                    if end <= data.input_registers.len() {
-                        let registers = data.input_registers[start..end].to_vec();
-                        Response::ReadInputRegisters(registers)
-                    } else {
-                        return Err(ExceptionCode::IllegalDataAddress);
-                    }
+                    debug!("Read {qty} input register(s) starting at {addr}");
+                    let values =
+                        read_range(&data.input_registers, offset(addr, base)?, qty, clamp)?;
+                    announce_read(&events, || read_event_json(unit, "input", addr, qty));
+                    Response::ReadInputRegisters(values)
                 }
                 Request::WriteSingleCoil(addr, value) => {
-                    let addr = addr as usize;
-                    if addr < data.coils.len() {
-                        println!("Write coil {addr}: {value}");
-                        data.coils[addr] = value;
-                        Response::WriteSingleCoil(addr as u16, value)
+                    let index = offset(addr, base)? as usize;
+                    if index < data.coils.len() {
+                        debug!("Write coil {addr}: {value}");
+                        data.coils.set(index, value);
+                        announce_write(&webhook, &events, || {
+                            write_event_json(unit, "coils", addr, &[value])
+                        });
+                        Response::WriteSingleCoil(addr, value)
                     } else {
                         return Err(ExceptionCode::IllegalDataAddress);
                     }
                 }
                 Request::WriteSingleRegister(addr, value) => {
-                    let addr = addr as usize;
-                    if addr < data.holding_registers.len() {
-                        println!("Write register {addr}: {value}");
-                        data.holding_registers[addr] = value;
-                        Response::WriteSingleRegister(addr as u16, value)
+                    let index = offset(addr, base)? as usize;
+                    if index < data.holding_registers.len() {
+                        debug!("Write register {addr}: {value}");
+                        data.holding_registers.set(index, value);
+                        if fifo_pointers.contains(&addr) {
+                            data.push_fifo(addr, value);
+                        }
+                        announce_write(&webhook, &events, || {
+                            write_event_json(unit, "holding", addr, &[value])
+                        });
+                        Response::WriteSingleRegister(addr, value)
                     } else {
                         return Err(ExceptionCode::IllegalDataAddress);
                     }
                 }
                 Request::WriteMultipleCoils(addr, values) => {
-                    let start = addr as usize;
+                    let start = offset(addr, base)? as usize;
                     let end = start + values.len();
                     if end <= data.coils.len() {
-                        println!("Write {} coils starting at {addr}", values.len());
+                        debug!("Write {} coils starting at {addr}", values.len());
                         for (i, &value) in values.iter().enumerate() {
-                            data.coils[start + i] = value;
+                            data.coils.set(start + i, value);
                         }
+                        announce_write(&webhook, &events, || {
+                            write_event_json(unit, "coils", addr, &values)
+                        });
                         Response::WriteMultipleCoils(addr, values.len() as u16)
                     } else {
                         return Err(ExceptionCode::IllegalDataAddress);
                     }
                 }
                 Request::WriteMultipleRegisters(addr, values) => {
-                    let start = addr as usize;
+                    let start = offset(addr, base)? as usize;
                     let end = start + values.len();
                     if end <= data.holding_registers.len() {
-                        println!("Write {} registers starting at {addr}", values.len());
+                        debug!("Write {} registers starting at {addr}", values.len());
                         for (i, &value) in values.iter().enumerate() {
-                            data.holding_registers[start + i] = value;
+                            data.holding_registers.set(start + i, value);
+                            let addr = addr.wrapping_add(i as u16);
+                            if fifo_pointers.contains(&addr) {
+                                data.push_fifo(addr, value);
+                            }
                         }
+                        announce_write(&webhook, &events, || {
+                            write_event_json(unit, "holding", addr, &values)
+                        });
                         Response::WriteMultipleRegisters(addr, values.len() as u16)
                     } else {
                         return Err(ExceptionCode::IllegalDataAddress);
                     }
                 }
+                Request::MaskWriteRegister(addr, and_mask, or_mask) => {
+                    let index = offset(addr, base)? as usize;
+                    if index < data.holding_registers.len() {
+                        let current = data.holding_registers.get(index);
+                        let value = (current & and_mask) | (or_mask & !and_mask);
+                        debug!("Mask write register {addr}: {current} -> {value}");
+                        data.holding_registers.set(index, value);
+                        if fifo_pointers.contains(&addr) {
+                            data.push_fifo(addr, value);
+                        }
+                        announce_write(&webhook, &events, || {
+                            write_event_json(unit, "holding", addr, &[value])
+                        });
+                        Response::MaskWriteRegister(addr, and_mask, or_mask)
+                    } else {
+                        return Err(ExceptionCode::IllegalDataAddress);
+                    }
+                }
+                Request::Custom(READ_FIFO_QUEUE, request_data) => {
+                    let &[hi, lo] = request_data.as_ref() else {
+                        return Err(ExceptionCode::IllegalDataValue);
+                    };
+                    let pointer = u16::from_be_bytes([hi, lo]);
+                    // The pointer is a holding register address, so it must be one
+                    if offset(pointer, base)? as usize >= data.holding_registers.len() {
+                        return Err(ExceptionCode::IllegalDataAddress);
+                    }
+                    let values: Vec<u16> = data
+                        .fifo_queues
+                        .get(&pointer)
+                        .map(|queue| queue.iter().copied().collect())
+                        .unwrap_or_default();
+                    debug!("Read FIFO queue at {pointer}: {} value(s)", values.len());
+                    Response::Custom(READ_FIFO_QUEUE, response_data(&values).into())
+                }
+                Request::Custom(DIAGNOSTICS, request_data) => {
+                    debug!("Diagnostics request: {request_data:02X?}");
+                    Response::Custom(DIAGNOSTICS, counters.respond(&request_data)?.into())
+                }
                 _ => {
                     return Err(ExceptionCode::IllegalFunction);
                 }
             };
             Ok(response)
+        };
+        Box::pin(async move {
+            let result = response.await;
+            if let Err(code) = result {
+                diagnostics.exception_sent(code);
+            }
+            result
         })
     }
 }
 
+impl Service for ModbusService {
+    type Request = SlaveRequest<'static>;
+    type Response = Response;
+    type Exception = ExceptionCode;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Exception>> + Send>,
+    >;
+
+    fn call(&self, req: Self::Request) -> Self::Future {
+        self.respond(req, true)
+    }
+}
+
+// Unit id that addresses every device on the line at once
+const BROADCAST_UNIT: u8 = 0;
+
+/// The service the servers run: answers as `ModbusService` does, except that broadcasts
+/// (unit 0) get no response, as the specification requires. Broadcast writes are applied to
+/// the one set of data the server holds; broadcast reads are invalid and ignored. Requests for
+/// a unit other than the server's own get no response either, as from a serial slave that
+/// isn't being addressed
+#[derive(Clone)]
+pub struct BroadcastService(ModbusService);
+
+impl Service for BroadcastService {
+    type Request = SlaveRequest<'static>;
+    type Response = Option<Response>;
+    type Exception = ExceptionCode;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Exception>> + Send>,
+    >;
+
+    fn call(&self, req: Self::Request) -> Self::Future {
+        if !self.0.answers_unit(req.slave) {
+            debug!(
+                "Ignored {:?} for unit {}: not this server's unit",
+                req.request, req.slave
+            );
+            return Box::pin(async { Ok(None) });
+        }
+        if req.slave != BROADCAST_UNIT {
+            let response = self.0.call(req);
+            return Box::pin(async move { response.await.map(Some) });
+        }
+        let diagnostics = self.0.options.diagnostics.clone();
+        let is_write = matches!(
+            req.request,
+            Request::WriteSingleCoil(..)
+                | Request::WriteMultipleCoils(..)
+                | Request::WriteSingleRegister(..)
+                | Request::WriteMultipleRegisters(..)
+                | Request::MaskWriteRegister(..)
+        );
+        if !is_write {
+            debug!(
+                "Ignored broadcast {:?}: only writes can be broadcast",
+                req.request
+            );
+            diagnostics.message_received();
+            diagnostics.no_response_sent();
+            return Box::pin(async { Ok(None) });
+        }
+        let response = self.0.respond(req, false);
+        Box::pin(async move {
+            if let Err(code) = response.await {
+                debug!("Broadcast write failed with {code:?}; broadcasts are never answered");
+            }
+            diagnostics.no_response_sent();
+            Ok(None)
+        })
+    }
+}
+
+/// TCP stream that keeps the active connection gauge up to date and drops idle clients
+struct TrackedStream {
+    inner: TcpStream,
+    peer: SocketAddr,
+    metrics: Arc<ServerMetrics>,
+    idle_timeout: Option<Duration>,
+    idle_deadline: Option<Pin<Box<Sleep>>>,
+    quiet: bool,
+    // Slot held under `--max-connections`, freed when the connection closes
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl TrackedStream {
+    fn new(
+        inner: TcpStream,
+        peer: SocketAddr,
+        metrics: Arc<ServerMetrics>,
+        idle_timeout: Option<Duration>,
+    ) -> Self {
+        metrics.connection_opened();
+        Self {
+            inner,
+            peer,
+            metrics,
+            idle_timeout,
+            idle_deadline: idle_timeout.map(|timeout| Box::pin(tokio::time::sleep(timeout))),
+            quiet: false,
+            _permit: None,
+        }
+    }
+
+    fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    fn with_permit(mut self, permit: Option<OwnedSemaphorePermit>) -> Self {
+        self._permit = permit;
+        self
+    }
+}
+
+impl Drop for TrackedStream {
+    fn drop(&mut self) {
+        if !self.quiet {
+            info!("Client disconnected: {}", self.peer);
+        }
+        self.metrics.connection_closed();
+    }
+}
+
+impl AsyncRead for TrackedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = &mut *self;
+        match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Ready(result) => {
+                // Any traffic from the client restarts the idle clock
+                if let (Some(deadline), Some(timeout)) =
+                    (&mut this.idle_deadline, this.idle_timeout)
+                {
+                    deadline.as_mut().reset(Instant::now() + timeout);
+                }
+                Poll::Ready(result)
+            }
+            Poll::Pending => {
+                let idle = this
+                    .idle_deadline
+                    .as_mut()
+                    .is_some_and(|deadline| deadline.as_mut().poll(cx).is_ready());
+                if idle {
+                    Poll::Ready(Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "connection idle for too long",
+                    )))
+                } else {
+                    Poll::Pending
+                }
+            }
+        }
+    }
+}
+
+impl AsyncWrite for TrackedStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
 pub async fn run_tcp_server(
     ip_addr: IpAddr,
     port: u16,
     data: Arc<tokio::sync::RwLock<ModbusData>>,
+    options: ServerOptions,
 ) -> anyhow::Result<()> {
     let socket_addr = SocketAddr::new(ip_addr, port);
     let listener = tokio::net::TcpListener::bind(socket_addr).await?;
-    println!("Modbus TCP server listening on {ip_addr}:{port}");
+    // Report the bound address, which differs from the requested one for `--port 0`
+    println!("Modbus TCP server listening on {}", listener.local_addr()?);
     println!("Press Ctrl+C to stop the server");
 
-    let server = Server::new(listener);
-    let service = ModbusService::new(data);
-
-    let on_connected = move |stream, socket_addr| {
-        let service = service.clone();
-        async move {
-            println!("Client connected: {socket_addr}");
-            tokio_modbus::server::tcp::accept_tcp_connection(stream, socket_addr, |_| {
-                Ok(Some(service.clone()))
-            })
-        }
-    };
-
-    let on_process_error = |err| {
-        eprintln!("Server error: {err}");
-    };
+    spawn_reset_on_sighup(data.clone())?;
 
     let ctrl_c = Box::pin(async {
         tokio::signal::ctrl_c().await.ok();
     });
 
-    match server
-        .serve_until(&on_connected, on_process_error, ctrl_c)
-        .await?
-    {
+    match serve_tcp(listener, data, options, ctrl_c).await? {
         tokio_modbus::server::Terminated::Finished => {
             println!("\nServer finished");
         }
@@ -193,50 +848,133 @@ pub async fn run_tcp_server(
     Ok(())
 }
 
+/// Serve Modbus TCP on an already bound listener until `shutdown` completes
+pub async fn serve_tcp<X>(
+    listener: tokio::net::TcpListener,
+    data: Arc<tokio::sync::RwLock<ModbusData>>,
+    options: ServerOptions,
+    shutdown: X,
+) -> anyhow::Result<tokio_modbus::server::Terminated>
+where
+    X: Future<Output = ()> + Sync + Send + Unpin + 'static,
+{
+    let server = Server::new(listener);
+    let metrics = options.metrics.clone();
+    let idle_timeout = options.idle_timeout;
+    let limit = options.max_connections;
+    let quiet = options.quiet;
+    let slots = limit.map(|limit| Arc::new(Semaphore::new(limit)));
+    let service = BroadcastService(ModbusService::new(data, options));
+
+    let on_connected = move |stream, socket_addr| {
+        let service = service.clone();
+        let metrics = metrics.clone();
+        // Like a real device, refuse connections beyond its capacity rather than queue them
+        let permit = slots
+            .as_ref()
+            .map(|slots| slots.clone().try_acquire_owned());
+        async move {
+            let permit = match permit {
+                Some(Ok(permit)) => Some(permit),
+                Some(Err(_)) => {
+                    warn!(
+                        "Connection limit of {} reached; refusing {socket_addr}",
+                        limit.unwrap_or_default()
+                    );
+                    return Ok(None);
+                }
+                None => None,
+            };
+            if !quiet {
+                info!("Client connected: {socket_addr}");
+            }
+            Ok(Some((
+                service,
+                TrackedStream::new(stream, socket_addr, metrics, idle_timeout)
+                    .with_permit(permit)
+                    .quiet(quiet),
+            )))
+        }
+    };
+
+    let on_process_error = |err| {
+        error!("Processing a request failed: {err}");
+    };
+
+    Ok(server
+        .serve_until(&on_connected, on_process_error, shutdown)
+        .await?)
+}
+
+/// Line settings for the RTU server's serial port
+#[derive(Debug, Clone, Copy)]
+pub struct SerialSettings {
+    pub baud: u32,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub data_bits: DataBits,
+    pub flow_control: FlowControl,
+}
+
 pub async fn run_rtu_server(
     device_path: &std::path::Path,
-    baud: u32,
-    parity: &Parity,
-    stop_bits: &StopBits,
-    data_bits: &DataBits,
+    settings: SerialSettings,
     data: Arc<tokio::sync::RwLock<ModbusData>>,
+    options: ServerOptions,
 ) -> anyhow::Result<()> {
     println!("Serial Configuration:");
-    println!("  Baud Rate: {baud}");
-    println!("  Parity: {:?}", parity);
-    println!("  Stop Bits: {:?}", stop_bits);
-    println!("  Data Bits: {:?}", data_bits);
+    println!("  Baud Rate: {}", settings.baud);
+    println!("  Parity: {:?}", settings.parity);
+    println!("  Stop Bits: {:?}", settings.stop_bits);
+    println!("  Data Bits: {:?}", settings.data_bits);
+    println!("  Flow Control: {:?}", settings.flow_control);
 
-    let builder = tokio_serial::new(device_path.to_string_lossy(), baud)
-        .parity((*parity).into())
-        .stop_bits((*stop_bits).into())
-        .data_bits((*data_bits).into());
+    let builder = tokio_serial::new(device_path.to_string_lossy(), settings.baud)
+        .parity(settings.parity.into())
+        .stop_bits(settings.stop_bits.into())
+        .data_bits(settings.data_bits.into())
+        .flow_control(settings.flow_control.into());
 
     match tokio_serial::SerialStream::open(&builder) {
         Ok(mut serial) => {
             // Disable exclusive access for virtual ports
             if let Err(e) = serial.set_exclusive(false) {
-                println!("Warning: Could not disable exclusive access: {e}");
+                warn!("Could not disable exclusive access: {e}");
             }
 
+            spawn_reset_on_sighup(data.clone())?;
+
             let rtu_server = rtu::Server::new(serial);
-            let service = ModbusService::new(data);
+            let service = BroadcastService(ModbusService::new(data, options));
             println!("Modbus RTU server listening on {}", device_path.display());
             println!("Press Ctrl+C to stop the server");
 
-            let serve_task = tokio::spawn(async move { rtu_server.serve_forever(service).await });
-
-            // Wait for Ctrl+C
-            tokio::signal::ctrl_c().await?;
-            println!("\nStopping RTU server...");
+            let mut serve_task =
+                tokio::spawn(async move { rtu_server.serve_forever(service).await });
 
-            // Abort the serve task
-            serve_task.abort();
-            println!("RTU server stopped");
-            Ok(())
+            // Run until Ctrl+C, but surface the error if the serve task ends first
+            tokio::select! {
+                result = &mut serve_task => {
+                    let error = match result {
+                        Ok(Ok(())) => anyhow::anyhow!("RTU server stopped unexpectedly"),
+                        Ok(Err(e)) => anyhow::anyhow!("RTU server failed: {e}"),
+                        Err(e) => anyhow::anyhow!("RTU server task panicked: {e}"),
+                    };
+                    error!("{error}");
+                    Err(error)
+                }
+                signal = tokio::signal::ctrl_c() => {
+                    signal?;
+                    println!("\nStopping RTU server...");
+                    serve_task.abort();
+                    println!("RTU server stopped");
+                    Ok(())
+                }
+            }
         }
         Err(e) => {
-            eprintln!(
+            let e = serial_open_error(e);
+            error!(
                 "Failed to open serial device {}: {}",
                 device_path.display(),
                 e
@@ -249,7 +987,12 @@ pub async fn run_rtu_server(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio_modbus::prelude::{ExceptionCode, Request, Response};
+    use crate::diag;
+
+    // Address a request to unit 1, as a client with the default --unit does
+    fn at_unit(request: Request<'static>) -> SlaveRequest<'static> {
+        SlaveRequest { slave: 1, request }
+    }
 
     #[test]
     fn test_modbus_data_new() {
@@ -261,14 +1004,14 @@ mod tests {
         assert_eq!(data.input_registers.len(), 400);
 
         // All coils and discrete inputs should be false initially
-        assert!(data.coils.iter().all(|&x| !x));
-        assert!(data.discrete_inputs.iter().all(|&x| !x));
+        assert!(data.coils.to_vec().iter().all(|&x| !x));
+        assert!(data.discrete_inputs.to_vec().iter().all(|&x| !x));
 
         // Holding and input registers should be initialized with address = value
-        for (addr, &value) in data.holding_registers.iter().enumerate() {
+        for (addr, &value) in data.holding_registers.to_vec().iter().enumerate() {
             assert_eq!(addr as u16, value);
         }
-        for (addr, &value) in data.input_registers.iter().enumerate() {
+        for (addr, &value) in data.input_registers.to_vec().iter().enumerate() {
             assert_eq!(addr as u16, value);
         }
     }
@@ -282,8 +1025,8 @@ mod tests {
         assert_eq!(data.holding_registers.len(), 3);
         assert_eq!(data.input_registers.len(), 4);
 
-        assert_eq!(data.holding_registers, [0, 1, 2]);
-        assert_eq!(data.input_registers, [0, 1, 2, 3]);
+        assert_eq!(data.holding_registers.to_vec(), [0, 1, 2]);
+        assert_eq!(data.input_registers.to_vec(), [0, 1, 2, 3]);
     }
 
     #[test]
@@ -296,6 +1039,129 @@ mod tests {
         assert!(data.input_registers.is_empty());
     }
 
+    #[test]
+    fn test_modbus_data_reset() {
+        let mut data = ModbusData::new(4, 4, 4, 4);
+        data.coils.set(1, true);
+        data.holding_registers.set(2, 999);
+        data.reset();
+
+        assert_eq!(data.coils.to_vec(), [false; 4]);
+        assert_eq!(data.holding_registers.to_vec(), [0, 1, 2, 3]);
+        assert_eq!(data.input_registers.len(), 4);
+    }
+
+    #[test]
+    fn test_modbus_data_heartbeat_wraps() {
+        let mut data = ModbusData::new(0, 0, 4, 0);
+        data.beat(2);
+        data.beat(2);
+        assert_eq!(data.holding_registers.to_vec(), [0, 1, 4, 3]);
+
+        data.holding_registers.set(3, u16::MAX);
+        data.beat(3);
+        assert_eq!(data.holding_registers.get(3), 0);
+    }
+
+    #[test]
+    fn test_modbus_data_sparse() {
+        let mut data = ModbusData::sparse(65535, 65535, 65535, 65535);
+        assert_eq!(data.holding_registers.len(), 65535);
+        assert_eq!(data.holding_registers.get(40000), 40000);
+        assert!(!data.coils.get(65534));
+
+        data.holding_registers.set(40000, 7);
+        data.coils.set(3, true);
+        assert_eq!(
+            read_range(&data.holding_registers, 39999, 3, false),
+            Ok(vec![39999, 7, 40001])
+        );
+        assert!(data.coils.get(3));
+
+        data.reset();
+        assert_eq!(data.holding_registers.get(40000), 40000);
+        assert!(!data.coils.get(3));
+        assert_eq!(data.input_registers.len(), 65535);
+    }
+
+    #[test]
+    fn test_fill_jitter_stays_near_the_pattern() {
+        let fill = |jitter| Fill {
+            pattern: Pattern::Ramp,
+            seed: 3,
+            jitter,
+        };
+        let smooth = ModbusData::new(0, 0, 50, 0).with_holding_fill(fill(0));
+        let noisy = ModbusData::new(0, 0, 50, 0).with_holding_fill(fill(5));
+        let (smooth, noisy) = (
+            smooth.holding_registers.to_vec(),
+            noisy.holding_registers.to_vec(),
+        );
+        assert_ne!(smooth, noisy);
+        // 5% of full scale either way, clamped at the ends of the range
+        for (&before, &after) in smooth.iter().zip(&noisy) {
+            assert!((before as i64 - after as i64).abs() <= 3276);
+        }
+        // The same seed gives the same noise, dense or sparse
+        let sparse = ModbusData::sparse(0, 0, 50, 0).with_holding_fill(fill(5));
+        assert_eq!(sparse.holding_registers.to_vec(), noisy);
+    }
+
+    #[test]
+    fn test_modbus_data_bit_fill_patterns() {
+        let data = ModbusData::new(5, 3, 0, 0)
+            .with_coil_fill(BitPattern::Alternating)
+            .with_discrete_fill(BitPattern::AllOn);
+        assert_eq!(data.coils.to_vec(), [true, false, true, false, true]);
+        assert_eq!(data.discrete_inputs.to_vec(), [true; 3]);
+
+        // Sparse areas report the pattern for unwritten addresses, and a reset restores it
+        let mut sparse = ModbusData::sparse(65535, 0, 0, 0).with_coil_fill(BitPattern::Alternating);
+        assert!(sparse.coils.get(40000));
+        assert!(!sparse.coils.get(40001));
+        sparse.coils.set(40000, false);
+        sparse.reset();
+        assert!(sparse.coils.get(40000));
+        assert!(!ModbusData::new(2, 0, 0, 0).coils.get(0));
+    }
+
+    #[test]
+    fn test_modbus_data_holding_fill_patterns() {
+        let fill = |pattern, seed| Fill {
+            pattern,
+            seed,
+            jitter: 0,
+        };
+        let data = ModbusData::new(0, 0, 5, 3).with_holding_fill(fill(Pattern::Ramp, 0));
+        assert_eq!(
+            data.holding_registers.to_vec(),
+            [0, 16383, 32767, 49151, 65535]
+        );
+        // Only holding registers follow the pattern
+        assert_eq!(data.input_registers.to_vec(), [0, 1, 2]);
+
+        let data = ModbusData::new(0, 0, 4, 0).with_holding_fill(fill(Pattern::Alternating, 0));
+        assert_eq!(data.holding_registers.to_vec(), [0, 65535, 0, 65535]);
+
+        // Random fills repeat for the same seed, dense or sparse, and survive a reset
+        let mut dense = ModbusData::new(0, 0, 100, 0).with_holding_fill(fill(Pattern::Random, 7));
+        let sparse = ModbusData::sparse(0, 0, 100, 0).with_holding_fill(fill(Pattern::Random, 7));
+        let other = ModbusData::new(0, 0, 100, 0).with_holding_fill(fill(Pattern::Random, 8));
+        assert_eq!(
+            dense.holding_registers.to_vec(),
+            sparse.holding_registers.to_vec()
+        );
+        assert_ne!(
+            dense.holding_registers.to_vec(),
+            other.holding_registers.to_vec()
+        );
+
+        let before = dense.holding_registers.to_vec();
+        dense.holding_registers.set(0, 1);
+        dense.reset();
+        assert_eq!(dense.holding_registers.to_vec(), before);
+    }
+
     #[tokio::test]
     async fn test_modbus_service_read_coils_valid() {
         let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(10, 10, 10, 10)));
@@ -303,14 +1169,14 @@ mod tests {
         // Set some coil values for testing
         {
             let mut data_lock = data.write().await;
-            data_lock.coils[0] = true;
-            data_lock.coils[2] = true;
+            data_lock.coils.set(0, true);
+            data_lock.coils.set(2, true);
         }
 
-        let service = ModbusService::new(data);
+        let service = ModbusService::new(data, ServerOptions::default());
         let request = Request::ReadCoils(0, 3);
 
-        let result = service.call(request).await;
+        let result = service.call(at_unit(request)).await;
         assert!(result.is_ok());
 
         if let Ok(Response::ReadCoils(coils)) = result {
@@ -326,24 +1192,256 @@ mod tests {
     #[tokio::test]
     async fn test_modbus_service_read_coils_out_of_bounds() {
         let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(5, 5, 5, 5)));
-        let service = ModbusService::new(data);
+        let service = ModbusService::new(data, ServerOptions::default());
 
         // Try to read beyond available coils
         let request = Request::ReadCoils(3, 5); // starts at 3, wants 5 coils = addresses 3,4,5,6,7 but only 0-4 exist
 
-        let result = service.call(request).await;
+        let result = service.call(at_unit(request)).await;
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), ExceptionCode::IllegalDataAddress);
     }
 
+    #[tokio::test]
+    async fn test_modbus_service_clamp_reads_zero_pads() {
+        let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(5, 5, 5, 5)));
+        let options = ServerOptions {
+            clamp_reads: true,
+            ..Default::default()
+        };
+        let service = ModbusService::new(data, options);
+
+        let result = service
+            .call(at_unit(Request::ReadHoldingRegisters(3, 4)))
+            .await;
+        assert_eq!(result, Ok(Response::ReadHoldingRegisters(vec![3, 4, 0, 0])));
+
+        let result = service.call(at_unit(Request::ReadCoils(4, 3))).await;
+        assert_eq!(result, Ok(Response::ReadCoils(vec![false, false, false])));
+    }
+
+    #[tokio::test]
+    async fn test_modbus_service_clamp_reads_rejects_start_out_of_range() {
+        let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(5, 5, 5, 5)));
+        let options = ServerOptions {
+            clamp_reads: true,
+            ..Default::default()
+        };
+        let service = ModbusService::new(data, options);
+
+        let result = service
+            .call(at_unit(Request::ReadInputRegisters(5, 1)))
+            .await;
+        assert_eq!(result, Err(ExceptionCode::IllegalDataAddress));
+    }
+
+    #[tokio::test]
+    async fn test_modbus_service_fails_first_requests() {
+        let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(5, 5, 5, 5)));
+        let options = ServerOptions {
+            busy_requests: Arc::new(AtomicUsize::new(2)),
+            ..Default::default()
+        };
+        // Every connection gets a clone of the service; the count is shared between them
+        let first = ModbusService::new(data.clone(), options);
+        let second = first.clone();
+
+        let read = || at_unit(Request::ReadHoldingRegisters(0, 1));
+        assert_eq!(
+            first.call(read()).await,
+            Err(ExceptionCode::ServerDeviceBusy)
+        );
+        assert_eq!(
+            second.call(read()).await,
+            Err(ExceptionCode::ServerDeviceBusy)
+        );
+        assert_eq!(
+            first.call(read()).await,
+            Ok(Response::ReadHoldingRegisters(vec![0]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_modbus_service_reads_fifo_queue() {
+        use crate::fifo::{parse_response, request_data};
+        use std::borrow::Cow;
+
+        let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(5, 5, 5, 5)));
+        data.write().await.push_fifo(2, 0x01B8);
+        data.write().await.push_fifo(2, 0x1284);
+        let service = ModbusService::new(data.clone(), ServerOptions::default());
+        let read = |pointer: u16| {
+            service.call(at_unit(Request::Custom(
+                READ_FIFO_QUEUE,
+                Cow::Owned(request_data(pointer)),
+            )))
+        };
+
+        let Ok(Response::Custom(READ_FIFO_QUEUE, bytes)) = read(2).await else {
+            panic!("Expected a FIFO queue response");
+        };
+        assert_eq!(parse_response(&bytes).unwrap(), [0x01B8, 0x1284]);
+
+        // A pointer without a queue reads as empty; one outside the holding area is rejected
+        let Ok(Response::Custom(_, bytes)) = read(3).await else {
+            panic!("Expected a FIFO queue response");
+        };
+        assert!(parse_response(&bytes).unwrap().is_empty());
+        assert_eq!(read(5).await, Err(ExceptionCode::IllegalDataAddress));
+
+        // Writes to a --fifo register are queued behind it
+        let service = ModbusService::new(
+            data.clone(),
+            ServerOptions {
+                fifo_pointers: vec![1],
+                ..Default::default()
+            },
+        );
+        service
+            .call(at_unit(Request::WriteMultipleRegisters(
+                0,
+                Cow::Owned(vec![10, 11, 12]),
+            )))
+            .await
+            .unwrap();
+        service
+            .call(at_unit(Request::WriteSingleRegister(1, 21)))
+            .await
+            .unwrap();
+        assert_eq!(data.read().await.fifo_queues[&1], [11, 21]);
+
+        // Full queues drop their oldest value
+        for value in 0..40 {
+            data.write().await.push_fifo(4, value);
+        }
+        let queue = &data.read().await.fifo_queues[&4];
+        assert_eq!((queue.len(), queue.front()), (MAX_FIFO_COUNT, Some(&9)));
+    }
+
+    #[test]
+    fn test_parse_write_delay() {
+        let every = parse_write_delay("200").unwrap();
+        assert_eq!(every.coils, Some(Duration::from_millis(200)));
+        assert_eq!(every.holding, Some(Duration::from_millis(200)));
+
+        let holding = parse_write_delay("holding=50").unwrap();
+        assert_eq!(holding.coils, None);
+        assert_eq!(holding.holding, Some(Duration::from_millis(50)));
+
+        assert!(parse_write_delay("input=50").is_err());
+        assert!(parse_write_delay("coil=fast").is_err());
+        assert!(parse_write_delay("-5").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_delay_applies_write_after_the_delay() {
+        let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(5, 5, 5, 5)));
+        let options = ServerOptions {
+            write_delay: parse_write_delay("holding=100").unwrap(),
+            ..Default::default()
+        };
+        let service = ModbusService::new(data, options);
+
+        let started = std::time::Instant::now();
+        let write = tokio::spawn({
+            let service = service.clone();
+            async move {
+                service
+                    .call(at_unit(Request::WriteSingleRegister(2, 7)))
+                    .await
+            }
+        });
+        // A read during the delay still sees the old value
+        let before = service
+            .call(at_unit(Request::ReadHoldingRegisters(2, 1)))
+            .await;
+        assert_eq!(before, Ok(Response::ReadHoldingRegisters(vec![2])));
+
+        write.await.unwrap().unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        let after = service
+            .call(at_unit(Request::ReadHoldingRegisters(2, 1)))
+            .await;
+        assert_eq!(after, Ok(Response::ReadHoldingRegisters(vec![7])));
+    }
+
+    #[tokio::test]
+    async fn test_modbus_service_publishes_events() {
+        let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(5, 5, 5, 5)));
+        let events = EventStream::default();
+        let mut receiver = events.subscribe();
+        let options = ServerOptions {
+            events: Some(events),
+            ..Default::default()
+        };
+        let service = ModbusService::new(data, options);
+
+        service
+            .call(at_unit(Request::ReadInputRegisters(1, 2)))
+            .await
+            .unwrap();
+        service
+            .call(at_unit(Request::WriteSingleRegister(3, 7)))
+            .await
+            .unwrap();
+        // Rejected requests are not events
+        let _ = service.call(at_unit(Request::ReadCoils(4, 3))).await;
+
+        let read = receiver.try_recv().unwrap();
+        assert_eq!(read.kind, "read");
+        assert_eq!(
+            read.data,
+            r#"{"unit":1,"area":"input","address":1,"quantity":2}"#
+        );
+        let write = receiver.try_recv().unwrap();
+        assert_eq!(write.kind, "write");
+        assert_eq!(
+            write.data,
+            r#"{"unit":1,"area":"holding","address":3,"values":[7]}"#
+        );
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_modbus_service_base_addr() {
+        let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(10, 10, 10, 10)));
+        let options = ServerOptions {
+            base_addr: 40000,
+            ..Default::default()
+        };
+        let service = ModbusService::new(data.clone(), options);
+
+        let result = service
+            .call(at_unit(Request::ReadHoldingRegisters(40002, 2)))
+            .await;
+        assert_eq!(result, Ok(Response::ReadHoldingRegisters(vec![2, 3])));
+
+        // Below the base and past the end of the window are both rejected
+        let result = service
+            .call(at_unit(Request::ReadHoldingRegisters(39999, 1)))
+            .await;
+        assert_eq!(result, Err(ExceptionCode::IllegalDataAddress));
+        let result = service
+            .call(at_unit(Request::ReadHoldingRegisters(40009, 2)))
+            .await;
+        assert_eq!(result, Err(ExceptionCode::IllegalDataAddress));
+
+        // Writes echo the protocol address but store at the offset index
+        let result = service
+            .call(at_unit(Request::WriteSingleRegister(40001, 999)))
+            .await;
+        assert_eq!(result, Ok(Response::WriteSingleRegister(40001, 999)));
+        assert_eq!(data.read().await.holding_registers.get(1), 999);
+    }
+
     #[tokio::test]
     async fn test_modbus_service_read_holding_registers() {
         let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(10, 10, 10, 10)));
-        let service = ModbusService::new(data);
+        let service = ModbusService::new(data, ServerOptions::default());
 
         let request = Request::ReadHoldingRegisters(5, 3);
 
-        let result = service.call(request).await;
+        let result = service.call(at_unit(request)).await;
         assert!(result.is_ok());
 
         if let Ok(Response::ReadHoldingRegisters(registers)) = result {
@@ -359,11 +1457,11 @@ mod tests {
     #[tokio::test]
     async fn test_modbus_service_write_single_coil() {
         let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(10, 10, 10, 10)));
-        let service = ModbusService::new(data.clone());
+        let service = ModbusService::new(data.clone(), ServerOptions::default());
 
         let request = Request::WriteSingleCoil(3, true);
 
-        let result = service.call(request).await;
+        let result = service.call(at_unit(request)).await;
         assert!(result.is_ok());
 
         if let Ok(Response::WriteSingleCoil(addr, value)) = result {
@@ -375,17 +1473,17 @@ mod tests {
 
         // Verify the coil was actually set
         let data_lock = data.read().await;
-        assert!(data_lock.coils[3]);
+        assert!(data_lock.coils.get(3));
     }
 
     #[tokio::test]
     async fn test_modbus_service_write_single_register() {
         let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(10, 10, 10, 10)));
-        let service = ModbusService::new(data.clone());
+        let service = ModbusService::new(data.clone(), ServerOptions::default());
 
         let request = Request::WriteSingleRegister(2, 12345);
 
-        let result = service.call(request).await;
+        let result = service.call(at_unit(request)).await;
         assert!(result.is_ok());
 
         if let Ok(Response::WriteSingleRegister(addr, value)) = result {
@@ -397,18 +1495,132 @@ mod tests {
 
         // Verify the register was actually set
         let data_lock = data.read().await;
-        assert_eq!(data_lock.holding_registers[2], 12345);
+        assert_eq!(data_lock.holding_registers.get(2), 12345);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_writes_are_applied_without_a_response() {
+        let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(10, 10, 10, 10)));
+        let options = ServerOptions::default();
+        let diagnostics = options.diagnostics.clone();
+        let service = BroadcastService(ModbusService::new(data.clone(), options));
+        let broadcast = |request| SlaveRequest { slave: 0, request };
+
+        let result = service
+            .call(broadcast(Request::WriteSingleRegister(2, 77)))
+            .await;
+        assert_eq!(result, Ok(None));
+        assert_eq!(data.read().await.holding_registers.get(2), 77);
+
+        // Failed broadcast writes stay silent too, and reads are ignored
+        let result = service
+            .call(broadcast(Request::WriteSingleRegister(50, 1)))
+            .await;
+        assert_eq!(result, Ok(None));
+        let result = service
+            .call(broadcast(Request::ReadHoldingRegisters(2, 1)))
+            .await;
+        assert_eq!(result, Ok(None));
+        let reply = diag::response_data(0x0F, 3);
+        assert_eq!(diagnostics.respond(&diag::request_data(0x0F)), Ok(reply));
+
+        // Other units are answered as usual
+        let result = service
+            .call(at_unit(Request::ReadHoldingRegisters(2, 1)))
+            .await;
+        assert_eq!(result, Ok(Some(Response::ReadHoldingRegisters(vec![77]))));
+    }
+
+    #[tokio::test]
+    async fn test_requests_for_other_units_are_ignored() {
+        let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(10, 10, 10, 10)));
+        let options = ServerOptions {
+            unit: Some(3),
+            ..Default::default()
+        };
+        let service = BroadcastService(ModbusService::new(data.clone(), options));
+        let to = |slave, request| SlaveRequest { slave, request };
+
+        let result = service
+            .call(to(1, Request::WriteSingleRegister(2, 77)))
+            .await;
+        assert_eq!(result, Ok(None));
+        assert_eq!(data.read().await.holding_registers.get(2), 2);
+
+        // Its own unit, broadcasts and the TCP direct unit are still served
+        service
+            .call(to(3, Request::WriteSingleRegister(2, 77)))
+            .await
+            .unwrap();
+        service
+            .call(to(0, Request::WriteSingleRegister(3, 78)))
+            .await
+            .unwrap();
+        let result = service
+            .call(to(MBAP_DIRECT_UNIT, Request::ReadHoldingRegisters(2, 2)))
+            .await;
+        assert_eq!(
+            result,
+            Ok(Some(Response::ReadHoldingRegisters(vec![77, 78])))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_writes_leave_fail_first_count() {
+        let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(10, 10, 10, 10)));
+        let options = ServerOptions {
+            busy_requests: Arc::new(AtomicUsize::new(1)),
+            ..Default::default()
+        };
+        let busy_requests = options.busy_requests.clone();
+        let service = BroadcastService(ModbusService::new(data.clone(), options));
+        let broadcast = |request| SlaveRequest { slave: 0, request };
+
+        // A Busy answer to a broadcast would never be seen, so the writes go through
+        for request in [
+            Request::WriteSingleRegister(2, 0x00F0),
+            Request::MaskWriteRegister(2, 0xFF0F, 0x0050),
+        ] {
+            assert_eq!(service.call(broadcast(request)).await, Ok(None));
+        }
+        assert_eq!(busy_requests.load(Ordering::Relaxed), 1);
+        assert_eq!(data.read().await.holding_registers.get(2), 0x0050);
+
+        let result = service
+            .call(at_unit(Request::ReadHoldingRegisters(2, 1)))
+            .await;
+        assert_eq!(result, Err(ExceptionCode::ServerDeviceBusy));
+        assert_eq!(busy_requests.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_modbus_service_mask_write_register() {
+        let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(0, 0, 5, 0)));
+        let service = ModbusService::new(data.clone(), ServerOptions::default());
+        data.write().await.holding_registers.set(1, 0x0012);
+
+        // The example from the specification: (0x12 AND 0xF2) OR (0x25 AND NOT 0xF2) = 0x17
+        let result = service
+            .call(at_unit(Request::MaskWriteRegister(1, 0x00F2, 0x0025)))
+            .await;
+        assert_eq!(result, Ok(Response::MaskWriteRegister(1, 0x00F2, 0x0025)));
+        assert_eq!(data.read().await.holding_registers.get(1), 0x0017);
+
+        let result = service
+            .call(at_unit(Request::MaskWriteRegister(5, 0, 0)))
+            .await;
+        assert_eq!(result, Err(ExceptionCode::IllegalDataAddress));
     }
 
     #[tokio::test]
     async fn test_modbus_service_write_multiple_coils() {
         let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(10, 10, 10, 10)));
-        let service = ModbusService::new(data.clone());
+        let service = ModbusService::new(data.clone(), ServerOptions::default());
 
         let values = [true, false, true];
         let request = Request::WriteMultipleCoils(1, values.to_vec().into());
 
-        let result = service.call(request).await;
+        let result = service.call(at_unit(request)).await;
         assert!(result.is_ok());
 
         if let Ok(Response::WriteMultipleCoils(addr, qty)) = result {
@@ -420,8 +1632,90 @@ mod tests {
 
         // Verify the coils were actually set
         let data_lock = data.read().await;
-        assert!(data_lock.coils[1]);
-        assert!(!data_lock.coils[2]);
-        assert!(data_lock.coils[3]);
+        assert!(data_lock.coils.get(1));
+        assert!(!data_lock.coils.get(2));
+        assert!(data_lock.coils.get(3));
+    }
+
+    #[tokio::test]
+    async fn test_tracked_stream_closes_idle_connection() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, peer) = listener.accept().await.unwrap();
+        let metrics = Arc::new(ServerMetrics::default());
+        let mut tracked =
+            TrackedStream::new(stream, peer, metrics, Some(Duration::from_millis(50)));
+
+        // Traffic is passed through and resets the idle clock
+        client.write_all(&[1, 2]).await.unwrap();
+        let mut buffer = [0u8; 2];
+        tracked.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(buffer, [1, 2]);
+
+        // Silence past the timeout fails the read so the server drops the connection
+        let error = tracked.read(&mut buffer).await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn test_tracked_stream_frees_connection_slot_on_close() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, peer) = listener.accept().await.unwrap();
+        let slots = Arc::new(Semaphore::new(1));
+        let permit = slots.clone().try_acquire_owned().unwrap();
+
+        let tracked =
+            TrackedStream::new(stream, peer, Arc::default(), None).with_permit(Some(permit));
+        assert!(slots.clone().try_acquire_owned().is_err());
+        drop(tracked);
+        assert!(slots.try_acquire_owned().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_encoded_writes_round_trip_in_every_word_order() {
+        use crate::cli::{ValueFormat, WordOrder};
+        use crate::table::{apply_byte_swap, decode_rows, encode_values, Decoded};
+
+        let data = Arc::new(tokio::sync::RwLock::new(ModbusData::new(10, 10, 10, 10)));
+        let service = ModbusService::new(data, ServerOptions::default());
+        let values = vec!["-1234.5".to_string(), "12.5".to_string()];
+
+        // ABCD, CDAB, BADC and DCBA
+        for (word_order, byte_swap) in [
+            (WordOrder::Big, false),
+            (WordOrder::Little, false),
+            (WordOrder::Big, true),
+            (WordOrder::Little, true),
+        ] {
+            let registers =
+                encode_values(&values, ValueFormat::Float32, word_order, byte_swap).unwrap();
+            let request = Request::WriteMultipleRegisters(0, registers.into());
+            service.call(at_unit(request)).await.unwrap();
+
+            let Ok(Response::ReadHoldingRegisters(read)) = service
+                .call(at_unit(Request::ReadHoldingRegisters(0, 4)))
+                .await
+            else {
+                panic!("Expected ReadHoldingRegisters response");
+            };
+            let rows: Vec<(u16, u16)> = (0..).zip(read).collect();
+            let decoded = decode_rows(
+                &apply_byte_swap(&rows, byte_swap),
+                ValueFormat::Float32,
+                word_order,
+            );
+            assert_eq!(
+                decoded.iter().map(|row| row.value).collect::<Vec<_>>(),
+                [Decoded::Float32(-1234.5), Decoded::Float32(12.5)],
+                "{word_order:?} byte_swap={byte_swap}"
+            );
+        }
     }
 }
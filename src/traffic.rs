@@ -0,0 +1,119 @@
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::table::group_thousands;
+
+// Bytes every client transport has written and read since the process started
+static BYTES_SENT: AtomicU64 = AtomicU64::new(0);
+static BYTES_RECEIVED: AtomicU64 = AtomicU64::new(0);
+// Set by --stats or --verbose; the totals are only printed when asked for
+static TRAFFIC_SUMMARY: AtomicBool = AtomicBool::new(false);
+
+/// Client transport that adds every byte it carries to the session totals: whole frames, so
+/// the MBAP header over TCP and UDP, and the unit id and CRC over RTU
+#[derive(Debug)]
+pub struct ByteCount<T> {
+    inner: T,
+}
+
+impl<T> ByteCount<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for ByteCount<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut self.get_mut().inner).poll_read(cx, buf))?;
+        let read = buf.filled().len() - before;
+        BYTES_RECEIVED.fetch_add(read as u64, Ordering::Relaxed);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for ByteCount<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let written = ready!(Pin::new(&mut self.get_mut().inner).poll_write(cx, buf))?;
+        BYTES_SENT.fetch_add(written as u64, Ordering::Relaxed);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Print the traffic totals when the command ends
+pub fn enable_traffic_summary() {
+    TRAFFIC_SUMMARY.store(true, Ordering::Relaxed);
+}
+
+/// The session's traffic as `96 bytes sent, 1,130 bytes received`, if a summary was asked for
+pub fn traffic_summary() -> Option<String> {
+    TRAFFIC_SUMMARY.load(Ordering::Relaxed).then(|| {
+        format_traffic(
+            BYTES_SENT.load(Ordering::Relaxed),
+            BYTES_RECEIVED.load(Ordering::Relaxed),
+        )
+    })
+}
+
+fn format_traffic(sent: u64, received: u64) -> String {
+    format!(
+        "{} bytes sent, {} bytes received",
+        group_thousands(sent),
+        group_thousands(received)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_byte_count_adds_to_totals() {
+        let (client, mut device) = tokio::io::duplex(64);
+        let mut client = ByteCount::new(client);
+        let (sent, received) = (
+            BYTES_SENT.load(Ordering::Relaxed),
+            BYTES_RECEIVED.load(Ordering::Relaxed),
+        );
+
+        client.write_all(&[0x01, 0x03, 0x00, 0x00]).await.unwrap();
+        client.flush().await.unwrap();
+        let mut request = [0u8; 4];
+        device.read_exact(&mut request).await.unwrap();
+        device.write_all(&[0x01, 0x03]).await.unwrap();
+        let mut response = [0u8; 2];
+        client.read_exact(&mut response).await.unwrap();
+
+        // Other tests may count traffic concurrently, so only a lower bound is certain
+        assert!(BYTES_SENT.load(Ordering::Relaxed) >= sent + 4);
+        assert!(BYTES_RECEIVED.load(Ordering::Relaxed) >= received + 2);
+    }
+
+    #[test]
+    fn test_format_traffic() {
+        assert_eq!(
+            format_traffic(96, 1130),
+            "96 bytes sent, 1,130 bytes received"
+        );
+    }
+}
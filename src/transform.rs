@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+/// Per-register conversion to engineering units, `value * scale + offset`, keyed by address
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    entries: HashMap<u16, (f64, f64)>,
+}
+
+impl Transform {
+    /// Parse `address,scale,offset` rows, one per line:
+    ///
+    /// ```text
+    /// address,scale,offset
+    /// 0,0.1,0      # temperature in tenths of a degree
+    /// 4,1,-40
+    /// ```
+    ///
+    /// A leading `address,...` header line is skipped and the offset may be left out
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut entries = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() || (number == 0 && line.starts_with("address")) {
+                continue;
+            }
+
+            let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+            let (addr, scale, offset) = match cells.as_slice() {
+                [addr, scale] => (addr, scale, &"0"),
+                [addr, scale, offset] => (addr, scale, offset),
+                _ => {
+                    return Err(format!(
+                        "line {}: expected address,scale,offset",
+                        number + 1
+                    ))
+                }
+            };
+            let addr = addr
+                .parse::<u16>()
+                .map_err(|_| format!("line {}: invalid address '{addr}'", number + 1))?;
+            let number_cell = |cell: &str, what: &str| {
+                cell.parse::<f64>()
+                    .ok()
+                    .filter(|value| value.is_finite())
+                    .ok_or_else(|| format!("line {}: invalid {what} '{cell}'", number + 1))
+            };
+            let scale = number_cell(scale, "scale")?;
+            let offset = number_cell(offset, "offset")?;
+            if entries.insert(addr, (scale, offset)).is_some() {
+                return Err(format!(
+                    "line {}: address {addr} is listed twice",
+                    number + 1
+                ));
+            }
+        }
+
+        if entries.is_empty() {
+            return Err("transform has no address,scale,offset rows".to_string());
+        }
+        Ok(Self { entries })
+    }
+
+    /// The value in engineering units; addresses without a row pass through unchanged
+    pub fn apply(&self, addr: u16, value: f64) -> f64 {
+        match self.entries.get(&addr) {
+            Some(&(scale, offset)) => value * scale + offset,
+            None => value,
+        }
+    }
+}
+
+/// Display a transformed value, rounded to 6 decimal places so 234 * 0.1 shows as 23.4
+pub fn format_transformed(value: f64) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    let rounded = (value * 1e6).round() / 1e6;
+    // Avoid printing "-0" for tiny negative results
+    if rounded == 0.0 {
+        "0".to_string()
+    } else {
+        rounded.to_string()
+    }
+}
+
+// Value parser for `--transform`: read and parse the file up front so errors surface before connecting
+pub fn parse_transform_file(path: &str) -> Result<Transform, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read transform file '{path}': {e}"))?;
+    Transform::parse(&text).map_err(|e| format!("Invalid transform file '{path}': {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_apply_transform() {
+        let transform =
+            Transform::parse("address,scale,offset\n0,0.1,0 # temp\n\n4, 1, -40\n7,2").unwrap();
+        assert_eq!(format_transformed(transform.apply(0, 234.0)), "23.4");
+        assert_eq!(transform.apply(4, 65.0), 25.0);
+        assert_eq!(transform.apply(7, 3.0), 6.0);
+        // Unlisted addresses are left raw
+        assert_eq!(transform.apply(1, 17.0), 17.0);
+    }
+
+    #[test]
+    fn test_parse_transform_errors() {
+        assert!(Transform::parse("").is_err());
+        assert!(Transform::parse("address,scale,offset").is_err());
+        assert!(Transform::parse("x,1,0").is_err());
+        assert!(Transform::parse("0,abc,0").is_err());
+        assert!(Transform::parse("0,1,0,5").is_err());
+        assert!(Transform::parse("0,1\n0,2").is_err());
+    }
+
+    #[test]
+    fn test_format_transformed() {
+        assert_eq!(format_transformed(-0.0000001), "0");
+        assert_eq!(format_transformed(12.5), "12.5");
+        assert_eq!(format_transformed(1500.0), "1500");
+    }
+}
@@ -0,0 +1,100 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::UdpSocket;
+
+// Largest Modbus TCP ADU: 7-byte MBAP header plus a 253-byte PDU
+const MAX_ADU_LEN: usize = 260;
+
+/// Byte-stream adapter over a connected UDP socket so the Modbus TCP framing can run over datagrams.
+///
+/// Each write is sent as one datagram and each received datagram is handed out as stream bytes.
+/// UDP can drop or reorder datagrams. The MBAP transaction ID still guards against stale or
+/// reordered replies, but a dropped datagram is only noticed through the operation timeout, so
+/// operations over UDP are resent after a timeout, up to `--retries` times; keep `--timeout` short.
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: UdpSocket,
+    pending: Vec<u8>,
+    offset: usize,
+}
+
+impl UdpTransport {
+    pub fn new(socket: UdpSocket) -> Self {
+        Self {
+            socket,
+            pending: Vec::new(),
+            offset: 0,
+        }
+    }
+}
+
+impl AsyncRead for UdpTransport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.offset >= this.pending.len() {
+            let mut datagram = [0u8; MAX_ADU_LEN];
+            let mut datagram_buf = ReadBuf::new(&mut datagram);
+            ready!(this.socket.poll_recv(cx, &mut datagram_buf))?;
+            this.pending = datagram_buf.filled().to_vec();
+            this.offset = 0;
+        }
+
+        let len = buf.remaining().min(this.pending.len() - this.offset);
+        buf.put_slice(&this.pending[this.offset..this.offset + len]);
+        this.offset += len;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for UdpTransport {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.socket.poll_send(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_udp_transport_round_trip() {
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(peer.local_addr().unwrap()).await.unwrap();
+        let mut transport = UdpTransport::new(socket);
+
+        // A write leaves as a single datagram
+        transport.write_all(&[0, 1, 0, 0, 0, 6]).await.unwrap();
+        let mut received = [0u8; MAX_ADU_LEN];
+        let (len, from) = peer.recv_from(&mut received).await.unwrap();
+        assert_eq!(&received[..len], [0, 1, 0, 0, 0, 6]);
+
+        // A reply datagram can be consumed in smaller reads
+        peer.send_to(&[1, 2, 3, 4], from).await.unwrap();
+        let mut head = [0u8; 3];
+        transport.read_exact(&mut head).await.unwrap();
+        assert_eq!(head, [1, 2, 3]);
+        let mut tail = [0u8; 1];
+        transport.read_exact(&mut tail).await.unwrap();
+        assert_eq!(tail, [4]);
+    }
+}
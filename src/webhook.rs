@@ -0,0 +1,225 @@
+use serde::Serialize;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// Give up on a slow webhook receiver after this long; the Modbus response never waits for it
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Plain `http://host[:port]/path` endpoint that receives write notifications
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookUrl {
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl std::fmt::Display for WebhookUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "http://{}:{}{}", self.host, self.port, self.path)
+    }
+}
+
+pub fn parse_webhook_url(s: &str) -> Result<WebhookUrl, String> {
+    let rest = s
+        .strip_prefix("http://")
+        .ok_or_else(|| format!("Invalid webhook URL '{s}': only http:// URLs are supported"))?;
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.ends_with(']') => {
+            let port = port
+                .parse()
+                .map_err(|_| format!("Invalid webhook URL '{s}': bad port '{port}'"))?;
+            (host, port)
+        }
+        _ => (authority, 80),
+    };
+    if host.is_empty() {
+        return Err(format!("Invalid webhook URL '{s}': missing host"));
+    }
+    Ok(WebhookUrl {
+        host: host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string(),
+        port,
+        path: path.to_string(),
+    })
+}
+
+/// JSON body describing one write, e.g. {"unit":1,"area":"holding","address":100,"values":[1,2]}
+#[derive(Debug, Serialize)]
+pub struct WriteEvent<'a, V> {
+    pub unit: u8,
+    pub area: &'a str,
+    pub address: u16,
+    pub values: &'a [V],
+}
+
+/// JSON body for setting or clearing one bit of a holding register with `--addr register.bit`
+#[derive(Debug, Serialize)]
+pub struct WriteBitEvent {
+    pub unit: u8,
+    pub area: &'static str,
+    pub address: u16,
+    pub bit: u8,
+    pub value: bool,
+}
+
+// JSON body describing one read, e.g. {"unit":1,"area":"holding","address":100,"quantity":4}
+#[derive(Debug, Serialize)]
+struct ReadEvent<'a> {
+    unit: u8,
+    area: &'a str,
+    address: u16,
+    quantity: u16,
+}
+
+pub fn write_event<'a, V>(
+    unit: u8,
+    area: &'a str,
+    address: u16,
+    values: &'a [V],
+) -> WriteEvent<'a, V> {
+    WriteEvent {
+        unit,
+        area,
+        address,
+        values,
+    }
+}
+
+pub fn write_bit_event(unit: u8, address: u16, bit: u8, value: bool) -> WriteBitEvent {
+    WriteBitEvent {
+        unit,
+        area: "holding",
+        address,
+        bit,
+        value,
+    }
+}
+
+pub fn write_event_json(unit: u8, area: &str, address: u16, values: &[impl Serialize]) -> String {
+    serde_json::to_string(&write_event(unit, area, address, values))
+        .expect("events serialize to JSON")
+}
+
+pub fn read_event_json(unit: u8, area: &str, address: u16, quantity: u16) -> String {
+    serde_json::to_string(&ReadEvent {
+        unit,
+        area,
+        address,
+        quantity,
+    })
+    .expect("events serialize to JSON")
+}
+
+// POST the body in the background; failures are logged and never reach the Modbus client
+pub fn notify(url: &WebhookUrl, body: String) {
+    let url = url.clone();
+    tokio::spawn(async move {
+        match tokio::time::timeout(WEBHOOK_TIMEOUT, post_json(&url, &body)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => eprintln!("Webhook POST to {url} failed: {e}"),
+            Err(_) => eprintln!(
+                "Webhook POST to {url} timed out after {} seconds",
+                WEBHOOK_TIMEOUT.as_secs()
+            ),
+        }
+    });
+}
+
+async fn post_json(url: &WebhookUrl, body: &str) -> anyhow::Result<()> {
+    let mut stream = TcpStream::connect((url.host.as_str(), url.port)).await?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        url.path,
+        url.host,
+        url.port,
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let status_line = String::from_utf8_lossy(&response);
+    let status = status_line.split_whitespace().nth(1).unwrap_or("");
+    if status.starts_with('2') {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "unexpected response '{}'",
+            status_line.lines().next().unwrap_or("")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_parse_webhook_url() {
+        let url = parse_webhook_url("http://localhost:8080/hooks/modbus").unwrap();
+        assert_eq!(url.host, "localhost");
+        assert_eq!(url.port, 8080);
+        assert_eq!(url.path, "/hooks/modbus");
+
+        let url = parse_webhook_url("http://10.0.0.5").unwrap();
+        assert_eq!((url.port, url.path.as_str()), (80, "/"));
+
+        assert!(parse_webhook_url("https://example.com/").is_err());
+        assert!(parse_webhook_url("http://:80/").is_err());
+        assert!(parse_webhook_url("http://host:port/").is_err());
+    }
+
+    #[test]
+    fn test_write_event_json() {
+        assert_eq!(
+            write_event_json(1, "holding", 100, &[1u16, 2]),
+            r#"{"unit":1,"area":"holding","address":100,"values":[1,2]}"#
+        );
+        assert_eq!(
+            write_event_json(3, "coils", 0, &[true, false]),
+            r#"{"unit":3,"area":"coils","address":0,"values":[true,false]}"#
+        );
+    }
+
+    #[test]
+    fn test_read_event_json() {
+        assert_eq!(
+            read_event_json(1, "input", 5, 2),
+            r#"{"unit":1,"area":"input","address":5,"quantity":2}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_json_sends_body() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = WebhookUrl {
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+            path: "/writes".to_string(),
+        };
+
+        let receiver = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0u8; 1024];
+            let len = stream.read(&mut buffer).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&buffer[..len]).to_string()
+        });
+
+        post_json(&url, r#"{"unit":1}"#).await.unwrap();
+        let request = receiver.await.unwrap();
+        assert!(request.starts_with("POST /writes HTTP/1.1\r\n"));
+        assert!(request.ends_with("\r\n\r\n{\"unit\":1}"));
+    }
+}